@@ -13,15 +13,72 @@
 // - Expose simple methods for register, login and upload that return
 //   `anyhow::Result` with helpful context messages on failure.
 
+use crate::cache::{ResponseCache, DEFAULT_CACHE_TTL_SECS};
+use crate::health::HealthCheck;
+use crate::backend::Backend;
+use crate::signing::{sign_request, unix_now};
+use crate::profile::{Profile, ProfilePatch};
+use crate::proxy::proxy_from_env;
+use crate::query::QueryParams;
+use crate::retry::{is_retryable_error, RetryPolicy};
+use crate::state::StateStore;
 use anyhow::{Context, Result};
-use reqwest::blocking::{Client, multipart};
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::blocking::{Client, RequestBuilder, Response, multipart};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+// Re-exported so callers of `ApiClient::request` don't need reqwest directly.
+pub use reqwest::Method;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
 use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use serde_json::json;
 
+// JSON keys whose values must never reach the debug dump in cleartext.
+const REDACTED_KEYS: &[&str] = &["contrasena", "password", "token", "access_token", "refresh_token", "codigo"];
+/// Legacy saved-token file, replaced by the state file (see `state.rs`).
+pub const TOKEN_FILE_NAME: &str = ".neumodiag_token";
+/// Legacy token metadata file, replaced by the state file.
+pub const TOKEN_META_FILE_NAME: &str = ".neumodiag_token.meta";
+// Download errors: the user has no picture, or the body is not one.
+const NO_PICTURE_MSG: &str = "No hay una foto de perfil para descargar";
+const NOT_AN_IMAGE_MSG: &str = "La respuesta del servidor no es una imagen; no se guardó nada";
+// Shown when a successful response is an HTML page: the URL most likely
+// points at the web front-end instead of the API gateway.
+const HTML_RESPONSE_MSG: &str = "El servidor respondió HTML; ¿apunta la URL al API y no a la interfaz web?";
+// Redirects followed per request before giving up. Kept small: a healthy
+// gateway needs at most one or two hops.
+const DEFAULT_MAX_REDIRECTS: usize = 3;
+/// Per-request timeout in seconds unless `--timeout` says otherwise
+/// (the same default reqwest's blocking client uses).
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// Seconds to wait for the TCP/TLS connection unless `--connect-timeout`
+/// says otherwise.
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+/// Port of the local gateway used when no URL is configured;
+/// `API_GATEWAY_PORT` replaces it.
+pub const DEFAULT_GATEWAY_PORT: u16 = 8080;
+/// Error for operations refused by `--read-only` (see `with_read_only`).
+pub const READ_ONLY_MSG: &str =
+    "Modo solo lectura: esta operación modifica datos y está deshabilitada (--read-only).";
+// Response headers a gateway may use to identify a request in its logs.
+const CORRELATION_HEADERS: &[&str] = &["x-request-id", "x-correlation-id"];
+// Response headers checked, in order, for the token when a login reply
+// has none in its body (see `with_token_header`).
+const DEFAULT_TOKEN_HEADERS: &[&str] = &["authorization", "x-auth-token"];
+/// Request header naming the role the user acts as (see
+/// `set_active_role`).
+pub const ACTIVE_ROLE_HEADER: &str = "x-active-role";
+/// Request header carrying the CSRF token (see `with_csrf`).
+pub const CSRF_HEADER: &str = "x-csrf-token";
+// Endpoint that hands out CSRF tokens.
+const CSRF_PATH: &str = "/csrf";
+
 /// Simple API client
 ///
 /// This struct centralizes HTTP calls, stores the base URL used for
@@ -33,10 +90,196 @@ use serde_json::json;
 pub struct ApiClient {
     // Underlying reqwest blocking client used for synchronous requests
     client: Client,
-    // Base URL for API gateway (defaults to http://localhost:8081)
+    // Same settings without gzip, for gateways whose compressed replies
+    // can't be read (see `execute`).
+    uncompressed_client: Client,
+    // Base URL for API gateway (see `default_gateway_url`)
     base_url: String,
     // Optional JWT token used for authenticated endpoints
     token: Option<String>,
+    // Optional JSONL file where every request/response pair is appended
+    // (see `--debug-dump`). Secrets are redacted before writing.
+    debug_dump: Option<PathBuf>,
+    // Print diagnostics (final URL after redirects, ...) to stderr.
+    verbose: bool,
+    // Set once the caller stopped waiting for this client's request (see
+    // `with_abandon_flag`); silences verbose output from then on.
+    abandoned: Option<Arc<AtomicBool>>,
+    // Text fields sent with the image (see `with_upload_fields`).
+    upload_fields: UploadFields,
+    // Maximum redirects followed per request (see `with_max_redirects`).
+    max_redirects: usize,
+    // Per-request timeout (see `with_timeout`).
+    timeout: Duration,
+    // HTTP version and TLS requirements (see `with_http2_prior_knowledge`
+    // and `with_min_tls_version`).
+    transport: Transport,
+    // Cache for `/capabilities` and `/profile` (see `cache.rs`); `None`
+    // with `--no-cache`.
+    cache: Option<Arc<ResponseCache>>,
+    // Whether the gateway host already resolved (see `preflight_dns`).
+    // Shared between clones so the lookup happens once per run.
+    dns_checked: Arc<AtomicBool>,
+    // Correlation id the gateway sent with the most recent response (see
+    // `CORRELATION_HEADERS`). Shared between clones like `redirect_hops`.
+    last_correlation_id: Arc<Mutex<Option<String>>>,
+    // Redirect hops taken by the most recent request, written by the
+    // redirect policy. Shared between clones; the CLI issues one request
+    // at a time so the value always belongs to the last call.
+    redirect_hops: Arc<AtomicUsize>,
+    // Retry settings and the process-wide retry budget (see `retry.rs`).
+    retry: RetryPolicy,
+    // Headers attached to every request (see `with_extra_headers`).
+    extra_headers: HeaderMap,
+    // Keep the token in memory only: nothing is read from or written to
+    // disk (see `with_ephemeral`).
+    ephemeral: bool,
+    // Refuse calls that change data on the server (see `with_read_only`).
+    read_only: bool,
+    // Folder holding the session file; the project folder when unset.
+    state_dir: Option<PathBuf>,
+    // Login response headers that may carry the token.
+    token_headers: Vec<HeaderName>,
+    // Role the user acts as, sent as `X-Active-Role` (see
+    // `set_active_role`).
+    active_role: Option<String>,
+    // CSRF token for state-changing requests, fetched on first use and
+    // shared between clones; `None` when CSRF support is off.
+    csrf: Option<Arc<Mutex<Option<HeaderValue>>>>,
+    // Request used by `health_check` (see `with_health_check`).
+    health: HealthCheck,
+    // Shared secret requests are signed with (see `signing.rs`).
+    hmac_secret: Option<String>,
+    // Unix seconds stamped on signed requests (see `with_signing_clock`).
+    signing_clock: fn() -> u64,
+    // Gateways tried, in order, when `base_url` can't be reached (see
+    // `with_fallback_urls`).
+    fallback_urls: Vec<String>,
+    // Index into `base_url` + `fallback_urls` of the gateway that answered
+    // last. Shared between clones so the whole session sticks to it.
+    active_gateway: Arc<AtomicUsize>,
+    // Answers the typed gateway calls instead of HTTP (see `backend.rs`).
+    backend: Option<Arc<dyn Backend>>,
+}
+
+/// Connection, HTTP version and TLS settings baked into the HTTP clients.
+#[derive(Debug, Clone, Default)]
+struct Transport {
+    http2_prior_knowledge: bool,
+    min_tls: Option<reqwest::tls::Version>,
+    connect_timeout: Option<Duration>,
+    // Proxy for every request (see `with_proxy`); `None` keeps reqwest's
+    // default of honouring the `*_PROXY` environment variables.
+    proxy: Option<String>,
+    // Extra root certificate, PEM (see `with_ca_cert`).
+    ca_cert: Option<PathBuf>,
+}
+
+/// Error raised by the redirect policy when a request exceeds the
+/// configured redirect limit. Carries the last `Location` seen so the
+/// message points at where the gateway was sending us.
+#[derive(Debug)]
+struct TooManyRedirects {
+    max: usize,
+    last_location: String,
+}
+
+impl std::fmt::Display for TooManyRedirects {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Demasiadas redirecciones (máximo {}); última ubicación: {}", self.max, self.last_location)
+    }
+}
+
+impl std::error::Error for TooManyRedirects {}
+
+/// ApiError
+///
+/// A non-2xx answer from the gateway, kept structured so callers can
+/// tell an authoritative rejection (401/403) from other failures and
+/// show the server's reason. Displays as "<label> failed: <status> -
+/// <body>", or "<summary>: <status> - <body>" when a summary is set.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub label: String,
+    pub status: StatusCode,
+    pub body: String,
+    /// Message for the user that replaces "<label> failed", e.g. "El
+    /// servidor rechazó la imagen".
+    pub summary: Option<String>,
+}
+
+impl ApiError {
+    /// The reason given by the server: the `error`, `message`, `mensaje`
+    /// or `detail` field of a JSON body, every message of an `errors`
+    /// list, or the plain-text body. `None` when the body is empty.
+    pub fn server_message(&self) -> Option<String> {
+        if let Ok(v) = serde_json::from_str::<serde_json::Value>(&self.body) {
+            for key in ["error", "message", "mensaje", "detail"] {
+                if let Some(msg) = v.get(key).and_then(|m| m.as_str()) {
+                    return Some(msg.to_string());
+                }
+            }
+        }
+        let errors = self.field_errors();
+        if !errors.is_empty() {
+            return Some(errors.iter().map(FieldError::to_string).collect::<Vec<_>>().join("; "));
+        }
+        let body = self.body.trim();
+        (!body.is_empty()).then(|| body.to_string())
+    }
+
+    /// Validation errors in the `{"errors": [{"field": ..., "message":
+    /// ...}]}` shape. Entries may also be plain strings (no field).
+    /// Empty for any other body.
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        let v: serde_json::Value = match serde_json::from_str(&self.body) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        };
+        let items = match v.get("errors").and_then(|e| e.as_array()) {
+            Some(items) => items,
+            None => return Vec::new(),
+        };
+        items
+            .iter()
+            .filter_map(|item| match item {
+                serde_json::Value::String(message) => Some(FieldError { field: None, message: message.clone() }),
+                _ => serde_json::from_value(item.clone()).ok(),
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.summary {
+            Some(summary) => write!(f, "{}: {} - {}", summary, self.status, self.body),
+            None => write!(f, "{} failed: {} - {}", self.label, self.status, self.body),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// FieldError
+///
+/// One entry of a multi-error response: the offending field, when the
+/// server names it, and its message.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    #[serde(default, alias = "campo")]
+    pub field: Option<String>,
+    #[serde(alias = "mensaje")]
+    pub message: String,
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}: {}", field, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
 }
 
 /// RegisterRequest
@@ -50,13 +293,434 @@ pub struct ApiClient {
 pub struct RegisterRequest {
     pub nombre_completo: String,
     pub edad: i32,
-    pub rol: String,
+    pub rol: Role,
     pub identificacion: String,
     pub correo: String,
     pub contrasena: String,
     pub acepta_tratamiento_datos: bool,
 }
 
+impl RegisterRequest {
+    /// Check every free-text field against `limits`, and the password
+    /// against its policy. The interactive flow already re-prompts on
+    /// invalid input; this is the last guard before
+    /// the request leaves the client.
+    pub fn validate_lengths(&self, limits: &FieldLimits) -> Result<()> {
+        let fields = [
+            ("Nombre completo", &self.nombre_completo, limits.nombre_completo),
+            ("Identificación", &self.identificacion, limits.identificacion),
+            ("Correo electrónico", &self.correo, limits.correo),
+        ];
+        for (name, value, max) in fields {
+            if let Err(msg) = check_length(value, max) {
+                anyhow::bail!("{}: {}", name, msg);
+            }
+        }
+        if let Err(msg) = limits.check_password(&self.contrasena) {
+            anyhow::bail!("Contraseña: {}", msg);
+        }
+        Ok(())
+    }
+
+    /// Every client-side check, field by field: required fields, lengths,
+    /// e-mail shape, age range, identification characters, a role the
+    /// backend accepts (not `Role::Unknown`) and consent. Empty when the
+    /// request would be sent as is.
+    pub fn problems(&self, limits: &FieldLimits) -> Vec<(RegisterField, String)> {
+        let consent = if self.acepta_tratamiento_datos { Ok(()) } else { Err("Debe aceptarse para registrarse.".to_string()) };
+        let checks = [
+            (
+                RegisterField::NombreCompleto,
+                check_required(&self.nombre_completo).and_then(|_| check_length(&self.nombre_completo, limits.nombre_completo)),
+            ),
+            (RegisterField::Edad, check_age(self.edad)),
+            (
+                RegisterField::Rol,
+                if self.rol == Role::Unknown { Err("Debe ser 'doctor' o 'paciente'.".to_string()) } else { Ok(()) },
+            ),
+            (
+                RegisterField::Identificacion,
+                check_identificacion(&self.identificacion).and_then(|_| check_length(&self.identificacion, limits.identificacion)),
+            ),
+            (RegisterField::Correo, check_email(&self.correo).and_then(|_| check_length(&self.correo, limits.correo))),
+            (
+                RegisterField::Contrasena,
+                check_required(&self.contrasena).and_then(|_| limits.check_password(&self.contrasena)),
+            ),
+            (RegisterField::AceptaTratamientoDatos, consent),
+        ];
+        checks.into_iter().filter_map(|(field, check)| check.err().map(|msg| (field, msg))).collect()
+    }
+}
+
+/// Role
+///
+/// Role chosen at registration, or held by a logged-in user. Serialized
+/// as the exact strings the backend expects (`"doctor"`, `"paciente"`);
+/// any other string, other casings included, deserializes as `Unknown`,
+/// so a role added on the server doesn't break reading profiles. Parsing
+/// with `FromStr` is lenient about case and surrounding blanks but
+/// strict otherwise, for typed input (config, CSV files).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Doctor,
+    Paciente,
+    /// A role this client doesn't know. Never offered or sent.
+    #[serde(other)]
+    Unknown,
+}
+
+impl Role {
+    /// Every role, in the order the register form offers them.
+    pub const ALL: [Role; 2] = [Role::Doctor, Role::Paciente];
+
+    /// Name on the wire (`doctor`, `paciente`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Doctor => "doctor",
+            Role::Paciente => "paciente",
+            Role::Unknown => "unknown",
+        }
+    }
+
+    /// Name shown in menus (`Doctor`, `Paciente`).
+    pub fn label(&self) -> &'static str {
+        match self {
+            Role::Doctor => "Doctor",
+            Role::Paciente => "Paciente",
+            Role::Unknown => "Otro",
+        }
+    }
+
+    /// The role named by a server value (token claim, profile field):
+    /// as lenient as `FromStr`, and `Unknown` for other names.
+    pub fn from_claim(name: &str) -> Role {
+        name.parse().unwrap_or(Role::Unknown)
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Role, String> {
+        match s.trim().to_lowercase().as_str() {
+            "doctor" => Ok(Role::Doctor),
+            "paciente" => Ok(Role::Paciente),
+            _ => Err(format!("Debe ser 'doctor' o 'paciente' (se ingresó '{}').", s)),
+        }
+    }
+}
+
+/// RegisterField
+///
+/// A field of `RegisterRequest`, for routing server validation errors
+/// back to the prompt that fills it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterField {
+    NombreCompleto,
+    Edad,
+    Rol,
+    Identificacion,
+    Correo,
+    Contrasena,
+    AceptaTratamientoDatos,
+}
+
+impl RegisterField {
+    /// The field a server error names (`correo`, `email`, ...), if it is
+    /// one of the registration fields.
+    pub fn from_name(name: &str) -> Option<RegisterField> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "nombre_completo" | "nombre" | "name" => Some(RegisterField::NombreCompleto),
+            "edad" | "age" => Some(RegisterField::Edad),
+            "rol" | "role" => Some(RegisterField::Rol),
+            "identificacion" | "identificación" => Some(RegisterField::Identificacion),
+            "correo" | "email" => Some(RegisterField::Correo),
+            "contrasena" | "contraseña" | "password" => Some(RegisterField::Contrasena),
+            "acepta_tratamiento_datos" | "consentimiento" | "consent" => Some(RegisterField::AceptaTratamientoDatos),
+            _ => None,
+        }
+    }
+
+    /// Prompt label of the field.
+    pub fn label(&self) -> &'static str {
+        match self {
+            RegisterField::NombreCompleto => "Nombre completo",
+            RegisterField::Edad => "Edad",
+            RegisterField::Rol => "Rol",
+            RegisterField::Identificacion => "Identificación",
+            RegisterField::Correo => "Correo electrónico",
+            RegisterField::Contrasena => "Contraseña",
+            RegisterField::AceptaTratamientoDatos => "Acepta tratamiento de datos",
+        }
+    }
+}
+
+/// RegisterDraft
+///
+/// The non-secret registration fields entered so far, kept in memory for
+/// the rest of the session so a cancelled registration can be resumed.
+/// There is deliberately no password field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegisterDraft {
+    pub nombre_completo: Option<String>,
+    pub edad: Option<i32>,
+    pub rol: Option<Role>,
+    pub identificacion: Option<String>,
+    pub correo: Option<String>,
+    pub acepta_tratamiento_datos: Option<bool>,
+}
+
+impl RegisterDraft {
+    /// Copy every non-secret field of `req`.
+    pub fn remember(&mut self, req: &RegisterRequest) {
+        *self = RegisterDraft {
+            nombre_completo: Some(req.nombre_completo.clone()),
+            edad: Some(req.edad),
+            rol: Some(req.rol),
+            identificacion: Some(req.identificacion.clone()),
+            correo: Some(req.correo.clone()),
+            acepta_tratamiento_datos: Some(req.acepta_tratamiento_datos),
+        };
+    }
+
+    /// Text to pre-fill the prompt for `field` with, if it was entered
+    /// before. Always `None` for the password.
+    pub fn initial_text(&self, field: RegisterField) -> Option<String> {
+        match field {
+            RegisterField::NombreCompleto => self.nombre_completo.clone(),
+            RegisterField::Edad => self.edad.map(|e| e.to_string()),
+            RegisterField::Rol => self.rol.map(|r| r.to_string()),
+            RegisterField::Identificacion => self.identificacion.clone(),
+            RegisterField::Correo => self.correo.clone(),
+            RegisterField::Contrasena => None,
+            RegisterField::AceptaTratamientoDatos => self.acepta_tratamiento_datos.map(|a| if a { "Sí" } else { "No" }.to_string()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == RegisterDraft::default()
+    }
+}
+
+/// FieldLimits
+///
+/// Maximum lengths (in characters) for the registration text fields,
+/// plus the password policy (minimum length, digits and uppercase
+/// letters; none by default). The length defaults are generous for real
+/// data but keep paste accidents (a 100k-character name) from reaching
+/// the backend. Each maximum can be overridden with `NEUMODIAG_MAX_NOMBRE_LEN`,
+/// `NEUMODIAG_MAX_IDENTIFICACION_LEN`, `NEUMODIAG_MAX_CORREO_LEN` and
+/// `NEUMODIAG_MAX_CONTRASENA_LEN`, and everything by the backend's
+/// advertised constraints (`with_constraints`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLimits {
+    pub nombre_completo: usize,
+    pub identificacion: usize,
+    pub correo: usize,
+    pub contrasena: usize,
+    pub contrasena_min: usize,
+    pub contrasena_digitos: usize,
+    pub contrasena_mayusculas: usize,
+}
+
+impl Default for FieldLimits {
+    fn default() -> Self {
+        FieldLimits {
+            nombre_completo: 120,
+            identificacion: 32,
+            // RFC 5321 caps a forward path at 254 characters.
+            correo: 254,
+            contrasena: 128,
+            contrasena_min: 0,
+            contrasena_digitos: 0,
+            contrasena_mayusculas: 0,
+        }
+    }
+}
+
+impl FieldLimits {
+    /// Defaults with any valid, non-zero environment overrides applied.
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: usize| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+        let d = FieldLimits::default();
+        FieldLimits {
+            nombre_completo: read("NEUMODIAG_MAX_NOMBRE_LEN", d.nombre_completo),
+            identificacion: read("NEUMODIAG_MAX_IDENTIFICACION_LEN", d.identificacion),
+            correo: read("NEUMODIAG_MAX_CORREO_LEN", d.correo),
+            contrasena: read("NEUMODIAG_MAX_CONTRASENA_LEN", d.contrasena),
+            ..d
+        }
+    }
+
+    /// These limits with the backend's advertised `constraints` applied
+    /// on top; the backend's values win since it enforces them anyway.
+    /// Zero maximums are ignored. Unchanged when `caps` is `None` or
+    /// advertises nothing.
+    pub fn with_constraints(mut self, caps: Option<&Capabilities>) -> Self {
+        let constraints = match caps {
+            Some(caps) => &caps.constraints,
+            None => return self,
+        };
+        for (name, c) in constraints {
+            let max = match RegisterField::from_name(name) {
+                Some(RegisterField::NombreCompleto) => &mut self.nombre_completo,
+                Some(RegisterField::Identificacion) => &mut self.identificacion,
+                Some(RegisterField::Correo) => &mut self.correo,
+                Some(RegisterField::Contrasena) => {
+                    self.contrasena_min = c.min_length.unwrap_or(self.contrasena_min);
+                    self.contrasena_digitos = c.min_digits.unwrap_or(self.contrasena_digitos);
+                    self.contrasena_mayusculas = c.min_uppercase.unwrap_or(self.contrasena_mayusculas);
+                    &mut self.contrasena
+                }
+                _ => continue,
+            };
+            if let Some(n) = c.max_length.filter(|n| *n > 0) {
+                *max = n;
+            }
+        }
+        self
+    }
+
+    /// Validator for the password: the maximum length and the policy.
+    pub fn check_password(&self, value: &str) -> std::result::Result<(), String> {
+        check_length(value, self.contrasena)?;
+        let len = value.chars().count();
+        if len < self.contrasena_min {
+            return Err(format!("Mínimo {} caracteres (se ingresaron {}).", self.contrasena_min, len));
+        }
+        if value.chars().filter(char::is_ascii_digit).count() < self.contrasena_digitos {
+            return Err(format!("Debe incluir al menos {}.", plural(self.contrasena_digitos, "número", "números")));
+        }
+        if value.chars().filter(|c| c.is_uppercase()).count() < self.contrasena_mayusculas {
+            return Err(format!("Debe incluir al menos {}.", plural(self.contrasena_mayusculas, "mayúscula", "mayúsculas")));
+        }
+        Ok(())
+    }
+
+    /// Prompt label for `field` with its rules appended when they differ
+    /// from the built-in defaults, e.g. "Contraseña (mínimo 10
+    /// caracteres, 1 número)" or "Nombre completo (máximo 80 caracteres)".
+    pub fn prompt(&self, field: RegisterField) -> String {
+        let d = FieldLimits::default();
+        let max = |limit: usize, default: usize| (limit != default).then(|| format!("máximo {} caracteres", limit));
+        let hints: Vec<String> = match field {
+            RegisterField::NombreCompleto => max(self.nombre_completo, d.nombre_completo).into_iter().collect(),
+            RegisterField::Identificacion => max(self.identificacion, d.identificacion).into_iter().collect(),
+            RegisterField::Correo => max(self.correo, d.correo).into_iter().collect(),
+            RegisterField::Contrasena => [
+                (self.contrasena_min > 0).then(|| format!("mínimo {} caracteres", self.contrasena_min)),
+                max(self.contrasena, d.contrasena),
+                (self.contrasena_digitos > 0).then(|| plural(self.contrasena_digitos, "número", "números")),
+                (self.contrasena_mayusculas > 0).then(|| plural(self.contrasena_mayusculas, "mayúscula", "mayúsculas")),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            _ => Vec::new(),
+        };
+        if hints.is_empty() {
+            field.label().to_string()
+        } else {
+            format!("{} ({})", field.label(), hints.join(", "))
+        }
+    }
+}
+
+/// "1 número", "2 números".
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+/// Gateway URL when nothing is configured in the file: `url`
+/// (`API_GATEWAY_URL`) when set, else `http://localhost` on `port`
+/// (`API_GATEWAY_PORT`) or `DEFAULT_GATEWAY_PORT`. Blank values count as
+/// unset; a port that isn't 1-65535 is an error.
+pub fn default_gateway_url(url: Option<String>, port: Option<String>) -> Result<String> {
+    if let Some(url) = url.filter(|u| !u.trim().is_empty()) {
+        return Ok(url);
+    }
+    let port = match port.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(raw) => raw.parse::<u16>().ok().filter(|p| *p > 0).ok_or_else(|| {
+            anyhow::anyhow!("API_GATEWAY_PORT inválido: '{}' (use un número entre 1 y 65535)", raw)
+        })?,
+        None => DEFAULT_GATEWAY_PORT,
+    };
+    Ok(format!("http://localhost:{}", port))
+}
+
+/// Validate that `value` has at most `max` characters. The error is a
+/// user-facing message so it can be handed straight to a dialoguer
+/// validator, which re-prompts until the input fits.
+pub fn check_length(value: &str, max: usize) -> std::result::Result<(), String> {
+    let len = value.chars().count();
+    if len > max {
+        return Err(format!("Máximo {} caracteres (se ingresaron {}).", max, len));
+    }
+    Ok(())
+}
+
+/// Validate that a required field is not blank.
+pub fn check_required(value: &str) -> std::result::Result<(), String> {
+    if value.trim().is_empty() {
+        return Err("Campo obligatorio.".into());
+    }
+    Ok(())
+}
+
+/// Validate the shape of an e-mail address: no spaces, one `@`, a
+/// non-empty local part and a domain with a dot inside it. Deliverability
+/// is left to the backend.
+pub fn check_email(value: &str) -> std::result::Result<(), String> {
+    let value = value.trim();
+    let valid = match value.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !value.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+    if !valid {
+        return Err("Correo electrónico inválido (ejemplo: nombre@dominio.com).".into());
+    }
+    Ok(())
+}
+
+/// Validate an age in years: 0 to 120.
+pub fn check_age(edad: i32) -> std::result::Result<(), String> {
+    if !(0..=120).contains(&edad) {
+        return Err(format!("Debe estar entre 0 y 120 (se ingresó {}).", edad));
+    }
+    Ok(())
+}
+
+/// Validate an identification number: required, and only letters,
+/// digits, `-` and `.`.
+pub fn check_identificacion(value: &str) -> std::result::Result<(), String> {
+    check_required(value)?;
+    if !value.trim().chars().all(|c| c.is_alphanumeric() || c == '-' || c == '.') {
+        return Err("Solo se permiten letras, números, '-' y '.'.".into());
+    }
+    Ok(())
+}
+
 /// AuthRequest
 ///
 /// Payload sent to the `/auth` endpoint. Also `Clone` so the CLI can
@@ -73,7 +737,9 @@ pub struct AuthRequest {
 /// The CLI expects the auth endpoint to reply with a JSON object
 /// containing at least a `token` (JWT) and a friendly `nombre` used
 /// for UI greetings. Other fields mirror the backend response and are
-/// kept generic where appropriate (e.g., `user_id` as Value).
+/// kept generic where appropriate (e.g., `user_id` as Value). When the
+/// token arrives in a header instead, `login` fills the other fields
+/// from its claims.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AuthResponse {
     pub nombre: String,
@@ -83,234 +749,2210 @@ pub struct AuthResponse {
     pub correo: String,
 }
 
-impl ApiClient {
-    /// Create an ApiClient configured from the environment variable
-    /// `API_GATEWAY_URL` or fallback to `http://localhost:8080`.
-    pub fn from_env() -> Result<Self> {
-        let base_url = std::env::var("API_GATEWAY_URL").unwrap_or_else(|_| "http://localhost:8080".into());
-        let client = Client::builder()
-            .build()
-            .context("Failed to build HTTP client")?;
-        Ok(ApiClient {
-            client,
-            base_url,
-            token: None,
-        })
-    }
+// Login reply as sent by the gateway: every field may be missing when the
+// token travels in a header.
+#[derive(Deserialize, Debug, Default)]
+struct AuthBody {
+    #[serde(default)]
+    nombre: Option<String>,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default, deserialize_with = "crate::profile::deserialize_rol")]
+    rol: Option<String>,
+    #[serde(default)]
+    user_id: Option<serde_json::Value>,
+    #[serde(default)]
+    correo: Option<String>,
+}
 
-    // Notes:
-    // - The client is built once and reused. `reqwest::blocking::Client`
-    //   holds connection pools and other internal caches which are
-    //   beneficial even for a CLI.
-    // - `API_GATEWAY_URL` environment variable allows pointing the CLI
-    //   to a different backend (e.g., a locally running auth-be vs a
-    //   gateway proxy).
+/// RefreshResponse
+///
+/// Reply of `POST /refresh`: a fresh token replacing the one sent.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RefreshResponse {
+    pub token: String,
+}
 
-    /// Store a JWT token for subsequent authenticated requests.
-    pub fn set_token(&mut self, token: &str) {
-        self.token = Some(token.to_string());
+/// Current layout of `TokenMeta`.
+pub const TOKEN_META_VERSION: u32 = 1;
+
+/// TokenMeta
+///
+/// Flags about the saved session, stored in the state file (formerly
+/// `.neumodiag_token.meta`). Missing keys take their defaults
+/// (`false`), so a file written by an older or newer CLI still parses.
+/// Files from before the `version` key existed read as version 0 and are
+/// migrated by `migrate`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct TokenMeta {
+    pub version: u32,
+    /// The user asked to remember the session.
+    pub persist: bool,
+    /// The previous run ended through the menu; only then is the saved
+    /// session restored automatically.
+    pub clean_exit: bool,
+    /// End of the remember window (unix seconds). `None` remembers the
+    /// session until logout; the token's own expiry still applies.
+    pub expires_at: Option<u64>,
+    /// Last time (unix seconds) a run was seen using this session,
+    /// refreshed by the menu loop. Lets the session survive a crash
+    /// shortly before (see `may_auto_login`).
+    pub last_seen: Option<u64>,
+}
+
+impl TokenMeta {
+    /// Meta for a freshly saved token: not yet cleanly closed.
+    pub fn new(persist: bool) -> TokenMeta {
+        TokenMeta { version: TOKEN_META_VERSION, persist, clean_exit: false, expires_at: None, last_seen: None }
     }
 
-    /// Clear any stored token (logout).
-    pub fn clear_token(&mut self) {
-        self.token = None;
+    /// Whether the saved session may be restored at `now` (unix
+    /// seconds): the previous run exited cleanly or, given an unclean
+    /// exit `grace`, it was last seen at most `grace` ago. The remember
+    /// window and the token's expiry are checked separately.
+    pub fn may_auto_login(&self, now: u64, grace: Option<Duration>) -> bool {
+        if self.clean_exit {
+            return true;
+        }
+        match (grace, self.last_seen) {
+            (Some(grace), Some(seen)) => now.saturating_sub(seen) <= grace.as_secs(),
+            _ => false,
+        }
     }
 
-    /// Returns whether a token is present in the client.
-    pub fn has_token(&self) -> bool {
-        self.token.is_some()
+    /// Whether the remember window is still open at `now` (unix seconds).
+    pub fn within_remember_window(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|end| now < end)
     }
 
-    /// Build authorization headers when a token is present.
-    fn auth_headers(&self) -> HeaderMap {
-        let mut headers = HeaderMap::new();
-        if let Some(t) = &self.token {
-            // Build a standard `Authorization: Bearer <token>` header.
-            // We `unwrap()` here because the formatted string is always
-            // valid for a header value; if this ever changes a proper
-            // error path should be added.
-            let val = format!("Bearer {}", t);
-            headers.insert(AUTHORIZATION, HeaderValue::from_str(&val).unwrap());
+    /// Parse meta JSON, migrating older layouts.
+    pub fn from_json(s: &str) -> Result<TokenMeta> {
+        let meta: TokenMeta = serde_json::from_str(s).context("parsing meta json")?;
+        Ok(meta.migrate())
+    }
+
+    /// Bring an older layout up to `TOKEN_META_VERSION`. Version 0 files
+    /// already hold `persist`/`clean_exit` with today's meaning, so only
+    /// the version number changes.
+    pub fn migrate(mut self) -> TokenMeta {
+        if self.version < TOKEN_META_VERSION {
+            self.version = TOKEN_META_VERSION;
         }
-        headers
+        self
     }
+}
 
-    /// Persist token and metadata into the project folder (cli-front-end).
-    /// This writes two files next to Cargo.toml: `.neumodiag_token` and
-    /// `.neumodiag_token.meta` which contains JSON like {"persist":true,"clean_exit":false}
-    pub fn persist_token_to_project(&self, token: &str, persist: bool) -> Result<()> {
-        let proj_dir = find_project_dir()?;
+/// How long after an unclean exit the saved session is still restored,
+/// from `NEUMODIAG_UNCLEAN_GRACE_SECS` (disabled when unset or 0).
+pub fn unclean_grace_from_env() -> Option<Duration> {
+    let secs: u64 = std::env::var("NEUMODIAG_UNCLEAN_GRACE_SECS").ok()?.trim().parse().ok()?;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
 
-        let token_path = proj_dir.join(".neumodiag_token");
-        let meta_path = proj_dir.join(".neumodiag_token.meta");
+/// RememberFor
+///
+/// How long a session is remembered, as chosen after logging in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RememberFor {
+    Always,
+    Days(u64),
+    No,
+}
 
-        // Write token
-        let mut f = File::create(&token_path).context("creating token file")?;
-        f.write_all(token.as_bytes()).context("writing token file")?;
+impl RememberFor {
+    /// The choices offered after logging in, in menu order.
+    pub const CHOICES: [RememberFor; 4] = [RememberFor::Always, RememberFor::Days(7), RememberFor::Days(1), RememberFor::No];
 
-        // Write meta
-        // meta stores whether the user asked to persist the token and
-        // whether the program exited cleanly in the previous run. The
-        // CLI sets `clean_exit` to `true` only when the user exits via
-        // the menu — this avoids auto-login after crashes.
-        let meta = json!({"persist": persist, "clean_exit": false});
-        let mut m = File::create(&meta_path).context("creating token meta file")?;
-        m.write_all(meta.to_string().as_bytes()).context("writing token meta file")?;
-        Ok(())
+    /// Menu label ("Siempre", "7 días", "1 día", "No").
+    pub fn label(&self) -> String {
+        match self {
+            RememberFor::Always => "Siempre".into(),
+            RememberFor::Days(1) => "1 día".into(),
+            RememberFor::Days(n) => format!("{} días", n),
+            RememberFor::No => "No".into(),
+        }
     }
 
-    /// Load token only if present in project folder. Returns Ok(None) when
-    /// no token is available. Note: does not automatically set ApiClient.token
-    /// so the caller can decide whether to honor auto-login rules.
-    pub fn load_token_from_project(&self) -> Result<Option<String>> {
-        let proj_dir = find_project_dir()?;
-        let token_path = proj_dir.join(".neumodiag_token");
-        if !token_path.exists() {
-            return Ok(None);
+    /// Meta for a token saved at `now` (unix seconds) with this choice.
+    pub fn meta(&self, now: u64) -> TokenMeta {
+        match self {
+            RememberFor::Always => TokenMeta::new(true),
+            RememberFor::Days(n) => TokenMeta { expires_at: Some(now + n * 86_400), ..TokenMeta::new(true) },
+            RememberFor::No => TokenMeta::new(false),
         }
-        let mut s = String::new();
-        let mut f = File::open(&token_path).context("opening token file")?;
-        // Read the raw token. Note: some editors or tools may add a
-        // trailing newline when saving files. The caller typically
-        // trims whitespace before use (see ui.rs) to be robust.
-        f.read_to_string(&mut s).context("reading token file")?;
-        Ok(Some(s))
     }
+}
 
-    /// Read meta JSON if present. Returns None when no meta file exists.
-    pub fn load_token_meta(&self) -> Result<Option<serde_json::Value>> {
-        let proj_dir = find_project_dir()?;
-        let meta_path = proj_dir.join(".neumodiag_token.meta");
-        if !meta_path.exists() {
-            return Ok(None);
+/// SessionState
+///
+/// Who is logged in, as far as the client can tell without asking the
+/// gateway (see `ApiClient::session_state`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionState {
+    LoggedOut,
+    /// Name and role come from the token's claims (the role chosen with
+    /// `set_active_role` wins); `expires_at` is its `exp`, if any.
+    LoggedIn { name: Option<String>, role: Option<String>, expires_at: Option<u64> },
+    /// A token exists but its `exp`, or the remember window chosen when it
+    /// was saved, has passed.
+    Expired,
+}
+
+impl SessionState {
+    /// State for `token` at `now` (unix seconds), from its claims alone.
+    /// A token that isn't a decodable JWT still counts as logged in.
+    pub fn from_token(token: Option<&str>, now: u64) -> SessionState {
+        let token = match token.map(str::trim).filter(|t| !t.is_empty()) {
+            Some(token) => token,
+            None => return SessionState::LoggedOut,
+        };
+        let expires_at = crate::profile::token_expiry(token);
+        if expires_at.is_some_and(|exp| exp <= now) {
+            return SessionState::Expired;
         }
-        let s = std::fs::read_to_string(&meta_path).context("reading meta file")?;
-        let v: serde_json::Value = serde_json::from_str(&s).context("parsing meta json")?;
-        Ok(Some(v))
+        let profile = Profile::from_jwt(token).unwrap_or_default();
+        SessionState::LoggedIn { name: profile.nombre_completo, role: crate::profile::joined_roles(token), expires_at }
     }
+}
 
-    /// Update meta.clean_exit flag to the provided value. Creates meta if missing.
-    pub fn set_clean_exit_meta(&self, clean: bool) -> Result<()> {
-        let proj_dir = find_project_dir()?;
-        let meta_path = proj_dir.join(".neumodiag_token.meta");
-        let mut meta = if meta_path.exists() {
-            let s = std::fs::read_to_string(&meta_path).unwrap_or_else(|_| "{}".into());
-            // Merge with existing meta when possible. If the meta file is
-            // malformed we fall back to an empty object to avoid panics.
-            serde_json::from_str(&s).unwrap_or_else(|_| json!({}))
-        } else {
-            json!({})
-        };
-        meta["clean_exit"] = json!(clean);
-        let mut m = File::create(&meta_path).context("creating meta file")?;
-        m.write_all(meta.to_string().as_bytes()).context("writing meta file")?;
-        Ok(())
+/// RawResponse
+///
+/// Uninterpreted reply returned by `ApiClient::request`: status, headers
+/// and the raw body bytes.
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl RawResponse {
+    /// Body decoded as UTF-8 (invalid sequences are replaced).
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
     }
 
-    /// Clear persisted token and meta files in the project folder.
-    pub fn clear_persisted_token_in_project(&self) {
-        let proj_dir = find_project_dir().unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-        let token_path = proj_dir.join(".neumodiag_token");
-        let meta_path = proj_dir.join(".neumodiag_token.meta");
-        let _ = std::fs::remove_file(token_path);
-        let _ = std::fs::remove_file(meta_path);
+    /// Parse the body as JSON.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_slice(&self.body).context("parsing response json")
     }
 
-    /// Register a user by POSTing to /register. Returns a simple String
-    /// on success, or an error with the server response body on failure.
-    pub fn register(&self, req: &RegisterRequest) -> Result<String> {
-        let url = format!("{}/register", &self.base_url);
-        let res = self.client.post(&url)
-            .json(req)
-            .send()
-            .context("Failed to send register request")?;
-        if !res.status().is_success() {
-            let status = res.status();
-            let txt = res.text().unwrap_or_else(|_| "".into());
-            anyhow::bail!("Register failed: {} - {}", status, txt);
-        }
-        Ok("Registered".into())
+    /// Whether the body is an HTML page (`Content-Type: text/html`).
+    pub fn is_html(&self) -> bool {
+        is_html_content_type(&self.headers)
     }
 
-    /// Perform login and parse the expected AuthResponse JSON.
-    pub fn login(&self, req: &AuthRequest) -> Result<AuthResponse> {
-        let url = format!("{}/auth", &self.base_url);
-        let res = self.client.post(&url)
-            .json(req)
-            .send()
-            .context("Failed to send auth request")?;
-        if !res.status().is_success() {
-            let status = res.status();
-            let txt = res.text().unwrap_or_else(|_| "".into());
-            anyhow::bail!("Login failed: {} - {}", status, txt);
+    /// Read a sent response into memory, decoding a gzip body reqwest
+    /// left compressed (see `decode_leftover_gzip`).
+    fn read(res: Response) -> Self {
+        let status = res.status();
+        let mut headers = res.headers().clone();
+        let body = res.bytes().map(|b| b.to_vec()).unwrap_or_default();
+        let body = decode_leftover_gzip(&mut headers, body);
+        RawResponse { status, headers, body }
+    }
+
+    /// Turn a non-2xx status into an `ApiError` ("<label> failed:
+    /// <status> - <body>") and reject HTML pages (see
+    /// `HTML_RESPONSE_MSG`). Shared by every typed call.
+    fn into_success(self, label: &str) -> Result<Self> {
+        if !self.status.is_success() {
+            return Err(ApiError { label: label.to_string(), status: self.status, body: self.text(), summary: None }.into());
         }
-        let resp: AuthResponse = res.json().context("Parsing auth response json")?;
-        Ok(resp)
+        if self.is_html() {
+            anyhow::bail!(HTML_RESPONSE_MSG);
+        }
+        Ok(self)
     }
+}
 
-    /// Upload a profile picture using multipart/form-data. The backend
-    /// path `/upload` is used here and the multipart field is `foto`.
-    /// The function adds the Authorization header if a token is present.
-    pub fn upload_profile_picture(&self, file_path: &PathBuf) -> Result<String> {
-        // auth-be exposes the upload handler at /upload and expects the
-        // multipart field to be named "foto".
-        let url = format!("{}/upload", &self.base_url);
+/// Capabilities
+///
+/// Optional features advertised by the backend at `GET /capabilities`,
+/// e.g. `{"features": ["register", "upload", "refresh"]}`. The CLI uses
+/// it to hide menu entries the backend does not implement. Feature names
+/// are compared case-insensitively. `upload_fields` lists extra form
+/// fields the upload accepts (`["descripcion", "categoria"]`), which the
+/// menu asks for before uploading. `constraints` carries the backend's
+/// registration rules (see `FieldConstraint`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Capabilities {
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub upload_fields: Vec<String>,
+    #[serde(default)]
+    pub constraints: BTreeMap<String, FieldConstraint>,
+}
 
-        // Open file and create a multipart part. We set a default filename
-        // and `image/jpeg` as the mime type for the prototype; a real app
-        // would detect the mime type from the file extension.
-        let file = File::open(file_path).context("Failed to open image file")?;
-        let file_name = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("image.jpg");
+/// FieldConstraint
+///
+/// Rules the backend advertises for one registration field, keyed by the
+/// field's name under `constraints` in `/capabilities`:
+/// `{"nombre_completo": {"max_length": 80}, "contrasena": {"min_length":
+/// 10, "min_digits": 1}}`. Field names follow `RegisterField::from_name`.
+/// `max_length` applies to the text fields; the `min_*` rules to the
+/// password only. Unknown fields and rules are ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FieldConstraint {
+    pub max_length: Option<usize>,
+    pub min_length: Option<usize>,
+    pub min_digits: Option<usize>,
+    pub min_uppercase: Option<usize>,
+}
 
-        let part = multipart::Part::reader(file).file_name(file_name.to_string()).mime_str("image/jpeg").unwrap();
-        // Use field name "foto" to match auth-be's HandlerGuardarFotoPerfil
-        let form = multipart::Form::new().part("foto", part);
+impl Capabilities {
+    /// Returns whether the backend advertised `feature`.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f.eq_ignore_ascii_case(feature))
+    }
+}
 
-        let mut req = self.client.post(&url).multipart(form);
-        // Add auth header if present
-        if let Some(_) = &self.token {
-            req = req.headers(self.auth_headers());
-        }
+impl ApiClient {
+    /// Create an ApiClient configured from the environment: see
+    /// `default_gateway_url`.
+    pub fn from_env() -> Result<Self> {
+        let base_url =
+            default_gateway_url(std::env::var("API_GATEWAY_URL").ok(), std::env::var("API_GATEWAY_PORT").ok())?;
+        ApiClient::new(base_url)
+    }
 
-        let res = req.send().context("Failed to send upload request")?;
-        if !res.status().is_success() {
-            let status = res.status();
-            let txt = res.text().unwrap_or_else(|_| "".into());
-            anyhow::bail!("Upload failed: {} - {}", status, txt);
-        }
-        Ok("Upload OK".into())
+    /// Create an ApiClient targeting an explicit base URL (for example a
+    /// mock gateway in tests).
+    pub fn new(base_url: impl Into<String>) -> Result<Self> {
+        let redirect_hops = Arc::new(AtomicUsize::new(0));
+        let timeout = Duration::from_secs(DEFAULT_TIMEOUT_SECS);
+        let transport = Transport {
+            connect_timeout: Some(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS)),
+            ..Transport::default()
+        };
+        let client = build_http_client(DEFAULT_MAX_REDIRECTS, timeout, &transport, &redirect_hops, true)?;
+        let uncompressed_client = build_http_client(DEFAULT_MAX_REDIRECTS, timeout, &transport, &redirect_hops, false)?;
+        Ok(ApiClient {
+            client,
+            uncompressed_client,
+            base_url: base_url.into(),
+            token: None,
+            debug_dump: None,
+            verbose: false,
+            abandoned: None,
+            upload_fields: UploadFields::default(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            timeout,
+            transport,
+            cache: Some(Arc::new(ResponseCache::new(Duration::from_secs(DEFAULT_CACHE_TTL_SECS)))),
+            dns_checked: Arc::new(AtomicBool::new(false)),
+            last_correlation_id: Arc::new(Mutex::new(None)),
+            redirect_hops,
+            retry: RetryPolicy::default(),
+            extra_headers: HeaderMap::new(),
+            ephemeral: false,
+            read_only: false,
+            state_dir: None,
+            token_headers: DEFAULT_TOKEN_HEADERS.iter().map(|h| HeaderName::from_static(h)).collect(),
+            active_role: None,
+            csrf: None,
+            health: HealthCheck::default(),
+            hmac_secret: None,
+            signing_clock: unix_now,
+            fallback_urls: Vec::new(),
+            active_gateway: Arc::new(AtomicUsize::new(0)),
+            backend: None,
+        })
     }
-}
 
-/// Try to locate the project directory by checking CARGO_MANIFEST_DIR, then
-/// walking up from the current executable location looking for Cargo.toml.
-fn find_project_dir() -> Result<PathBuf> {
-    if let Ok(s) = std::env::var("CARGO_MANIFEST_DIR") {
-        return Ok(PathBuf::from(s));
+    /// Follow at most `max` redirects per request (default 3). Exceeding
+    /// the limit fails the request with an error naming the last location.
+    pub fn with_max_redirects(mut self, max: usize) -> Result<Self> {
+        self.max_redirects = max;
+        self.rebuild_clients()?;
+        Ok(self)
     }
 
-    if let Ok(exe) = std::env::current_exe() {
-        if let Some(mut dir) = exe.parent() {
-            loop {
-                // Walk upwards from the executable location looking for a
-                // `Cargo.toml` file. This heuristic finds the project root
-                // in common development layouts (cargo run, target/debug
-                // exe, etc.). If nothing is found, we fall back to the
-                // current working directory below.
-                let candidate = dir.join("Cargo.toml");
-                if candidate.exists() {
-                    return Ok(dir.to_path_buf());
-                }
-                // Move up one directory and repeat. Stop when we reach
-                // the filesystem root (no parent).
-                if let Some(p) = dir.parent() {
-                    dir = p;
-                } else {
-                    break;
-                }
-            }
-        }
+    /// Give up on a request after `timeout` (default 30 s). A stalled
+    /// server then fails the call instead of blocking forever. The limit
+    /// applies to the first byte of the reply and again to its body, and
+    /// each case gets its own message.
+    pub fn with_timeout(mut self, timeout: Duration) -> Result<Self> {
+        self.timeout = timeout;
+        self.rebuild_clients()?;
+        Ok(self)
+    }
+
+    /// Give up connecting to the gateway after `timeout` (default 10 s),
+    /// separately from the wait for its reply (see `with_timeout`).
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Result<Self> {
+        self.transport.connect_timeout = Some(timeout);
+        self.rebuild_clients()?;
+        Ok(self)
+    }
+
+    /// A copy for background work such as the health probe: same
+    /// gateway, headers and token, but its own correlation id and redirect
+    /// count and no debug dump, so its traffic never replaces what the
+    /// user's last request recorded.
+    pub(crate) fn detached(&self) -> Result<ApiClient> {
+        let mut copy = self.clone();
+        copy.debug_dump = None;
+        copy.last_correlation_id = Arc::new(Mutex::new(None));
+        copy.redirect_hops = Arc::new(AtomicUsize::new(0));
+        // The redirect policy writes to the hop counter it was built with.
+        copy.rebuild_clients()?;
+        Ok(copy)
+    }
+
+    // Rebuild both HTTP clients after a setting they bake in changed.
+    fn rebuild_clients(&mut self) -> Result<()> {
+        self.client = build_http_client(self.max_redirects, self.timeout, &self.transport, &self.redirect_hops, true)?;
+        self.uncompressed_client = build_http_client(self.max_redirects, self.timeout, &self.transport, &self.redirect_hops, false)?;
+        Ok(())
+    }
+
+    /// Speak HTTP/2 from the first byte, for gateways that only accept
+    /// HTTP/2 over plain TCP (h2c). Off by default: HTTP/1.1, or HTTP/2
+    /// when negotiated over TLS.
+    pub fn with_http2_prior_knowledge(mut self, enabled: bool) -> Result<Self> {
+        self.transport.http2_prior_knowledge = enabled;
+        self.rebuild_clients()?;
+        Ok(self)
+    }
+
+    /// Refuse TLS connections below `version`. `None` keeps the TLS
+    /// library's default. Not every TLS backend can require 1.3; then
+    /// this fails.
+    pub fn with_min_tls_version(mut self, version: Option<reqwest::tls::Version>) -> Result<Self> {
+        self.transport.min_tls = version;
+        self.rebuild_clients()
+            .with_context(|| format!("No se pudo exigir TLS {:?} como versión mínima en esta compilación", version))?;
+        Ok(self)
+    }
+
+    /// Send every request through the proxy at `url` (`http://`,
+    /// `https://`). `None` keeps the `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY`
+    /// variables in charge. A URL that cannot be used is an error naming it.
+    pub fn with_proxy(mut self, url: Option<String>) -> Result<Self> {
+        self.transport.proxy = url;
+        self.rebuild_clients()?;
+        Ok(self)
     }
 
-    Ok(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    /// Trust the PEM certificate at `path` as an extra root, for gateways
+    /// behind a private CA. Fails if the file cannot be read or is not a
+    /// certificate.
+    pub fn with_ca_cert(mut self, path: Option<PathBuf>) -> Result<Self> {
+        self.transport.ca_cert = path;
+        self.rebuild_clients()?;
+        Ok(self)
+    }
+
+    /// Per-request timeout (see `with_timeout`).
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Cache `/capabilities` and `/profile` responses for `ttl`, or not at
+    /// all with `None` (`--no-cache`).
+    pub fn with_cache_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.cache = ttl.map(|ttl| Arc::new(ResponseCache::new(ttl)));
+        self
+    }
+
+    /// Stop printing diagnostics once `abandoned` is set. Used when the UI
+    /// stops waiting for a background request, so the orphaned call can't
+    /// write over whatever is on screen by the time it finishes.
+    pub fn with_abandon_flag(mut self, abandoned: Arc<AtomicBool>) -> Self {
+        self.abandoned = Some(abandoned);
+        self
+    }
+
+    /// Send `fields` as extra text fields next to `foto` in the uploads
+    /// made through this client.
+    pub fn with_upload_fields(mut self, fields: UploadFields) -> Self {
+        self.upload_fields = fields;
+        self
+    }
+
+    // Verbose output, unless the caller abandoned this request.
+    fn verbose(&self) -> bool {
+        self.verbose && !self.abandoned.as_ref().is_some_and(|a| a.load(Ordering::SeqCst))
+    }
+
+    /// Retry transient failures up to `max_retries` times per call, drawing
+    /// from a budget of `budget` retries shared by every clone of this
+    /// client. Calls whose body cannot be replayed are never retried.
+    pub fn with_retries(mut self, max_retries: usize, budget: usize) -> Self {
+        self.retry.max_retries = max_retries;
+        self.retry.budget = crate::retry::RetryBudget::new(budget);
+        self
+    }
+
+    /// Also retry responses with these statuses (`retry_status` in the
+    /// config), on top of 502/503/504. Fails on statuses that retrying
+    /// can't fix (see `validate_retry_statuses`).
+    pub fn with_retry_statuses(mut self, statuses: &[u16]) -> Result<Self> {
+        crate::retry::validate_retry_statuses(statuses)?;
+        self.retry.extra_statuses = statuses.to_vec();
+        Ok(self)
+    }
+
+    /// Send a status line to `progress` before each retry, so a spinner
+    /// can show "Reintentando (n/m)..." instead of a static message.
+    pub fn with_retry_progress(mut self, progress: std::sync::mpsc::Sender<String>) -> Self {
+        self.retry.progress = Some(progress);
+        self
+    }
+
+    /// Keep the token in memory for the lifetime of the process only.
+    /// Persisting, loading and clearing saved tokens become no-ops, so no
+    /// session files are created and nothing is restored on startup.
+    pub fn with_ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
+    /// Whether the client runs in memory-only mode (see `with_ephemeral`).
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// Refuse operations that change data on the server: registering,
+    /// uploads, revoking consent, and raw requests other than GET, HEAD
+    /// and OPTIONS. They fail with `READ_ONLY_MSG` before anything is
+    /// sent. Logging in and reading data still work.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Whether `--verbose` diagnostics are on (see `with_verbose`).
+    pub fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    /// Whether write operations are refused (see `with_read_only`).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Fail with `READ_ONLY_MSG` in read-only mode.
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            anyhow::bail!(READ_ONLY_MSG);
+        }
+        Ok(())
+    }
+
+    /// Store the session file in `dir` instead of the project folder.
+    pub fn with_state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.state_dir = Some(dir.into());
+        self
+    }
+
+    /// Attach `headers` (e.g. `X-Tenant`, `X-Api-Key`) to every request.
+    /// Names and values are validated up front so a typo in the config
+    /// fails at startup rather than on the first request. The token's
+    /// `Authorization` header, when present, takes precedence.
+    pub fn with_extra_headers(mut self, headers: &BTreeMap<String, String>) -> Result<Self> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            let n = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow::anyhow!("Nombre de encabezado inválido: '{}'", name))?;
+            let v = HeaderValue::from_str(value)
+                .map_err(|_| anyhow::anyhow!("Valor inválido para el encabezado '{}'", name))?;
+            if self.verbose {
+                let shown = if crate::config::is_sensitive_header(name) { "***" } else { value.as_str() };
+                eprintln!("[verbose] encabezado extra {}: {}", name, shown);
+            }
+            map.insert(n, v);
+        }
+        self.extra_headers = map;
+        Ok(self)
+    }
+
+    /// Response header that carries the token for gateways that don't put
+    /// it in the login body. `None` keeps the default: `Authorization`,
+    /// then `X-Auth-Token`.
+    pub fn with_token_header(mut self, name: Option<&str>) -> Result<Self> {
+        if let Some(name) = name {
+            let header = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow::anyhow!("Nombre de encabezado inválido: '{}'", name))?;
+            self.token_headers = vec![header];
+        }
+        Ok(self)
+    }
+
+    /// Gateways to fail over to, in order, when the current one refuses
+    /// the connection (high-availability setups). The session then keeps
+    /// using the gateway that answered.
+    pub fn with_fallback_urls(mut self, urls: Vec<String>) -> Result<Self> {
+        for url in &urls {
+            Url::parse(url).with_context(|| format!("URL del gateway inválida: {}", url))?;
+        }
+        self.fallback_urls = urls;
+        Ok(self)
+    }
+
+    /// Sign every request with HMAC-SHA256 using `secret` (see
+    /// `signing.rs`). `None` sends requests unsigned.
+    pub fn with_hmac_secret(mut self, secret: Option<String>) -> Self {
+        self.hmac_secret = secret;
+        self
+    }
+
+    /// Send the typed gateway calls (login, register, profile, pictures,
+    /// ...) to `backend` instead of over HTTP, e.g. an in-memory
+    /// `FakeBackend` in tests. Local checks still apply first.
+    pub fn with_backend(mut self, backend: Arc<dyn Backend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Whether requests are signed. Signing reads a streamed body into
+    /// memory before anything is sent, so upload progress then only
+    /// measures that read.
+    pub fn signs_requests(&self) -> bool {
+        self.hmac_secret.is_some()
+    }
+
+    /// Clock read for the timestamp of each signed attempt (unix
+    /// seconds); the system clock by default.
+    pub fn with_signing_clock(mut self, clock: fn() -> u64) -> Self {
+        self.signing_clock = clock;
+        self
+    }
+
+    /// Method, path, expected statuses and body of the health check
+    /// (default `GET /health`, any 2xx).
+    pub fn with_health_check(mut self, check: HealthCheck) -> Result<Self> {
+        check.validate()?;
+        self.health = check;
+        Ok(self)
+    }
+
+    /// For gateways that require a CSRF token: fetch one from `GET /csrf`
+    /// before the first POST/PUT/PATCH/DELETE (register, login, upload,
+    /// ...) and send it as `X-CSRF-Token`. A 419, or a 403 mentioning
+    /// CSRF, discards the token; JSON requests are then sent once more
+    /// with a fresh one, while a rejected upload fetches it next time.
+    pub fn with_csrf(mut self, enabled: bool) -> Self {
+        self.csrf = enabled.then(|| Arc::new(Mutex::new(None)));
+        self
+    }
+
+    /// Print request diagnostics (such as the final URL after redirects)
+    /// to stderr.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Append every interaction (timestamp, method, URL, request body,
+    /// status and response body) to `path` as JSON lines. Passwords and
+    /// tokens are replaced with `***` before anything is written.
+    pub fn with_debug_dump(mut self, path: PathBuf) -> Self {
+        self.debug_dump = Some(path);
+        self
+    }
+
+    // Notes:
+    // - The client is built once and reused. `reqwest::blocking::Client`
+    //   holds connection pools and other internal caches which are
+    //   beneficial even for a CLI.
+    // - `API_GATEWAY_URL` environment variable allows pointing the CLI
+    //   to a different backend (e.g., a locally running auth-be vs a
+    //   gateway proxy).
+
+    /// Store a JWT token for subsequent authenticated requests.
+    pub fn set_token(&mut self, token: &str) {
+        self.token = Some(token.to_string());
+    }
+
+    /// Clear any stored token (logout), and the active role with it.
+    pub fn clear_token(&mut self) {
+        self.token = None;
+        self.active_role = None;
+    }
+
+    /// Act as `role` for users with several roles: every request then
+    /// carries `X-Active-Role: <role>`. `None` stops sending it.
+    pub fn set_active_role(&mut self, role: Option<&str>) {
+        self.active_role = role.map(str::to_string);
+    }
+
+    /// Role chosen with `set_active_role`, if any.
+    pub fn active_role(&self) -> Option<&str> {
+        self.active_role.as_deref()
+    }
+
+    /// Primary gateway base URL, as configured.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Gateway base URL requests are sent to: the primary, or the fallback
+    /// that last answered after the primary couldn't be reached.
+    pub fn active_gateway_url(&self) -> &str {
+        match self.active_gateway.load(Ordering::SeqCst) {
+            0 => &self.base_url,
+            i => self.fallback_urls.get(i - 1).unwrap_or(&self.base_url),
+        }
+    }
+
+    // Gateway number `index` of `base_url` + `fallback_urls`.
+    fn gateway_url(&self, index: usize) -> &str {
+        if index == 0 {
+            &self.base_url
+        } else {
+            &self.fallback_urls[index - 1]
+        }
+    }
+
+    /// The token currently held in memory, if any.
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Returns whether a token is present in the client.
+    pub fn has_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Session state for embedders (menus, GUIs): the in-memory token or,
+    /// when there is none, the saved one. Claims are not verified; use
+    /// `verify_token` for an authoritative answer.
+    pub fn session_state(&self) -> SessionState {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.session_state_at(now)
+    }
+
+    /// `session_state` at `now` (unix seconds).
+    pub fn session_state_at(&self, now: u64) -> SessionState {
+        let state = match &self.token {
+            Some(token) => SessionState::from_token(Some(token), now),
+            None => {
+                let saved = self.load_token_from_project().ok().flatten();
+                let window_closed = saved.is_some()
+                    && self.load_token_meta().ok().flatten().is_some_and(|meta| !meta.within_remember_window(now));
+                if window_closed {
+                    return SessionState::Expired;
+                }
+                SessionState::from_token(saved.as_deref(), now)
+            }
+        };
+        match state {
+            SessionState::LoggedIn { name, role, expires_at } => {
+                SessionState::LoggedIn { name, role: self.active_role.clone().or(role), expires_at }
+            }
+            other => other,
+        }
+    }
+
+    /// Headers for an outgoing request: the configured extra headers plus
+    /// `Authorization: Bearer` when a token is present and
+    /// `X-Active-Role` when a role was chosen. Fails when the token can't
+    /// be sent as a header value.
+    fn request_headers(&self) -> Result<HeaderMap> {
+        let mut headers = self.extra_headers.clone();
+        if let Some(value) = self.active_role.as_deref().and_then(|r| HeaderValue::from_str(r).ok()) {
+            headers.insert(ACTIVE_ROLE_HEADER, value);
+        }
+        if let Some(t) = &self.token {
+            headers.insert(AUTHORIZATION, bearer_header(t)?);
+        }
+        Ok(headers)
+    }
+
+    /// Persist token and metadata into the project folder (cli-front-end),
+    /// in the state file next to Cargo.toml (see `state.rs`). The meta
+    /// records whether the user asked to persist the token and whether
+    /// the program exited cleanly in the previous run.
+    pub fn persist_token_to_project(&self, token: &str, persist: bool) -> Result<()> {
+        self.persist_token_with_meta(token, TokenMeta::new(persist))
+    }
+
+    /// `persist_token_to_project` with explicit meta, e.g. to keep the
+    /// remember window of a session whose token was replaced.
+    pub fn persist_token_with_meta(&self, token: &str, meta: TokenMeta) -> Result<()> {
+        if self.ephemeral {
+            return Ok(());
+        }
+        let store = self.state_store()?;
+
+        // Written under the shutdown lock and replaced atomically, so a
+        // SIGTERM never leaves the file half written.
+        let _guard = crate::shutdown::lock_state_writes();
+
+        // The CLI sets `clean_exit` to `true` only when the user exits via
+        // the menu — this avoids auto-login after crashes.
+        store.update(|state| {
+            state.token = Some(token.to_string());
+            state.meta = Some(TokenMeta { clean_exit: false, ..meta });
+        })
+    }
+
+    /// Load token only if present in project folder. Returns Ok(None) when
+    /// no token is available. Note: does not automatically set ApiClient.token
+    /// so the caller can decide whether to honor auto-login rules.
+    pub fn load_token_from_project(&self) -> Result<Option<String>> {
+        if self.ephemeral {
+            return Ok(None);
+        }
+        // Some editors or tools may add a trailing newline when saving
+        // files. The caller typically trims whitespace before use (see
+        // ui.rs) to be robust.
+        let token = self.state_store()?.load()?.token;
+        if let Some(t) = &token {
+            bearer_header(t.trim()).context("La sesión guardada contiene un token inválido; inicie sesión nuevamente")?;
+        }
+        Ok(token)
+    }
+
+    /// Read the token meta if present. Returns None when no meta is saved.
+    /// Older layouts are migrated (see `TokenMeta::migrate`).
+    pub fn load_token_meta(&self) -> Result<Option<TokenMeta>> {
+        if self.ephemeral {
+            return Ok(None);
+        }
+        Ok(self.state_store()?.load()?.meta)
+    }
+
+    /// Update meta.clean_exit flag to the provided value. Creates meta if missing.
+    pub fn set_clean_exit_meta(&self, clean: bool) -> Result<()> {
+        let _guard = crate::shutdown::lock_state_writes();
+        self.write_clean_exit_meta(clean)
+    }
+
+    /// `set_clean_exit_meta` for a caller already holding the shutdown
+    /// write lock.
+    pub(crate) fn write_clean_exit_meta(&self, clean: bool) -> Result<()> {
+        if self.ephemeral {
+            return Ok(());
+        }
+        let store = self.state_store()?;
+        // A malformed state file starts over from the defaults.
+        let mut state = store.load().unwrap_or_default();
+        let mut meta = state.meta.unwrap_or(TokenMeta { version: TOKEN_META_VERSION, ..TokenMeta::default() });
+        meta.clean_exit = clean;
+        state.meta = Some(meta);
+        store.save(&state)
+    }
+
+    /// Record `now` (unix seconds) as the session's `last_seen`. Does
+    /// nothing when no session is saved.
+    pub fn touch_last_seen(&self, now: u64) -> Result<()> {
+        if self.ephemeral {
+            return Ok(());
+        }
+        let store = self.state_store()?;
+        let _guard = crate::shutdown::lock_state_writes();
+        let mut state = store.load()?;
+        match state.meta.as_mut() {
+            Some(meta) if state.token.is_some() => meta.last_seen = Some(now),
+            _ => return Ok(()),
+        }
+        store.save(&state)
+    }
+
+    /// Clear the persisted token and meta in the project folder.
+    pub fn clear_persisted_token_in_project(&self) {
+        if self.ephemeral {
+            return;
+        }
+        // Without a folder there is nothing saved to clear.
+        if let Ok(store) = self.state_store() {
+            let _guard = crate::shutdown::lock_state_writes();
+            store.clear();
+        }
+    }
+
+    /// The session state store in `state_dir`.
+    pub fn state_store(&self) -> Result<StateStore> {
+        Ok(StateStore::new(&self.state_dir()?))
+    }
+
+    /// Folder holding the session file: the override from `with_state_dir`
+    /// or the project folder.
+    pub fn state_dir(&self) -> Result<PathBuf> {
+        match &self.state_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => find_project_dir(),
+        }
+    }
+
+    /// Resolve the gateway host once per run so an unknown name fails with
+    /// a message naming it instead of a generic connection error. IP
+    /// literals and unparsable URLs are left to reqwest.
+    pub fn preflight_dns(&self) -> Result<()> {
+        // With fallback gateways an unresolvable host is handled like any
+        // other connection failure: by moving on to the next gateway.
+        if self.dns_checked.load(Ordering::SeqCst) || !self.fallback_urls.is_empty() {
+            return Ok(());
+        }
+        let url = match reqwest::Url::parse(&self.base_url) {
+            Ok(url) => url,
+            Err(_) => return Ok(()),
+        };
+        // Through a proxy the host is resolved by the proxy, which may
+        // know names this machine doesn't.
+        if proxy_from_env(&url, self.transport.proxy.as_deref()).is_some() {
+            return Ok(());
+        }
+        // `domain()` is `None` for IP addresses.
+        if let Some(host) = url.domain() {
+            let port = url.port_or_known_default().unwrap_or(80);
+            crate::dns::resolve_host(host, port)?;
+        }
+        self.dns_checked.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Send a request through the retry layer. Transient failures are
+    /// retried per `self.retry` while the shared budget lasts; when the
+    /// budget is spent the call fails fast. `what` names the operation for
+    /// error context ("register", "auth", ...).
+    fn send(&self, client: &Client, req: RequestBuilder, what: &str) -> Result<Response> {
+        // Keep a copy for a possible retry before the builder is consumed.
+        // Bodies that can't be cloned are sent once; uploads use
+        // `send_rebuilt` instead.
+        let mut spare = Some(req);
+        self.send_rebuilt(client, what, || {
+            let req = spare.take();
+            spare = req.as_ref().and_then(RequestBuilder::try_clone);
+            Ok(req)
+        })
+    }
+
+    /// `send` with a request made by `build` for every attempt, so
+    /// streaming bodies (an upload's file reader) start over from the
+    /// first byte on a retry. `build` returns `None` when no further
+    /// attempt is possible.
+    fn send_rebuilt<F>(&self, client: &Client, what: &str, mut build: F) -> Result<Response>
+    where
+        F: FnMut() -> Result<Option<RequestBuilder>>,
+    {
+        let mut pending = build()?.with_context(|| format!("no {} request to send", what))?;
+        self.preflight_dns()?;
+        let mut attempt = 0;
+        loop {
+            let outcome = self.send_once(client, pending, what);
+            let retryable = attempt < self.retry.max_retries
+                && match &outcome {
+                    Ok(res) => self.retry.retries_status(res.status()),
+                    Err(e) => is_retryable_error(e),
+                };
+            if !retryable {
+                return outcome;
+            }
+            let next = match build()? {
+                Some(next) => next,
+                None => return outcome,
+            };
+            if !self.retry.budget.try_take() {
+                let cause = match outcome {
+                    Ok(res) => res.status().to_string(),
+                    Err(e) => e.to_string(),
+                };
+                anyhow::bail!("presupuesto de reintentos agotado ({}: {})", what, cause);
+            }
+            attempt += 1;
+            if self.verbose() {
+                eprintln!("[verbose] reintentando {} ({}/{})", what, attempt, self.retry.max_retries);
+            }
+            self.retry.report(attempt);
+            std::thread::sleep(self.retry.backoff(attempt));
+            pending = next;
+        }
+    }
+
+    /// Send a request once through `client`, signed when a signing secret
+    /// is set, mapping redirect-limit failures to a clear message and, in
+    /// verbose mode, reporting where redirects ended up.
+    fn send_once(&self, client: &Client, req: RequestBuilder, what: &str) -> Result<Response> {
+        self.redirect_hops.store(0, Ordering::SeqCst);
+        self.set_last_correlation_id(None);
+        let started = Instant::now();
+        let sent = req.build().and_then(|request| self.execute_with_failover(client, request, what));
+        let res = match sent {
+            Ok(res) => res,
+            Err(e) => {
+                if let Some(limit) = std::error::Error::source(&e).and_then(|s| s.downcast_ref::<TooManyRedirects>()) {
+                    anyhow::bail!("{}", limit);
+                }
+                if is_protocol_mismatch(&e) {
+                    let cause = anyhow::Error::new(e);
+                    anyhow::bail!(
+                        "No se pudo negociar el protocolo con el gateway ({}). Es posible que exija HTTP/2 (pruebe --http2) \
+                         u otra versión de TLS (--min-tls); si el problema sigue, actualice el cliente.",
+                        cause.root_cause()
+                    );
+                }
+                // Behind a proxy, a failed connection is a failed connection
+                // to the proxy: name it so a stale `ALL_PROXY` is easy to
+                // spot.
+                let proxy = e.url().filter(|_| e.is_connect()).and_then(|url| proxy_from_env(url, self.transport.proxy.as_deref()));
+                if let Some(proxy) = proxy {
+                    let msg = format!(
+                        "No se pudo conectar a través del proxy {}. Compruebe que el proxy está activo y accesible, \
+                         o quite {} para conectar directamente.",
+                        proxy, proxy.source
+                    );
+                    return Err(anyhow::Error::new(e).context(msg));
+                }
+                if e.is_timeout() {
+                    // Keep the reqwest error in the chain: the retry layer
+                    // looks for it.
+                    let msg = match (e.is_connect(), self.transport.connect_timeout) {
+                        (true, Some(connect)) => format!(
+                            "No se pudo conectar con el gateway en {} s; compruebe la red y la URL (--connect-timeout)",
+                            connect.as_secs()
+                        ),
+                        _ => format!(
+                            "El gateway no empezó a responder en {} s; puede estar sobrecargado (--timeout para esperar más)",
+                            self.timeout.as_secs()
+                        ),
+                    };
+                    return Err(anyhow::Error::new(e).context(msg));
+                }
+                return Err(anyhow::Error::new(e).context(format!("Failed to send {} request", what)));
+            }
+        };
+        if self.verbose() {
+            eprintln!("[verbose] {}: primer byte tras {} ms", what, started.elapsed().as_millis());
+        }
+        let correlation_id = CORRELATION_HEADERS
+            .iter()
+            .find_map(|name| res.headers().get(*name))
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        self.set_last_correlation_id(correlation_id);
+        let hops = self.redirect_hops.load(Ordering::SeqCst);
+        if self.verbose() && hops > 0 {
+            eprintln!("[verbose] {} redirigido {} vez/veces; URL final: {}", what, hops, res.url());
+        }
+        Ok(res)
+    }
+
+    // Execute `request`, signed when a signing secret is set. When the
+    // gateway can't be reached and fallbacks are configured, the request
+    // is sent to each of the other gateways in turn; the first one that
+    // answers becomes the active gateway. Streaming bodies can't be sent
+    // twice, so an upload fails over only on its next attempt.
+    fn execute_with_failover(&self, client: &Client, mut request: reqwest::blocking::Request, what: &str) -> reqwest::Result<Response> {
+        let gateways = 1 + self.fallback_urls.len();
+        let mut current = self.active_gateway.load(Ordering::SeqCst).min(gateways - 1);
+        let mut tried = 1;
+        loop {
+            let spare = if tried < gateways { request.try_clone() } else { None };
+            if let Some(secret) = &self.hmac_secret {
+                sign_request(&mut request, secret.as_bytes(), (self.signing_clock)())?;
+            }
+            let err = match client.execute(request) {
+                Err(e) if e.is_connect() => e,
+                other => {
+                    self.active_gateway.store(current, Ordering::SeqCst);
+                    return other;
+                }
+            };
+            let next = (current + 1) % gateways;
+            let rebased = spare.and_then(|mut spare| {
+                let url = rebase_url(spare.url(), self.gateway_url(current), self.gateway_url(next))?;
+                *spare.url_mut() = url;
+                Some(spare)
+            });
+            request = match rebased {
+                Some(next_request) => next_request,
+                None => {
+                    // A body that can't be resent: point the next attempt
+                    // at the next gateway instead.
+                    if tried < gateways {
+                        self.active_gateway.store(next, Ordering::SeqCst);
+                    }
+                    return Err(err);
+                }
+            };
+            if self.verbose() {
+                eprintln!(
+                    "[verbose] {}: {} no responde; probando {}",
+                    what,
+                    self.gateway_url(current),
+                    self.gateway_url(next)
+                );
+            }
+            current = next;
+            tried += 1;
+        }
+    }
+
+    fn set_last_correlation_id(&self, id: Option<String>) {
+        if let Ok(mut last) = self.last_correlation_id.lock() {
+            *last = id;
+        }
+    }
+
+    /// Correlation id (`X-Request-Id` / `X-Correlation-Id`) of the most
+    /// recent response, if the gateway sent one.
+    pub fn last_correlation_id(&self) -> Option<String> {
+        self.last_correlation_id.lock().ok().and_then(|id| id.clone())
+    }
+
+    /// Append one interaction to the debug dump file, if enabled. This is
+    /// best-effort: a failure to write the dump never breaks the flow.
+    fn dump_interaction(&self, method: &str, url: &str, request: serde_json::Value, status: u16, response: &str) {
+        let path = match &self.debug_dump {
+            Some(p) => p,
+            None => return,
+        };
+        // Prefer structured bodies so nested secrets can be redacted;
+        // fall back to the raw text for non-JSON responses.
+        let response = match serde_json::from_str::<serde_json::Value>(response) {
+            Ok(v) => redact_secrets(v),
+            Err(_) => json!(response),
+        };
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let entry = json!({
+            "timestamp": timestamp,
+            "method": method,
+            "url": url,
+            "request": redact_secrets(request),
+            "status": status,
+            "response": response,
+        });
+        if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(path) {
+            let _ = writeln!(f, "{}", entry);
+        }
+    }
+
+    /// Low-level request to any gateway endpoint. `path` is appended to the
+    /// base URL, `body` (if any) is sent as JSON and the bearer token is
+    /// attached when present. The response is returned as-is, whatever
+    /// its status, so callers can reach endpoints that have no typed
+    /// wrapper yet. Transport failures are still errors.
+    pub fn request(&self, method: Method, path: &str, body: Option<&serde_json::Value>) -> Result<RawResponse> {
+        self.request_with_query(method, path, &[], body)
+    }
+
+    /// `request` with query parameters, encoded by `build_url`.
+    pub fn request_with_query(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+    ) -> Result<RawResponse> {
+        if !matches!(method, Method::GET | Method::HEAD | Method::OPTIONS) {
+            self.ensure_writable()?;
+        }
+        let url = self.build_url(path, query)?;
+        self.execute(method, url, body, path)
+    }
+
+    /// The URL of gateway endpoint `path` with `query` appended. Every
+    /// request URL is built here, with one policy:
+    /// - `path` is relative to the base URL, including any path prefix
+    ///   the base has (`http://host/api` + `/profile` is
+    ///   `http://host/api/profile`);
+    /// - leading and trailing slashes in `path` and a trailing slash on
+    ///   the base are ignored, so no URL ends in `/` or contains `//`;
+    /// - each path segment and each query name/value is percent-encoded
+    ///   (`@`, `+`, spaces, ...), so callers pass raw values.
+    pub fn build_url(&self, path: &str, query: &[(&str, &str)]) -> Result<Url> {
+        let base = self.active_gateway_url();
+        let mut url = Url::parse(base).with_context(|| format!("URL del gateway inválida: {}", base))?;
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| anyhow::anyhow!("URL del gateway inválida: {}", base))?;
+            segments.pop_if_empty();
+            segments.extend(path.split('/').filter(|s| !s.is_empty()));
+        }
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        Ok(url)
+    }
+
+    /// Shared implementation of `request`; `what` names the operation in
+    /// error messages ("register", "auth", ...). A request rejected for
+    /// its CSRF token is sent once more with a fresh token.
+    fn execute(&self, method: Method, url: Url, body: Option<&serde_json::Value>, what: &str) -> Result<RawResponse> {
+        let raw = self.execute_once(method.clone(), url.clone(), body, what)?;
+        if self.csrf.is_some() && needs_csrf(&method) && is_csrf_rejection(raw.status, &raw.text()) {
+            if self.verbose() {
+                eprintln!("[verbose] {}: token CSRF rechazado ({}); se pide uno nuevo", what, raw.status);
+            }
+            self.discard_csrf_token();
+            return self.execute_once(method, url, body, what);
+        }
+        Ok(raw)
+    }
+
+    fn execute_once(&self, method: Method, url: Url, body: Option<&serde_json::Value>, what: &str) -> Result<RawResponse> {
+        let mut req = self.client.request(method.clone(), url.clone()).headers(self.request_headers()?);
+        if needs_csrf(&method) {
+            if let Some(token) = self.csrf_token()? {
+                req = req.header(CSRF_HEADER, token);
+            }
+        }
+        if let Some(b) = body {
+            req = req.json(b);
+        }
+        let res = self.send(&self.client, req, what)?;
+        let mut status = res.status();
+        let mut headers = res.headers().clone();
+        let body_bytes = match res.bytes() {
+            Ok(b) => b.to_vec(),
+            // Headers arrived but the body stopped coming: the network, not
+            // the gateway's load, is the likely cause.
+            Err(e) if e.is_timeout() => {
+                return Err(anyhow::Error::new(e).context(format!(
+                    "La respuesta se detuvo a mitad de la transferencia ({} s sin completarse); la conexión pudo haberse cortado",
+                    self.timeout.as_secs()
+                )));
+            }
+            // Some misconfigured gateways compress the body but send the
+            // uncompressed Content-Length (or the reverse), and the body
+            // then fails to decode. reqwest hides the Content-Encoding of
+            // a body it decompresses, so any body failure on a GET counts;
+            // a GET is safe to repeat, so ask once more without
+            // compression.
+            Err(e) if method == Method::GET && (e.is_decode() || e.is_body()) => {
+                if self.verbose() {
+                    eprintln!("[verbose] {}: respuesta comprimida ilegible ({}); reintentando sin compresión", what, e);
+                }
+                let req = self.uncompressed_client.get(url.clone()).headers(self.request_headers()?);
+                let res = self.send(&self.uncompressed_client, req, what)?;
+                status = res.status();
+                headers = res.headers().clone();
+                res.bytes().map(|b| b.to_vec()).with_context(|| format!("Failed to read {} response", what))?
+            }
+            Err(_) => Vec::new(),
+        };
+        let body_bytes = decode_leftover_gzip(&mut headers, body_bytes);
+        let raw = RawResponse { status, headers, body: body_bytes };
+        let request_dump = body.cloned().unwrap_or(serde_json::Value::Null);
+        self.dump_interaction(method.as_str(), url.as_str(), request_dump, raw.status.as_u16(), &raw.text());
+        Ok(raw)
+    }
+
+    /// The CSRF token for the next state-changing request, fetched from
+    /// `GET /csrf` when none is cached. `None` when CSRF support is off.
+    fn csrf_token(&self) -> Result<Option<HeaderValue>> {
+        let slot = match &self.csrf {
+            Some(slot) => slot,
+            None => return Ok(None),
+        };
+        if let Some(token) = slot.lock().ok().and_then(|t| t.clone()) {
+            return Ok(Some(token));
+        }
+        let req = self.client.get(self.build_url(CSRF_PATH, &[])?).headers(self.request_headers()?);
+        let res = self.send(&self.client, req, "csrf")?;
+        let status = res.status();
+        if !status.is_success() {
+            anyhow::bail!("No se pudo obtener el token CSRF: {}", status);
+        }
+        // The token may come in the `X-CSRF-Token` header, a JSON body
+        // (`csrf_token` / `csrfToken` / `token`) or as plain text.
+        let from_header = res.headers().get(CSRF_HEADER).cloned();
+        let text = res.text().context("Failed to read csrf response")?;
+        let token = match from_header {
+            Some(value) => value,
+            None => {
+                let value = serde_json::from_str::<serde_json::Value>(&text)
+                    .ok()
+                    .and_then(|v| {
+                        ["csrf_token", "csrfToken", "token"]
+                            .iter()
+                            .find_map(|k| v.get(*k).and_then(|t| t.as_str()).map(str::to_string))
+                    })
+                    .unwrap_or_else(|| text.trim().to_string());
+                if value.is_empty() {
+                    anyhow::bail!("El servidor no devolvió un token CSRF");
+                }
+                HeaderValue::from_str(&value).context("token CSRF inválido")?
+            }
+        };
+        if let Ok(mut slot) = slot.lock() {
+            *slot = Some(token.clone());
+        }
+        Ok(Some(token))
+    }
+
+    // Forget the cached CSRF token so the next request fetches a new one.
+    fn discard_csrf_token(&self) {
+        if let Some(Ok(mut slot)) = self.csrf.as_ref().map(|s| s.lock()) {
+            *slot = None;
+        }
+    }
+
+    /// Run a typed call: send, then turn non-2xx statuses into
+    /// `ApiError`s and reject HTML pages (see `RawResponse::into_success`).
+    /// Returns the successful response.
+    fn call(&self, method: Method, path: &str, body: Option<&serde_json::Value>, what: &str, label: &str) -> Result<RawResponse> {
+        self.call_url(method, self.build_url(path, &[])?, body, what, label)
+    }
+
+    /// `call` for a URL that already carries a query string.
+    fn call_url(&self, method: Method, url: Url, body: Option<&serde_json::Value>, what: &str, label: &str) -> Result<RawResponse> {
+        let raw = self.execute(method, url, body, what)?;
+        if let Some(err) = upgrade_required_error(raw.status, &raw.headers) {
+            return Err(err);
+        }
+        raw.into_success(label)
+    }
+
+    /// `call` for idempotent GETs, answered from the cache while fresh.
+    fn cached_get(&self, path: &str, what: &str, label: &str) -> Result<RawResponse> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.call(Method::GET, path, None, what, label),
+        };
+        let key = self.cache_key(path);
+        if let Some(raw) = cache.get(&key) {
+            return Ok(raw);
+        }
+        let raw = self.call(Method::GET, path, None, what, label)?;
+        cache.put(key, raw.clone());
+        Ok(raw)
+    }
+
+    // Cache key of `GET path` for the current session: URL and token.
+    fn cache_key(&self, path: &str) -> String {
+        format!("{}{} {}", self.base_url, path, self.token.as_deref().unwrap_or(""))
+    }
+
+    /// Register a user by POSTing to /register. Returns a simple String
+    /// on success, or an error with the server response body on failure.
+    pub fn register(&self, req: &RegisterRequest) -> Result<String> {
+        self.ensure_writable()?;
+        if let Some(backend) = &self.backend {
+            backend.register(req)?;
+            return Ok("Registered".into());
+        }
+        self.call(Method::POST, "/register", Some(&json!(req)), "register", "Register")?;
+        Ok("Registered".into())
+    }
+
+    /// Perform login and build the AuthResponse. The token normally comes
+    /// in the JSON body; some gateways send it in a response header
+    /// instead (see `with_token_header`), and then the fields missing
+    /// from the body are taken from the token's claims.
+    pub fn login(&self, req: &AuthRequest) -> Result<AuthResponse> {
+        if let Some(backend) = &self.backend {
+            return backend.login(req);
+        }
+        let raw = self.call(Method::POST, "/auth", Some(&json!(req)), "auth", "Login")?;
+        let body: AuthBody = if raw.body.iter().all(u8::is_ascii_whitespace) {
+            AuthBody::default()
+        } else {
+            serde_json::from_slice(&raw.body).context("Parsing auth response json")?
+        };
+        let token = match body.token {
+            Some(token) => token,
+            None => self
+                .token_from_headers(&raw.headers)
+                .ok_or_else(|| anyhow::anyhow!("La respuesta de inicio de sesión no incluye un token"))?,
+        };
+        // Rejected here rather than on the next request, which would
+        // otherwise fail far from the login that caused it.
+        bearer_header(&token)?;
+        let claims = Profile::from_jwt(&token).unwrap_or_default();
+        Ok(AuthResponse {
+            nombre: body
+                .nombre
+                .filter(|n| !n.trim().is_empty())
+                .unwrap_or_else(|| crate::profile::greeting_name_from_jwt(&token)),
+            rol: body.rol.or_else(|| crate::profile::joined_roles(&token)).unwrap_or_default(),
+            user_id: body.user_id.or(claims.user_id).unwrap_or(serde_json::Value::Null),
+            correo: body.correo.or(claims.correo).unwrap_or_else(|| req.correo.clone()),
+            token,
+        })
+    }
+
+    // First configured token header present, without a `Bearer ` prefix.
+    fn token_from_headers(&self, headers: &HeaderMap) -> Option<String> {
+        self.token_headers.iter().find_map(|name| {
+            let value = headers.get(name)?.to_str().ok()?.trim();
+            let token = value.strip_prefix("Bearer ").or_else(|| value.strip_prefix("bearer ")).unwrap_or(value);
+            (!token.is_empty()).then(|| token.to_string())
+        })
+    }
+
+    /// Ask the gateway whether it is up, by default with `GET /health`
+    /// (see `with_health_check`). A status outside the expected set (any
+    /// 2xx unless configured) and transport failures are errors.
+    pub fn health_check(&self) -> Result<()> {
+        let check = &self.health;
+        let raw = self.execute(check.method.as_method(), self.build_url(&check.path, &[])?, check.body.as_ref(), "health")?;
+        if let Some(err) = upgrade_required_error(raw.status, &raw.headers) {
+            return Err(err);
+        }
+        if !check.accepts(raw.status) {
+            return Err(ApiError { label: "Health check".into(), status: raw.status, body: raw.text(), summary: None }.into());
+        }
+        Ok(())
+    }
+
+    /// Query `GET /capabilities` to learn which optional features the
+    /// backend supports. Callers should treat an error as "unknown" and
+    /// fall back to offering every feature.
+    pub fn get_capabilities(&self) -> Result<Capabilities> {
+        if let Some(backend) = &self.backend {
+            return backend.capabilities();
+        }
+        let raw = self.cached_get("/capabilities", "capabilities", "Capabilities")?;
+        let caps: Capabilities = serde_json::from_slice(&raw.body).context("Parsing capabilities json")?;
+        Ok(caps)
+    }
+
+    /// Exchange the current token for a fresh one via `POST /refresh`
+    /// (the current token is the credential). Returns the new token; the
+    /// caller decides whether to store it.
+    pub fn refresh(&self) -> Result<String> {
+        if self.token.is_none() {
+            anyhow::bail!("No hay una sesión activa para renovar");
+        }
+        if let Some(backend) = &self.backend {
+            return backend.refresh(self.token.as_deref().unwrap_or_default());
+        }
+        let raw = self.call(Method::POST, "/refresh", None, "refresh", "Refresh")?;
+        let resp: RefreshResponse = serde_json::from_slice(&raw.body).context("Parsing refresh response json")?;
+        Ok(resp.token)
+    }
+
+    /// Withdraw the user's data-processing consent via
+    /// `POST /consent/revoke` (authenticated).
+    pub fn revoke_consent(&self) -> Result<()> {
+        self.ensure_writable()?;
+        if self.token.is_none() {
+            anyhow::bail!("No hay una sesión activa para revocar el consentimiento");
+        }
+        if let Some(backend) = &self.backend {
+            return backend.revoke_consent(self.token.as_deref().unwrap_or_default());
+        }
+        self.call(Method::POST, "/consent/revoke", None, "consent_revoke", "Consent revoke")?;
+        Ok(())
+    }
+
+    /// Ask the backend (`GET /verify`) whether the current token is still
+    /// valid, which local decoding cannot tell for revoked tokens.
+    /// `Ok(true)`: valid. `Ok(false)`: the server rejected it (401/403).
+    /// `Err`: no authoritative answer (network failure, 5xx, ...).
+    pub fn verify_token(&self) -> Result<bool> {
+        if self.token.is_none() {
+            anyhow::bail!("No hay una sesión activa para verificar");
+        }
+        if let Some(backend) = &self.backend {
+            return backend.verify_token(self.token.as_deref().unwrap_or_default());
+        }
+        let raw = self.execute(Method::GET, self.build_url("/verify", &[])?, None, "verify")?;
+        match raw.status {
+            s if s.is_success() => Ok(true),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Ok(false),
+            s => anyhow::bail!("Verify failed: {} - {}", s, raw.text()),
+        }
+    }
+
+    /// Fetch the logged-in user's profile from `GET /profile`. Requires a
+    /// token; combine with `Profile::from_jwt` via `Profile::merge` to fill
+    /// fields the server leaves out.
+    pub fn get_profile(&self) -> Result<Profile> {
+        if let Some(backend) = &self.backend {
+            return backend.profile(self.token.as_deref().unwrap_or_default());
+        }
+        let raw = self.cached_get("/profile", "profile", "Profile")?;
+        let profile: Profile = serde_json::from_slice(&raw.body).context("Parsing profile json")?;
+        Ok(profile)
+    }
+
+    /// Send a profile edit with `PATCH /profile` (authenticated). An
+    /// empty `patch` is not sent: `Ok(None)` without a request, so the
+    /// server never sees an empty body. Once it is accepted, the server's
+    /// advisories (see `response_warnings`), usually none.
+    pub fn update_profile(&self, patch: &ProfilePatch) -> Result<Option<Vec<String>>> {
+        if patch.is_empty() {
+            return Ok(None);
+        }
+        self.ensure_writable()?;
+        if self.token.is_none() {
+            anyhow::bail!("No hay una sesión activa para editar el perfil");
+        }
+        if let Some(backend) = &self.backend {
+            return backend.update_profile(self.token.as_deref().unwrap_or_default(), patch).map(Some);
+        }
+        let raw = self.call(Method::PATCH, "/profile", Some(&json!(patch)), "profile_update", "Profile update")?;
+        // The next `get_profile` must show the edit, not the cached copy.
+        if let Some(cache) = &self.cache {
+            cache.remove(&self.cache_key("/profile"));
+        }
+        Ok(Some(response_warnings(&raw.text())))
+    }
+
+    /// One page of `GET /users`, paged and filtered by `params`. The
+    /// response is returned as JSON since its shape varies between
+    /// gateways (a bare array or an object with `items` and a total).
+    pub fn list_users(&self, params: &QueryParams) -> Result<serde_json::Value> {
+        let pairs = params.pairs();
+        let query: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let raw = self.call_url(Method::GET, self.build_url("/users", &query)?, None, "users", "List users")?;
+        serde_json::from_slice(&raw.body).context("Parsing users json")
+    }
+
+    /// Upload a profile picture using multipart/form-data. The backend
+    /// path `/upload` is used here and the multipart field is `foto`.
+    /// The function adds the Authorization header if a token is present.
+    pub fn upload_profile_picture(&self, file_path: &PathBuf) -> Result<String> {
+        self.upload_profile_picture_with_progress(file_path, |_, _| {})?;
+        Ok("Upload OK".into())
+    }
+
+    /// `upload_profile_picture` reporting progress to `on_progress(sent,
+    /// total)` in bytes of the image file, for embedders that draw their
+    /// own progress (the CLI spinner is one such consumer).
+    ///
+    /// The callback runs on the HTTP client's I/O thread, not the
+    /// caller's, hence `Send + 'static`: forward the numbers (a channel,
+    /// an atomic) rather than touching UI state directly, and return
+    /// quickly since the upload waits for it. It is called after every
+    /// chunk read, with `sent` increasing up to `total`; a retried upload
+    /// starts again from 0. Signed requests buffer the whole file before
+    /// sending (see `signing.rs`), so with signing on the callback reaches
+    /// `total` before the first byte leaves.
+    pub fn upload_profile_picture_with_progress<F>(&self, file_path: &PathBuf, on_progress: F) -> Result<UploadResponse>
+    where
+        F: FnMut(u64, u64) + Send + 'static,
+    {
+        // The MIME type follows the file's content (see `image.rs`);
+        // unknown content is sent as JPEG, the prototype's original
+        // default. The UI rejects such files before getting here.
+        let file_name = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("image.jpg");
+        let mime = crate::image::detect_file_mime(file_path).unwrap_or("image/jpeg");
+        if let Some(backend) = &self.backend {
+            self.ensure_writable()?;
+            let bytes = std::fs::read(file_path).context("Failed to open image file")?;
+            let mut on_progress = on_progress;
+            on_progress(bytes.len() as u64, bytes.len() as u64);
+            return backend.upload_picture(self.token.as_deref().unwrap_or_default(), file_name, mime, bytes);
+        }
+        let on_progress = Arc::new(Mutex::new(on_progress));
+
+        // Each attempt opens the file again: a reader consumed by a failed
+        // attempt would send an empty body.
+        self.send_upload(file_name, || {
+            let file = File::open(file_path).context("Failed to open image file")?;
+            let total = file.metadata().context("Failed to read image file size")?.len();
+            let reader = ProgressReader { inner: file, sent: 0, total, on_progress: Arc::clone(&on_progress) };
+            let part = multipart::Part::reader_with_length(reader, total).file_name(file_name.to_string());
+            set_part_mime(part, mime)
+        })
+    }
+
+    /// Upload an image already in memory (e.g. read from stdin) as
+    /// `file_name`. The MIME type is sniffed from the bytes, then taken
+    /// from the name's extension, then defaults to JPEG.
+    pub fn upload_profile_picture_bytes(&self, bytes: Vec<u8>, file_name: &str) -> Result<UploadResponse> {
+        let mime = crate::image::sniff_mime(&bytes)
+            .or_else(|| crate::image::mime_for_path(Path::new(file_name)))
+            .unwrap_or("image/jpeg");
+        self.send_upload(file_name, || {
+            let part = multipart::Part::bytes(bytes.clone()).file_name(file_name.to_string());
+            set_part_mime(part, mime)
+        })
+    }
+
+    // POST the part made by `make_part` to /upload and map the reply. The
+    // part is made again for every attempt (see `send_rebuilt`).
+    fn send_upload<P>(&self, file_name: &str, mut make_part: P) -> Result<UploadResponse>
+    where
+        P: FnMut() -> Result<multipart::Part>,
+    {
+        self.ensure_writable()?;
+        // auth-be exposes the upload handler at /upload and expects the
+        // multipart field to be named "foto". The URL is built per attempt
+        // since a failed attempt may switch gateways (see `with_fallback_urls`).
+        let build = || {
+            let url = self.build_url("/upload", &[])?;
+            // Use field name "foto" to match auth-be's HandlerGuardarFotoPerfil
+            let mut form = multipart::Form::new().part("foto", make_part()?);
+            for (name, value) in self.upload_fields.iter() {
+                form = form.text(name.to_string(), value.to_string());
+            }
+            // Extra headers, plus the auth header if a token is present
+            let mut req = self.client.post(url).multipart(form).headers(self.request_headers()?);
+            if let Some(token) = self.csrf_token()? {
+                req = req.header(CSRF_HEADER, token);
+            }
+            Ok(Some(req))
+        };
+
+        let res = self.send_rebuilt(&self.client, "upload", build).map_err(|e| {
+            // Distinguish a connection lost while the image was being sent
+            // from a server that answered with an error status.
+            if is_interrupted_transfer(&e) {
+                let cause = e.root_cause().to_string();
+                anyhow::anyhow!("La conexión se interrumpió durante la subida ({})", cause)
+            } else {
+                e
+            }
+        })?;
+        let raw = RawResponse::read(res);
+        // The multipart body is binary; record only which file was sent.
+        let url = self.build_url("/upload", &[])?;
+        self.dump_interaction("POST", url.as_str(), json!({"foto": file_name}), raw.status.as_u16(), &raw.text());
+        if let Some(err) = upgrade_required_error(raw.status, &raw.headers) {
+            return Err(err);
+        }
+        if self.csrf.is_some() && is_csrf_rejection(raw.status, &raw.text()) {
+            // The image stream can't be replayed; the next upload fetches
+            // a fresh token.
+            self.discard_csrf_token();
+        }
+        if !raw.status.is_success() {
+            return Err(ApiError {
+                label: "Upload".into(),
+                status: raw.status,
+                body: raw.text(),
+                summary: Some("El servidor rechazó la imagen".into()),
+            }
+            .into());
+        }
+        let raw = raw.into_success("Upload")?;
+        Ok(UploadResponse { status: raw.status, body: raw.text() })
+    }
+
+    /// Download the logged-in user's picture from `GET /profile/picture`
+    /// to `dest`. Without an extension, `dest` gets the one matching the
+    /// reply's Content-Type (`foto` becomes `foto.png`). Returns the path
+    /// written.
+    ///
+    /// Bytes arrive in `<dest>.part` first, which is renamed when the
+    /// download completes. If a `.part` file is left from an interrupted
+    /// download, only the missing bytes are asked for (`Range`), and the
+    /// download starts over when the server doesn't support ranges.
+    ///
+    /// An existing file is never replaced here (see
+    /// `download_profile_picture_with_progress`), and a body whose content
+    /// is not an image is refused before anything is written.
+    pub fn download_profile_picture(&self, dest: &Path) -> Result<PathBuf> {
+        self.download_profile_picture_with_progress(dest, false, |_, _| {})
+    }
+
+    /// `download_profile_picture` reporting `on_progress(received, total)`
+    /// in bytes of the whole file (a resumed download starts at the bytes
+    /// already on disk). `total` is 0 when the server doesn't say. An
+    /// existing file at the final path is replaced only with `overwrite`;
+    /// otherwise the download fails naming it.
+    pub fn download_profile_picture_with_progress<F>(&self, dest: &Path, overwrite: bool, mut on_progress: F) -> Result<PathBuf>
+    where
+        F: FnMut(u64, u64),
+    {
+        // With an extension the final path is known up front: don't
+        // download what can't be saved.
+        if !overwrite && dest.extension().is_some() && dest.exists() {
+            return Err(destination_exists(dest));
+        }
+        if let Some(backend) = &self.backend {
+            let (mime, bytes) = backend
+                .download_picture(self.token.as_deref().unwrap_or_default())?
+                .ok_or_else(|| anyhow::anyhow!(NO_PICTURE_MSG))?;
+            if crate::image::sniff_mime(&bytes).is_none() {
+                anyhow::bail!(NOT_AN_IMAGE_MSG);
+            }
+            let final_path = picture_path(dest, Some(&mime));
+            if !overwrite && final_path.exists() {
+                return Err(destination_exists(&final_path));
+            }
+            std::fs::write(&final_path, &bytes).with_context(|| format!("No se pudo guardar {}", final_path.display()))?;
+            on_progress(bytes.len() as u64, bytes.len() as u64);
+            return Ok(final_path);
+        }
+        let mut part_name = dest.as_os_str().to_owned();
+        part_name.push(".part");
+        let part_path = PathBuf::from(part_name);
+        let mut resume_from = std::fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+
+        // Ranges count bytes of the encoded body, so ask for it
+        // uncompressed.
+        let client = &self.uncompressed_client;
+        let mut res = loop {
+            let url = self.build_url(PROFILE_PICTURE_PATH, &[])?;
+            let mut req = client.get(url).headers(self.request_headers()?);
+            if resume_from > 0 {
+                req = req.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+            }
+            let res = self.send(client, req, "download")?;
+            // The partial file already holds everything (or more than the
+            // server has now): start over.
+            if res.status() == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+                resume_from = 0;
+                continue;
+            }
+            // A range other than the one asked for can't be appended.
+            if resume_from > 0 && res.status() == StatusCode::PARTIAL_CONTENT && content_range_start(&res) != Some(resume_from) {
+                resume_from = 0;
+                continue;
+            }
+            break res;
+        };
+        let status = res.status();
+        if let Some(err) = upgrade_required_error(status, res.headers()) {
+            return Err(err);
+        }
+        if status == StatusCode::NOT_FOUND {
+            anyhow::bail!(NO_PICTURE_MSG);
+        }
+        if !status.is_success() {
+            let body = res.text().unwrap_or_default();
+            return Err(ApiError { label: "Download picture".into(), status, body, summary: None }.into());
+        }
+        if is_html_content_type(res.headers()) {
+            anyhow::bail!(HTML_RESPONSE_MSG);
+        }
+
+        // A 206 continues where the partial file ends; a 200 (the range
+        // was ignored) replaces it.
+        let (start, total) = match res.headers().get(reqwest::header::CONTENT_RANGE).and_then(|v| v.to_str().ok()) {
+            Some(range) if status == StatusCode::PARTIAL_CONTENT && resume_from > 0 => parse_content_range(range).unwrap_or((resume_from, 0)),
+            _ => (0, res.content_length().unwrap_or(0)),
+        };
+        let mime = res
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+            .filter(|m| !m.is_empty());
+
+        // Check the content before touching the disk. A resumed download
+        // was checked when its first bytes arrived.
+        let mut buf = [0u8; 64 * 1024];
+        let mut head = 0;
+        if start == 0 {
+            while head < crate::image::SNIFF_LEN {
+                let n = res.read(&mut buf[head..]).context("La descarga se interrumpió; vuelva a intentarlo para continuarla")?;
+                if n == 0 {
+                    break;
+                }
+                head += n;
+            }
+            if crate::image::sniff_mime(&buf[..head]).is_none() {
+                anyhow::bail!(NOT_AN_IMAGE_MSG);
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(start > 0)
+            .truncate(start == 0)
+            .open(&part_path)
+            .with_context(|| format!("No se pudo escribir {}", part_path.display()))?;
+        let mut received = start;
+        on_progress(received, total);
+        loop {
+            let n = if head > 0 {
+                std::mem::take(&mut head)
+            } else {
+                res.read(&mut buf).context("La descarga se interrumpió; vuelva a intentarlo para continuarla")?
+            };
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buf[..n]).with_context(|| format!("No se pudo escribir {}", part_path.display()))?;
+            received += n as u64;
+            on_progress(received, total);
+        }
+        drop(file);
+        if total > 0 && received < total {
+            anyhow::bail!("La descarga se interrumpió ({} de {} bytes); vuelva a intentarlo para continuarla", received, total);
+        }
+
+        let final_path = picture_path(dest, mime.as_deref());
+        if !overwrite && final_path.exists() {
+            let _ = std::fs::remove_file(&part_path);
+            return Err(destination_exists(&final_path));
+        }
+        std::fs::rename(&part_path, &final_path).with_context(|| format!("No se pudo guardar {}", final_path.display()))?;
+        Ok(final_path)
+    }
+}
+
+/// Path of the logged-in user's picture (see `download_profile_picture`).
+pub const PROFILE_PICTURE_PATH: &str = "/profile/picture";
+
+// `dest`, or with no extension the one for `mime` (`foto` becomes
+// `foto.png`).
+fn picture_path(dest: &Path, mime: Option<&str>) -> PathBuf {
+    match (dest.extension(), mime.and_then(crate::image::extension_for_mime)) {
+        (None, Some(ext)) => dest.with_extension(ext),
+        _ => dest.to_path_buf(),
+    }
+}
+
+// Error for a download whose destination already exists.
+fn destination_exists(path: &Path) -> anyhow::Error {
+    anyhow::anyhow!("Ya existe {}; no se reemplazó", path.display())
+}
+
+// Start offset of a 206 reply's `Content-Range`.
+fn content_range_start(res: &Response) -> Option<u64> {
+    let value = res.headers().get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+    parse_content_range(value).map(|(start, _)| start)
+}
+
+/// Start offset and complete length from a `Content-Range: bytes a-b/n`
+/// header; the length is 0 when the server sends `*`.
+fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.trim().parse().ok()?;
+    let total = if total.trim() == "*" { 0 } else { total.trim().parse().ok()? };
+    Some((start, total))
+}
+
+/// UploadResponse
+///
+/// The server's reply to a successful upload. The body is kept as text
+/// since the backend's format is not fixed.
+#[derive(Debug, Clone)]
+pub struct UploadResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl UploadResponse {
+    /// Advisories the server attached to the upload (see
+    /// `response_warnings`).
+    pub fn warnings(&self) -> Vec<String> {
+        response_warnings(&self.body)
+    }
+}
+
+/// Non-fatal advisories in a successful reply: the `warnings` (or
+/// `advertencias`) list of a JSON body, e.g. `{"warnings": ["la imagen
+/// fue recomprimida"]}`. Entries may be strings or objects with a
+/// `message`/`mensaje`. Empty for any other body.
+pub fn response_warnings(body: &str) -> Vec<String> {
+    let v: serde_json::Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+    let items = match v.get("warnings").or_else(|| v.get("advertencias")).and_then(|w| w.as_array()) {
+        Some(items) => items,
+        None => return Vec::new(),
+    };
+    items
+        .iter()
+        .filter_map(|item| match item {
+            serde_json::Value::String(message) => Some(message.clone()),
+            _ => item.get("message").or_else(|| item.get("mensaje"))?.as_str().map(str::to_string),
+        })
+        .filter(|message| !message.trim().is_empty())
+        .collect()
+}
+
+/// Longest value, in characters, of an extra upload field.
+pub const MAX_UPLOAD_FIELD_LEN: usize = 500;
+
+/// UploadFields
+///
+/// Extra text fields sent in the upload form next to the image, for
+/// backends that expect more than `foto` (`descripcion`, `categoria`,
+/// ...). Names are checked and values cleaned as they are added, so
+/// whatever is stored is safe to send.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadFields {
+    fields: BTreeMap<String, String>,
+}
+
+impl UploadFields {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a field. The name must be 1–64 ASCII letters, digits, `_` or
+    /// `-`, and not `foto`. Control characters are removed from the value,
+    /// which is then trimmed and cut to `MAX_UPLOAD_FIELD_LEN` characters;
+    /// a value left empty is not sent.
+    pub fn insert(&mut self, name: &str, value: &str) -> Result<()> {
+        let name = name.trim();
+        let valid = !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            anyhow::bail!("Nombre de campo inválido: '{}' (use letras, números, '_' o '-')", name);
+        }
+        if name.eq_ignore_ascii_case("foto") {
+            anyhow::bail!("El campo 'foto' está reservado para la imagen");
+        }
+        let clean: String = value.chars().filter(|c| !c.is_control()).collect();
+        let clean: String = clean.trim().chars().take(MAX_UPLOAD_FIELD_LEN).collect();
+        if clean.is_empty() {
+            self.fields.remove(name);
+        } else {
+            self.fields.insert(name.to_string(), clean);
+        }
+        Ok(())
+    }
+
+    /// Fields in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+// Reader over the image file that reports each chunk to a callback. The
+// callback is shared so every upload attempt reports to the same one.
+struct ProgressReader<R, F> {
+    inner: R,
+    sent: u64,
+    total: u64,
+    on_progress: Arc<Mutex<F>>,
+}
+
+impl<R: std::io::Read, F: FnMut(u64, u64)> std::io::Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.sent += n as u64;
+            if let Ok(mut on_progress) = self.on_progress.lock() {
+                (*on_progress)(self.sent, self.total);
+            }
+        }
+        Ok(n)
+    }
+}
+
+// `Authorization: Bearer <token>` value; tokens with control characters
+// such as a newline can't be sent as a header.
+fn bearer_header(token: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(&format!("Bearer {}", token))
+        .map_err(|_| anyhow::anyhow!("El token recibido no es válido como encabezado HTTP"))
+}
+
+/// Write `contents` to a sibling temp file and rename it over `path`, so
+/// readers see either the old file or the new one, never a partial write.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    let mut f = File::create(&tmp)?;
+    f.write_all(contents)?;
+    f.sync_all()?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Set the MIME type of an upload part, failing instead of panicking when
+/// `mime` doesn't parse.
+pub fn set_part_mime(part: multipart::Part, mime: &str) -> Result<multipart::Part> {
+    part.mime_str(mime).with_context(|| format!("tipo MIME inválido para la imagen: {:?}", mime))
+}
+
+/// Whether `err` is a transport failure after the connection was made
+/// (reset, broken pipe, closed before the response), as opposed to a
+/// failed connect, a timeout or an error status.
+fn is_interrupted_transfer(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<reqwest::Error>())
+        .any(|e| (e.is_request() || e.is_body()) && !e.is_connect() && !e.is_timeout())
+}
+
+/// `url`, built on gateway base `from`, moved onto gateway base `to`:
+/// same path below the base, same query. `None` when `url` is not under
+/// `from` or either base doesn't parse.
+fn rebase_url(url: &Url, from: &str, to: &str) -> Option<Url> {
+    let from = Url::parse(from).ok()?;
+    let mut rebased = Url::parse(to).ok()?;
+    if url.origin() != from.origin() {
+        return None;
+    }
+    let rest = url.path().strip_prefix(from.path().trim_end_matches('/'))?;
+    let path = format!("{}{}", rebased.path().trim_end_matches('/'), rest);
+    rebased.set_path(&path);
+    rebased.set_query(url.query());
+    Some(rebased)
+}
+
+/// A clear error for `426 Upgrade Required`: the gateway wants another
+/// protocol (named in its `Upgrade` header, e.g. `h2c` or `TLS/1.3`).
+fn upgrade_required_error(status: StatusCode, headers: &HeaderMap) -> Option<anyhow::Error> {
+    if status != StatusCode::UPGRADE_REQUIRED {
+        return None;
+    }
+    let wanted = headers
+        .get(reqwest::header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| format!(" (solicita: {})", v))
+        .unwrap_or_default();
+    Some(anyhow::anyhow!(
+        "El gateway exige otro protocolo: 426 Upgrade Required{}. Pruebe --http2 o --min-tls 1.3, o actualice el cliente.",
+        wanted
+    ))
+}
+
+/// Whether a transport error comes from a failed TLS handshake or an
+/// HTTP version the server doesn't speak, rather than from the network or
+/// an untrusted certificate. Decided from the error types in the chain,
+/// not from messages, which include the URL.
+fn is_protocol_mismatch(err: &reqwest::Error) -> bool {
+    if !(err.is_connect() || err.is_request()) {
+        return false;
+    }
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        if let Some(h2) = e.downcast_ref::<h2::Error>() {
+            // HTTP/1.x bytes read as HTTP/2 frames, or a server that
+            // wants HTTP/1.1.
+            return h2.reason().is_some_and(|reason| {
+                [h2::Reason::FRAME_SIZE_ERROR, h2::Reason::PROTOCOL_ERROR, h2::Reason::HTTP_1_1_REQUIRED].contains(&reason)
+            });
+        }
+        if e.downcast_ref::<hyper::Error>().is_some_and(hyper::Error::is_parse) {
+            return true;
+        }
+        if let Some(tls) = e.downcast_ref::<native_tls::Error>() {
+            return !is_certificate_error(tls);
+        }
+        source = e.source();
+    }
+    false
+}
+
+// A handshake that failed on the server's certificate (untrusted,
+// expired, wrong host) rather than on the protocol. native-tls has no
+// error kinds, so this reads the TLS error's own text, which never holds
+// the URL.
+fn is_certificate_error(err: &native_tls::Error) -> bool {
+    err.to_string().to_ascii_lowercase().contains("certificate")
+}
+
+/// Whether `method` changes server state and so needs a CSRF token.
+fn needs_csrf(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Whether a response rejects the request's CSRF token: 419 (the
+/// Laravel-style "page expired") or a 403 whose body mentions CSRF.
+fn is_csrf_rejection(status: StatusCode, body: &str) -> bool {
+    status.as_u16() == 419 || (status == StatusCode::FORBIDDEN && body.to_ascii_lowercase().contains("csrf"))
+}
+
+/// Whether the headers declare an HTML body (`Content-Type: text/html`).
+fn is_html_content_type(headers: &HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.to_ascii_lowercase().starts_with("text/html"))
+        .unwrap_or(false)
+}
+
+/// Build the underlying reqwest client. The redirect policy follows up to
+/// `max_redirects` hops, recording the count in `hops` so `send` can
+/// report it, and fails with `TooManyRedirects` beyond the limit.
+/// Requests fail once `timeout` elapses. With `gzip` the client asks for
+/// compressed responses and decodes them.
+fn build_http_client(
+    max_redirects: usize,
+    timeout: Duration,
+    transport: &Transport,
+    hops: &Arc<AtomicUsize>,
+    gzip: bool,
+) -> Result<Client> {
+    let hops = Arc::clone(hops);
+    let policy = Policy::custom(move |attempt| {
+        // `previous()` holds every URL already requested, so its length is
+        // the number of the redirect being attempted now.
+        let n = attempt.previous().len();
+        if n > max_redirects {
+            let last_location = attempt.url().to_string();
+            return attempt.error(TooManyRedirects { max: max_redirects, last_location });
+        }
+        hops.store(n, Ordering::SeqCst);
+        attempt.follow()
+    });
+    let mut builder = Client::builder().redirect(policy).timeout(timeout).gzip(gzip);
+    if transport.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+    if let Some(min) = transport.min_tls {
+        builder = builder.min_tls_version(min);
+    }
+    if let Some(connect) = transport.connect_timeout {
+        builder = builder.connect_timeout(connect);
+    }
+    if let Some(url) = &transport.proxy {
+        let proxy = reqwest::Proxy::all(url.as_str())
+            .with_context(|| format!("URL de proxy inválida: {} (use http://host:puerto o https://host:puerto)", url))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &transport.ca_cert {
+        let pem = std::fs::read(path).with_context(|| format!("No se pudo leer el certificado CA en {}", path.display()))?;
+        let cert = reqwest::Certificate::from_pem(&pem)
+            .with_context(|| format!("El archivo {} no contiene un certificado CA en formato PEM", path.display()))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    builder.build().context("Failed to build HTTP client")
+}
+
+/// Decompress a body that still says `Content-Encoding: gzip`. reqwest
+/// removes that header from bodies it decompresses, so it is left only on
+/// replies it did not decode: those to the client without gzip (see
+/// `uncompressed_client`) from gateways that compress anyway, error pages
+/// included. Without this their text would be binary noise in error
+/// messages. A body that isn't valid gzip is returned unchanged.
+fn decode_leftover_gzip(headers: &mut HeaderMap, body: Vec<u8>) -> Vec<u8> {
+    let gzip = headers
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("gzip") || v.trim().eq_ignore_ascii_case("x-gzip"));
+    if !gzip || !body.starts_with(&[0x1f, 0x8b]) {
+        return body;
+    }
+    let mut decoded = Vec::new();
+    match flate2::read::MultiGzDecoder::new(body.as_slice()).read_to_end(&mut decoded) {
+        Ok(_) => {
+            headers.remove(reqwest::header::CONTENT_ENCODING);
+            headers.remove(reqwest::header::CONTENT_LENGTH);
+            decoded
+        }
+        Err(_) => body,
+    }
+}
+
+/// Replace the value of any secret-looking key (see `REDACTED_KEYS`) with
+/// `***`, recursing into nested objects and arrays.
+fn redact_secrets(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    if REDACTED_KEYS.contains(&k.to_lowercase().as_str()) {
+                        (k, json!("***"))
+                    } else {
+                        (k, redact_secrets(v))
+                    }
+                })
+                .collect(),
+        ),
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.into_iter().map(redact_secrets).collect()),
+        other => other,
+    }
+}
+
+/// Locate the folder holding the CLI's files (token, meta, config). See
+/// `locate_project_dir` for the lookup order.
+pub(crate) fn find_project_dir() -> Result<PathBuf> {
+    let non_empty = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    Ok(locate_project_dir(
+        non_empty(PROJECT_DIR_ENV),
+        non_empty("CARGO_MANIFEST_DIR"),
+        std::env::current_exe(),
+        std::env::current_dir(),
+    )?)
+}
+
+/// Environment variable that short-circuits the project folder lookup.
+pub const PROJECT_DIR_ENV: &str = "NEUMODIAG_CONFIG_DIR";
+
+/// Error returned when no folder can be determined at all: there is no
+/// override, the executable's location is unknown or has no
+/// `Cargo.toml` above it, and the working directory is unavailable.
+#[derive(Debug)]
+pub struct ProjectDirError {
+    pub cwd_error: String,
+}
+
+impl std::fmt::Display for ProjectDirError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "No se pudo determinar la carpeta de datos ({}); defina {} con una ruta válida",
+            self.cwd_error, PROJECT_DIR_ENV
+        )
+    }
+}
+
+impl std::error::Error for ProjectDirError {}
+
+/// Pick the project folder from, in order: the `NEUMODIAG_CONFIG_DIR`
+/// override, `CARGO_MANIFEST_DIR` (set by `cargo run`), the nearest
+/// folder above the executable whose `Cargo.toml` is this crate's (or
+/// names it as a workspace member), else the nearest folder with any
+/// `Cargo.toml`, and finally the working directory. Inputs are passed in
+/// so the lookup can be tested with a failing `current_exe`.
+pub fn locate_project_dir(
+    override_dir: Option<PathBuf>,
+    manifest_dir: Option<PathBuf>,
+    exe: std::io::Result<PathBuf>,
+    cwd: std::io::Result<PathBuf>,
+) -> std::result::Result<PathBuf, ProjectDirError> {
+    if let Some(dir) = override_dir.or(manifest_dir) {
+        return Ok(dir);
+    }
+
+    match exe {
+        Ok(exe) => {
+            // Walk upwards from the executable location looking for a
+            // `Cargo.toml` file. This heuristic finds the project root
+            // in common development layouts (cargo run, target/debug
+            // exe, etc.). In a workspace the nearest one is usually the
+            // workspace root, so keep going until this crate's own
+            // manifest turns up and settle for the nearest otherwise.
+            let mut nearest = None;
+            for dir in exe.ancestors().skip(1).filter(|dir| dir.join("Cargo.toml").exists()) {
+                if let Some(found) = package_dir_in(dir) {
+                    return Ok(found);
+                }
+                nearest.get_or_insert_with(|| dir.to_path_buf());
+            }
+            if let Some(dir) = nearest {
+                debug_log(&format!("no se encontró el Cargo.toml de {}; se usa {}", PACKAGE_NAME, dir.display()));
+                return Ok(dir);
+            }
+            debug_log(&format!("no se encontró Cargo.toml sobre {}; se usa el directorio actual", exe.display()));
+        }
+        Err(e) => debug_log(&format!("no se pudo obtener la ruta del ejecutable ({}); se usa el directorio actual", e)),
+    }
+
+    cwd.map_err(|e| ProjectDirError { cwd_error: e.to_string() })
+}
+
+// Package name looked for in `Cargo.toml` files by `locate_project_dir`.
+const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
+
+// This crate's folder when `dir/Cargo.toml` is its manifest, or a
+// workspace manifest listing it among its `members` (plain paths or
+// `dir/*`).
+fn package_dir_in(dir: &Path) -> Option<PathBuf> {
+    let manifest = read_manifest(dir)?;
+    if is_this_package(&manifest) {
+        return Some(dir.to_path_buf());
+    }
+    let members = manifest.get("workspace")?.get("members")?.as_array()?;
+    members
+        .iter()
+        .filter_map(|member| member.as_str())
+        .flat_map(|member| match member.strip_suffix("/*") {
+            Some(parent) => std::fs::read_dir(dir.join(parent))
+                .map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())).collect())
+                .unwrap_or_default(),
+            None => vec![dir.join(member)],
+        })
+        .find(|member| read_manifest(member).is_some_and(|m| is_this_package(&m)))
+}
+
+fn read_manifest(dir: &Path) -> Option<toml::Table> {
+    toml::from_str(&std::fs::read_to_string(dir.join("Cargo.toml")).ok()?).ok()
+}
+
+fn is_this_package(manifest: &toml::Table) -> bool {
+    manifest.get("package").and_then(|p| p.get("name")).and_then(|n| n.as_str()) == Some(PACKAGE_NAME)
+}
+
+// Diagnostics for setup problems, printed only when `NEUMODIAG_DEBUG` is
+// set. Used where no `ApiClient` (and thus no `--verbose`) is available.
+fn debug_log(message: &str) {
+    if std::env::var_os("NEUMODIAG_DEBUG").is_some_and(|v| !v.is_empty()) {
+        eprintln!("[debug] {}", message);
+    }
 }
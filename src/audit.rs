@@ -0,0 +1,160 @@
+// Audit log
+// ---------
+// Actions with data-protection relevance (e.g. revoking consent) and the
+// menu's session events (login, upload, logout) are appended to
+// `.neumodiag_audit.jsonl` in the project folder, one JSON object per
+// line: `{"ts": <unix secs>, "action": "...", ...}`. The log is local
+// evidence for the user; it never contains tokens or passwords, and user
+// ids and e-mails are masked (`mask_identifier`) before they are written.
+//
+// "Historial de sesión" reads it back (`read_events`), newest first,
+// masking identifiers again so logs written before masking don't show
+// them either.
+//
+// Nothing is written in `--ephemeral` mode. `reset` removes the file.
+
+use crate::api::ApiClient;
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Audit log file, next to the token files.
+pub const AUDIT_FILE_NAME: &str = ".neumodiag_audit.jsonl";
+/// Events per page in "Historial de sesión".
+pub const HISTORY_PAGE_SIZE: usize = 10;
+// Detail fields that identify the user, stored masked.
+const IDENTIFIER_KEYS: [&str; 2] = ["user_id", "correo"];
+
+/// Append `action` with extra `details` (a JSON object, merged into the
+/// entry) to the audit log in `api`'s state folder. `user_id` and
+/// `correo` are masked on the way in.
+pub fn record(api: &ApiClient, action: &str, details: serde_json::Value) -> Result<()> {
+    if api.is_ephemeral() {
+        return Ok(());
+    }
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut entry = json!({"ts": ts, "action": action});
+    if let (Some(entry), Some(details)) = (entry.as_object_mut(), details.as_object()) {
+        for (k, v) in details {
+            let v = match v {
+                Value::Null => Value::Null,
+                Value::String(s) if IDENTIFIER_KEYS.contains(&k.as_str()) => Value::String(mask_identifier(s)),
+                other if IDENTIFIER_KEYS.contains(&k.as_str()) => Value::String(mask_identifier(&other.to_string())),
+                other => other.clone(),
+            };
+            entry.insert(k.clone(), v);
+        }
+    }
+    let path = api.state_dir()?.join(AUDIT_FILE_NAME);
+    let mut f = OpenOptions::new().create(true).append(true).open(&path).context("opening audit log")?;
+    writeln!(f, "{}", entry).context("writing audit log")?;
+    Ok(())
+}
+
+/// AuditEvent
+///
+/// One entry of the audit log: when, what, and the remaining fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub ts: u64,
+    pub action: String,
+    pub details: Map<String, Value>,
+}
+
+impl AuditEvent {
+    /// One line for the history view, e.g.
+    /// "2026-10-16 14:03 UTC  Inicio de sesión (usuario ***07)".
+    pub fn describe(&self) -> String {
+        let label = match self.action.as_str() {
+            "login" => "Inicio de sesión",
+            "upload" => "Foto de perfil subida",
+            "logout" => "Cierre de sesión",
+            "consent_revoked" => "Consentimiento revocado",
+            other => other,
+        };
+        let who: Vec<String> = [("user_id", "usuario"), ("correo", "correo")]
+            .iter()
+            .filter_map(|(key, name)| {
+                let value = match self.details.get(*key)? {
+                    Value::String(s) => s.clone(),
+                    Value::Null => return None,
+                    other => other.to_string(),
+                };
+                Some(format!("{} {}", name, mask_identifier(&value)))
+            })
+            .collect();
+        let when = format_utc(self.ts);
+        if who.is_empty() {
+            format!("{}  {}", when, label)
+        } else {
+            format!("{}  {} ({})", when, label, who.join(", "))
+        }
+    }
+}
+
+/// Parse audit log text, newest event first. Lines that are not a JSON
+/// object with `ts` and `action` are skipped; events with the same `ts`
+/// keep the later line first.
+pub fn parse_events(text: &str) -> Vec<AuditEvent> {
+    let mut events: Vec<AuditEvent> = text
+        .lines()
+        .filter_map(|line| {
+            let Value::Object(mut details) = serde_json::from_str(line.trim()).ok()? else {
+                return None;
+            };
+            let ts = details.remove("ts")?.as_u64()?;
+            let action = details.remove("action")?.as_str()?.to_string();
+            Some(AuditEvent { ts, action, details })
+        })
+        .collect();
+    events.reverse();
+    events.sort_by_key(|e| std::cmp::Reverse(e.ts));
+    events
+}
+
+/// Events in `api`'s audit log, newest first; empty when there is no log
+/// (or in `--ephemeral` mode).
+pub fn read_events(api: &ApiClient) -> Result<Vec<AuditEvent>> {
+    if api.is_ephemeral() {
+        return Ok(Vec::new());
+    }
+    let path = api.state_dir()?.join(AUDIT_FILE_NAME);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => Ok(parse_events(&text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+/// Hide most of an identifier for display: e-mails keep their first
+/// character and domain ("a***@example.com"), anything else its last two
+/// characters ("***07"); short values are hidden entirely.
+pub fn mask_identifier(value: &str) -> String {
+    if let Some((user, domain)) = value.split_once('@') {
+        let first: String = user.chars().take(1).collect();
+        return format!("{}***@{}", first, domain);
+    }
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 4 {
+        return "***".into();
+    }
+    format!("***{}", chars[chars.len() - 2..].iter().collect::<String>())
+}
+
+// Unix seconds as "YYYY-MM-DD HH:MM UTC" (civil-from-days conversion).
+fn format_utc(ts: u64) -> String {
+    let days = (ts / 86_400) as i64;
+    let secs = ts % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, secs / 3_600, secs % 3_600 / 60)
+}
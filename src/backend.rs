@@ -0,0 +1,271 @@
+// Gateway backend
+// ---------------
+// The gateway calls the menu makes (login, register, profile, pictures,
+// ...) behind the `Backend` trait. By default `ApiClient` sends them over
+// HTTP itself; `ApiClient::with_backend` hands every one of them to a
+// `Backend` instead, after the client's own local checks (read-only mode,
+// "no session"). Token storage, the config and everything else local stay
+// in the client.
+//
+// `FakeBackend` is the in-memory implementation: accounts, sessions and
+// pictures live in a mutex, so a menu test can log in, upload and look at
+// what the "server" got without a mock gateway. Its errors are
+// `ApiError`s with the statuses a real gateway would send, so the client's
+// error messages are exercised too.
+
+use crate::api::{ApiError, AuthRequest, AuthResponse, Capabilities, RegisterRequest, Role, UploadResponse};
+use crate::profile::{Profile, ProfilePatch};
+use anyhow::Result;
+use reqwest::StatusCode;
+use std::sync::Mutex;
+
+/// Backend
+///
+/// The gateway as seen by `ApiClient`'s typed calls. Calls that need a
+/// session get the client's current token.
+pub trait Backend: Send + Sync {
+    /// Optional features (`GET /capabilities`).
+    fn capabilities(&self) -> Result<Capabilities>;
+    /// Create an account (`POST /register`).
+    fn register(&self, req: &RegisterRequest) -> Result<()>;
+    /// Log in (`POST /auth`).
+    fn login(&self, req: &AuthRequest) -> Result<AuthResponse>;
+    /// Whether `token` is still valid (`GET /verify`).
+    fn verify_token(&self, token: &str) -> Result<bool>;
+    /// A fresh token for `token` (`POST /refresh`).
+    fn refresh(&self, token: &str) -> Result<String>;
+    /// The session's profile (`GET /profile`).
+    fn profile(&self, token: &str) -> Result<Profile>;
+    /// Apply a profile edit (`PATCH /profile`); returns the advisories.
+    fn update_profile(&self, token: &str, patch: &ProfilePatch) -> Result<Vec<String>>;
+    /// Store the profile picture (`POST /upload`).
+    fn upload_picture(&self, token: &str, file_name: &str, mime: &str, bytes: Vec<u8>) -> Result<UploadResponse>;
+    /// The profile picture and its MIME type (`GET /profile/picture`);
+    /// `None` when there is none.
+    fn download_picture(&self, token: &str) -> Result<Option<(String, Vec<u8>)>>;
+    /// Withdraw the data-processing consent (`POST /consent/revoke`).
+    fn revoke_consent(&self, token: &str) -> Result<()>;
+}
+
+/// FakeBackend
+///
+/// An in-memory gateway. Build it with `with_user` / `with_features`;
+/// `calls` lists what it was asked, in order, e.g. `["capabilities",
+/// "login ana@example.com", "upload perfil.jpg"]`.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    state: Mutex<FakeState>,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    // `None`: capabilities fail, so the menu shows every entry.
+    features: Option<Vec<String>>,
+    users: Vec<FakeUser>,
+    // Token -> index into `users`.
+    sessions: Vec<(String, usize)>,
+    calls: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct FakeUser {
+    nombre_completo: String,
+    correo: String,
+    contrasena: String,
+    rol: Role,
+    consent: bool,
+    picture: Option<(String, Vec<u8>)>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        FakeBackend::default()
+    }
+
+    /// Add a patient account.
+    pub fn with_user(self, nombre_completo: &str, correo: &str, contrasena: &str) -> Self {
+        self.lock().users.push(FakeUser {
+            nombre_completo: nombre_completo.into(),
+            correo: correo.into(),
+            contrasena: contrasena.into(),
+            rol: Role::Paciente,
+            consent: true,
+            picture: None,
+        });
+        self
+    }
+
+    /// Advertise `features` in the capabilities.
+    pub fn with_features(self, features: &[&str]) -> Self {
+        self.lock().features = Some(features.iter().map(|f| f.to_string()).collect());
+        self
+    }
+
+    /// Every call received so far.
+    pub fn calls(&self) -> Vec<String> {
+        self.lock().calls.clone()
+    }
+
+    /// The picture stored for `correo`, if any.
+    pub fn picture(&self, correo: &str) -> Option<Vec<u8>> {
+        self.lock().users.iter().find(|u| u.correo == correo)?.picture.as_ref().map(|(_, bytes)| bytes.clone())
+    }
+
+    /// Whether `correo` has an account.
+    pub fn has_user(&self, correo: &str) -> bool {
+        self.lock().users.iter().any(|u| u.correo == correo)
+    }
+
+    /// Whether `correo` still consents to data processing.
+    pub fn has_consent(&self, correo: &str) -> bool {
+        self.lock().users.iter().any(|u| u.correo == correo && u.consent)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FakeState> {
+        // A test that panicked mid-call leaves nothing half-written.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Log `call` and run `f` on the state.
+    fn call<T>(&self, call: String, f: impl FnOnce(&mut FakeState) -> Result<T>) -> Result<T> {
+        let mut state = self.lock();
+        state.calls.push(call);
+        f(&mut state)
+    }
+}
+
+impl FakeState {
+    // The account behind `token`, or the 401 a gateway sends.
+    fn user(&mut self, token: &str, label: &str) -> Result<&mut FakeUser> {
+        match self.sessions.iter().find(|(t, _)| t == token) {
+            Some(&(_, index)) => Ok(&mut self.users[index]),
+            None => Err(rejected(label, StatusCode::UNAUTHORIZED, "token inválido")),
+        }
+    }
+
+    // A new unsigned JWT-shaped token for the user at `index`.
+    fn open_session(&mut self, index: usize) -> String {
+        use base64::Engine as _;
+        let user = &self.users[index];
+        let claims = serde_json::json!({
+            "nombre_completo": user.nombre_completo,
+            "correo": user.correo,
+            "rol": user.rol.as_str(),
+            "user_id": index + 1,
+        });
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(claims.to_string());
+        let token = format!("fake.{}.{}", payload, self.sessions.len() + 1);
+        self.sessions.push((token.clone(), index));
+        token
+    }
+}
+
+// The error a gateway returns for a refused call.
+fn rejected(label: &str, status: StatusCode, message: &str) -> anyhow::Error {
+    let body = serde_json::json!({ "error": message }).to_string();
+    ApiError { label: label.into(), status, body, summary: None }.into()
+}
+
+impl Backend for FakeBackend {
+    fn capabilities(&self) -> Result<Capabilities> {
+        self.call("capabilities".into(), |state| match &state.features {
+            Some(features) => Ok(Capabilities { features: features.clone(), ..Capabilities::default() }),
+            None => Err(rejected("Capabilities", StatusCode::NOT_FOUND, "not found")),
+        })
+    }
+
+    fn register(&self, req: &RegisterRequest) -> Result<()> {
+        self.call(format!("register {}", req.correo), |state| {
+            if state.users.iter().any(|u| u.correo == req.correo) {
+                return Err(rejected("Register", StatusCode::CONFLICT, "el correo ya está registrado"));
+            }
+            state.users.push(FakeUser {
+                nombre_completo: req.nombre_completo.clone(),
+                correo: req.correo.clone(),
+                contrasena: req.contrasena.clone(),
+                rol: req.rol,
+                consent: req.acepta_tratamiento_datos,
+                picture: None,
+            });
+            Ok(())
+        })
+    }
+
+    fn login(&self, req: &AuthRequest) -> Result<AuthResponse> {
+        self.call(format!("login {}", req.correo), |state| {
+            let index = state
+                .users
+                .iter()
+                .position(|u| u.correo == req.correo && u.contrasena == req.contrasena)
+                .ok_or_else(|| rejected("Login", StatusCode::UNAUTHORIZED, "credenciales inválidas"))?;
+            let token = state.open_session(index);
+            let user = &state.users[index];
+            Ok(AuthResponse {
+                nombre: user.nombre_completo.clone(),
+                token,
+                rol: user.rol.as_str().into(),
+                user_id: (index + 1).into(),
+                correo: user.correo.clone(),
+            })
+        })
+    }
+
+    fn verify_token(&self, token: &str) -> Result<bool> {
+        self.call("verify".into(), |state| Ok(state.sessions.iter().any(|(t, _)| t == token)))
+    }
+
+    fn refresh(&self, token: &str) -> Result<String> {
+        self.call("refresh".into(), |state| {
+            let index = state
+                .sessions
+                .iter()
+                .find(|(t, _)| t == token)
+                .map(|&(_, index)| index)
+                .ok_or_else(|| rejected("Refresh", StatusCode::UNAUTHORIZED, "token inválido"))?;
+            Ok(state.open_session(index))
+        })
+    }
+
+    fn profile(&self, token: &str) -> Result<Profile> {
+        self.call("profile".into(), |state| {
+            let user = state.user(token, "Profile")?;
+            Ok(Profile {
+                nombre_completo: Some(user.nombre_completo.clone()),
+                correo: Some(user.correo.clone()),
+                rol: Some(user.rol),
+                ..Profile::default()
+            })
+        })
+    }
+
+    fn update_profile(&self, token: &str, patch: &ProfilePatch) -> Result<Vec<String>> {
+        self.call("update_profile".into(), |state| {
+            let user = state.user(token, "Profile update")?;
+            if let Some(nombre) = &patch.nombre_completo {
+                user.nombre_completo = nombre.clone();
+            }
+            if let Some(correo) = &patch.correo {
+                user.correo = correo.clone();
+            }
+            Ok(Vec::new())
+        })
+    }
+
+    fn upload_picture(&self, token: &str, file_name: &str, mime: &str, bytes: Vec<u8>) -> Result<UploadResponse> {
+        self.call(format!("upload {}", file_name), |state| {
+            state.user(token, "Upload")?.picture = Some((mime.to_string(), bytes));
+            Ok(UploadResponse { status: StatusCode::OK, body: String::new() })
+        })
+    }
+
+    fn download_picture(&self, token: &str) -> Result<Option<(String, Vec<u8>)>> {
+        self.call("download".into(), |state| Ok(state.user(token, "Download picture")?.picture.clone()))
+    }
+
+    fn revoke_consent(&self, token: &str) -> Result<()> {
+        self.call("revoke_consent".into(), |state| {
+            state.user(token, "Consent revoke")?.consent = false;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,119 @@
+// Login benchmark
+// ---------------
+// `neumodiag-cli bench login --email <test account> --iterations N` logs
+// in N times in a row and reports min/max/average/p95 latency, as text
+// or as one JSON object for dashboards. Each attempt is timed around
+// `ApiClient::login`, so retries and redirects count as part of the
+// latency the user would see. The run stops early after
+// `MAX_CONSECUTIVE_FAILURES` failed logins in a row, since the server or
+// the account is then clearly not usable.
+
+use crate::api::{ApiClient, AuthRequest};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Failed logins in a row that stop the benchmark.
+pub const MAX_CONSECUTIVE_FAILURES: usize = 3;
+
+/// LatencyStats
+///
+/// Summary of a set of request durations, in milliseconds.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+}
+
+impl LatencyStats {
+    /// Statistics for `samples`; `None` when there are none. The p95 uses
+    /// the nearest-rank method, so it is always one of the samples.
+    pub fn from_samples(samples: &[Duration]) -> Option<LatencyStats> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        ms.sort_by(|a, b| a.total_cmp(b));
+        let rank = ((0.95 * ms.len() as f64).ceil() as usize).max(1);
+        Some(LatencyStats {
+            count: ms.len(),
+            min_ms: ms[0],
+            max_ms: ms[ms.len() - 1],
+            avg_ms: ms.iter().sum::<f64>() / ms.len() as f64,
+            p95_ms: ms[rank - 1],
+        })
+    }
+}
+
+/// BenchReport
+///
+/// Outcome of a benchmark run. `latency` covers successful logins only.
+#[derive(Serialize, Debug, Clone)]
+pub struct BenchReport {
+    pub iterations: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub stopped_early: bool,
+    pub latency: Option<LatencyStats>,
+    pub last_error: Option<String>,
+}
+
+impl BenchReport {
+    /// Human-readable lines for the terminal.
+    pub fn render_text(&self) -> String {
+        let mut out = format!(
+            "Inicios de sesión: {} de {} correctos ({} fallidos)",
+            self.succeeded, self.iterations, self.failed
+        );
+        if self.stopped_early {
+            out.push_str(&format!("\nDetenido tras {} fallos seguidos.", MAX_CONSECUTIVE_FAILURES));
+        }
+        if let Some(l) = &self.latency {
+            out.push_str(&format!(
+                "\nLatencia: mín {:.1} ms, máx {:.1} ms, prom {:.1} ms, p95 {:.1} ms",
+                l.min_ms, l.max_ms, l.avg_ms, l.p95_ms
+            ));
+        }
+        if let Some(e) = &self.last_error {
+            out.push_str(&format!("\nÚltimo error: {}", e));
+        }
+        out
+    }
+}
+
+/// Log in `iterations` times with `req`, timing each attempt.
+pub fn bench_login(api: &ApiClient, req: &AuthRequest, iterations: usize) -> BenchReport {
+    let mut samples = Vec::new();
+    let mut failed = 0;
+    let mut consecutive_failures = 0;
+    let mut last_error = None;
+    let mut stopped_early = false;
+    for _ in 0..iterations {
+        let started = Instant::now();
+        match api.login(req) {
+            Ok(_) => {
+                samples.push(started.elapsed());
+                consecutive_failures = 0;
+            }
+            Err(e) => {
+                failed += 1;
+                consecutive_failures += 1;
+                last_error = Some(e.to_string());
+                if consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                    stopped_early = true;
+                    break;
+                }
+            }
+        }
+    }
+    BenchReport {
+        iterations,
+        succeeded: samples.len(),
+        failed,
+        stopped_early,
+        latency: LatencyStats::from_samples(&samples),
+        last_error,
+    }
+}
@@ -0,0 +1,54 @@
+// Response cache
+// --------------
+// A tiny in-memory cache for idempotent GETs whose answer rarely changes
+// within a session (`/capabilities`, `/profile`). Entries are keyed by
+// URL and the token used, so a different session never sees another
+// one's profile, and expire after a TTL. Only successful responses are
+// stored, and a successful write drops the entry it changes (a profile
+// edit drops `/profile`). Disable it with `--no-cache`.
+
+use crate::api::RawResponse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default freshness of cached responses, in seconds.
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 60;
+
+/// ResponseCache
+///
+/// Shared by every clone of an `ApiClient`.
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, RawResponse)>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration) -> Self {
+        ResponseCache { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// The cached response for `key` if it is still fresh.
+    pub fn get(&self, key: &str) -> Option<RawResponse> {
+        let entries = self.entries.lock().ok()?;
+        match entries.get(key) {
+            Some((stored, raw)) if stored.elapsed() < self.ttl => Some(raw.clone()),
+            _ => None,
+        }
+    }
+
+    /// Store `raw` under `key`, replacing any older entry.
+    pub fn put(&self, key: String, raw: RawResponse) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(key, (Instant::now(), raw));
+        }
+    }
+
+    /// Forget the entry for `key`, e.g. after a write made it stale.
+    pub fn remove(&self, key: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(key);
+        }
+    }
+}
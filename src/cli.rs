@@ -0,0 +1,303 @@
+// Command-line arguments
+// ----------------------
+// Flags accepted by the binary. Parsing lives in the library so the
+// options are documented in one place and `main.rs` stays tiny: it only
+// parses, applies the options to the `ApiClient` and starts the menu.
+
+use crate::api::ApiClient;
+use crate::config::{Config, TokenStore};
+use crate::signing::hmac_secret_from_env;
+use crate::stats::SummaryFormat;
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::time::Duration;
+use std::path::PathBuf;
+
+/// NeumoDiagnostics CLI options.
+#[derive(Parser, Debug, Default)]
+#[command(name = "neumodiag-cli", version, about = "Cliente de línea de comandos de NeumoDiagnostics")]
+pub struct Cli {
+    /// Use the `[env.<name>]` section of `neumodiag.toml` (e.g. staging).
+    #[arg(long = "env", value_name = "NAME", env = "NEUMODIAG_ENV", global = true)]
+    pub env: Option<String>,
+
+    /// Read this config file instead of `neumodiag.toml` in the project
+    /// folder. The file must exist.
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config_path: Option<PathBuf>,
+
+    /// Append every request/response (with secrets redacted) to this
+    /// file as JSON lines. Useful when debugging with the backend team.
+    #[arg(long, value_name = "PATH", global = true)]
+    pub debug_dump: Option<PathBuf>,
+
+    /// Show the server's reason when a login fails (e.g. unknown user vs
+    /// wrong password). For local/dev backends only: by default every
+    /// credential failure reads the same.
+    #[arg(long, global = true)]
+    pub auth_debug: bool,
+
+    /// Print request diagnostics (redirects, final URLs) to stderr.
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+
+    /// Maximum number of redirects followed per request.
+    #[arg(long, value_name = "N", default_value_t = 3, global = true)]
+    pub max_redirects: usize,
+
+    /// Answer "yes" to non-destructive confirmations (continue, confirm
+    /// registration, remember session). Destructive actions still ask.
+    #[arg(long, short = 'y', global = true)]
+    pub yes: bool,
+
+    /// Delete local data without asking (logout's saved session, `reset`),
+    /// for automation. The configuration file is never deleted this way.
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Seconds to wait for a reply to start, and for its body, before
+    /// giving up.
+    #[arg(long, value_name = "SECS", env = "NEUMODIAG_TIMEOUT_SECS", default_value_t = crate::api::DEFAULT_TIMEOUT_SECS, global = true)]
+    pub timeout: u64,
+
+    /// Seconds to wait for the connection to the gateway.
+    #[arg(long, value_name = "SECS", env = "NEUMODIAG_CONNECT_TIMEOUT_SECS", default_value_t = crate::api::DEFAULT_CONNECT_TIMEOUT_SECS, global = true)]
+    pub connect_timeout: u64,
+
+    /// Seconds a capabilities/profile response is reused.
+    #[arg(long, value_name = "SECS", env = "NEUMODIAG_CACHE_TTL_SECS", default_value_t = crate::cache::DEFAULT_CACHE_TTL_SECS, global = true)]
+    pub cache_ttl: u64,
+
+    /// Always ask the server; don't reuse capabilities/profile responses.
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+
+    /// Retries per request for transient failures (0 disables retries).
+    #[arg(long, value_name = "N", env = "NEUMODIAG_RETRIES", default_value_t = 0, global = true)]
+    pub retries: usize,
+
+    /// Total retries allowed for the whole invocation, across all requests.
+    #[arg(long, value_name = "N", env = "NEUMODIAG_RETRY_BUDGET", default_value_t = crate::retry::DEFAULT_RETRY_BUDGET, global = true)]
+    pub retry_budget: usize,
+
+    /// Keep the session in memory only: no token files are written and no
+    /// saved session is restored. Logging out or exiting forgets the token.
+    /// Same as `--token-store none`.
+    #[arg(long, global = true)]
+    pub ephemeral: bool,
+
+    /// Refuse everything that changes data on the server (registering,
+    /// uploads, revoking consent); their menu entries are hidden. For
+    /// demos on production data.
+    #[arg(long, env = "NEUMODIAG_READ_ONLY", global = true)]
+    pub read_only: bool,
+
+    /// Where the session token is kept. Overrides `token_store` in the
+    /// config file.
+    #[arg(long, value_enum, value_name = "STORE", env = "NEUMODIAG_TOKEN_STORE", global = true)]
+    pub token_store: Option<TokenStore>,
+
+    /// Show results as soon as an operation finishes instead of keeping
+    /// the spinner up for a minimum time. The spinner still shows while
+    /// work is running.
+    #[arg(long, global = true)]
+    pub fast_results: bool,
+
+    /// Fetch a CSRF token from `GET /csrf` and send it as `X-CSRF-Token`
+    /// on requests that change data, for gateways that require one.
+    #[arg(long, env = "NEUMODIAG_CSRF", value_parser = clap::builder::BoolishValueParser::new(), global = true)]
+    pub csrf: bool,
+
+    /// Use HTTP/2 from the start (h2c), for gateways that only speak
+    /// HTTP/2.
+    #[arg(long, global = true)]
+    pub http2: bool,
+
+    /// Lowest TLS version accepted from the gateway.
+    #[arg(long, value_enum, value_name = "VERSION", global = true)]
+    pub min_tls: Option<MinTls>,
+
+    /// Proxy for every request (`http://host:puerto`). Without it the
+    /// `HTTP_PROXY`/`HTTPS_PROXY`/`ALL_PROXY` variables apply.
+    #[arg(long, value_name = "URL", env = "NEUMODIAG_PROXY", global = true)]
+    pub proxy: Option<String>,
+
+    /// Extra CA certificate (PEM) trusted for the gateway's TLS.
+    #[arg(long, value_name = "FILE", env = "NEUMODIAG_CA_CERT", global = true)]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Session summary printed when the menu exits.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = SummaryFormat::Text)]
+    pub summary: SummaryFormat,
+
+    /// Developer tool: send arbitrary requests to the gateway instead of
+    /// opening the menu. Hidden from `--help`.
+    #[arg(long, hide = true)]
+    pub explore: bool,
+
+    /// Run a single command instead of the interactive menu.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// The config file from `--config-path`, or the default search, with
+    /// the `--env` section applied.
+    pub fn load_config(&self) -> Result<Config> {
+        let config = match &self.config_path {
+            Some(path) => Config::load_explicit(path)?,
+            None => Config::load()?,
+        };
+        config.for_env(self.env.as_deref())
+    }
+
+    /// Build the `ApiClient` described by the flags and `config`. The
+    /// gateway URL comes from `API_GATEWAY_URL`, then the config, then the
+    /// built-in default (`localhost` on `API_GATEWAY_PORT` or 8080).
+    pub fn build_api_client(&self, config: &Config) -> Result<ApiClient> {
+        let mut urls = config.gateway_urls_with_env();
+        let api = if urls.is_empty() {
+            ApiClient::from_env()?
+        } else {
+            let primary = urls.remove(0);
+            ApiClient::new(primary)?.with_fallback_urls(urls)?
+        };
+        let store = self.token_store.or(config.token_store).unwrap_or_default();
+        if store == TokenStore::Keyring {
+            anyhow::bail!("El almacén de tokens 'keyring' no está disponible en esta versión; use 'file' o 'none'");
+        }
+        let mut api = api
+            .with_max_redirects(self.max_redirects)?
+            .with_timeout(Duration::from_secs(self.timeout))?
+            .with_connect_timeout(Duration::from_secs(self.connect_timeout))?
+            .with_verbose(self.verbose)
+            .with_retries(self.retries, self.retry_budget)
+            .with_retry_statuses(&config.retry_status)?
+            .with_ephemeral(self.ephemeral || store == TokenStore::None)
+            .with_read_only(self.read_only)
+            .with_cache_ttl((!self.no_cache).then(|| Duration::from_secs(self.cache_ttl)))
+            .with_extra_headers(&config.extra_headers_with_env())?
+            .with_token_header(config.token_header.as_deref())?
+            .with_health_check(config.health.clone())?
+            .with_hmac_secret(hmac_secret_from_env())
+            .with_csrf(self.csrf)
+            .with_http2_prior_knowledge(self.http2)?
+            .with_min_tls_version(self.min_tls.map(MinTls::version))?
+            .with_proxy(self.proxy.clone())?
+            .with_ca_cert(self.ca_cert.clone())?;
+        if let Some(path) = &self.debug_dump {
+            api = api.with_debug_dump(path.clone());
+        }
+        Ok(api)
+    }
+}
+
+/// Non-interactive subcommands. Without one the CLI opens the menu.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Move a saved session between machines.
+    #[command(subcommand)]
+    Session(SessionCommand),
+    /// Log in without the menu. The password comes from
+    /// `NEUMODIAG_PASSWORD` or a prompt.
+    Login {
+        /// Account e-mail.
+        #[arg(long, value_name = "CORREO")]
+        email: String,
+        /// Save the session so later runs (and the menu) reuse it.
+        #[arg(long, conflicts_with = "no_remember")]
+        remember: bool,
+        /// Don't save the session (the default).
+        #[arg(long)]
+        no_remember: bool,
+    },
+    /// Upload a profile picture with the saved session. `--file -` reads
+    /// the image from stdin (`cat foto.jpg | neumodiag-cli upload --file -`).
+    Upload {
+        /// Image to upload, or `-` for stdin.
+        #[arg(long, value_name = "FILE")]
+        file: PathBuf,
+        /// File name sent for an image read from stdin (default
+        /// `imagen.<ext>` after the detected format).
+        #[arg(long, value_name = "NOMBRE")]
+        name: Option<String>,
+    },
+    /// Show who the saved session belongs to.
+    Whoami,
+    /// Exchange the saved token for a fresh one.
+    Refresh,
+    /// Ask the backend whether the saved token is still valid.
+    Verify,
+    /// Delete every file the CLI saved on this machine (asks first).
+    Reset,
+    /// Print the local usage counts (see `telemetry` in the config).
+    Usage,
+    /// Check registration rows from a CSV file (or stdin) without
+    /// registering anyone or contacting the server.
+    Validate {
+        /// CSV file; `-` or omitted reads stdin.
+        #[arg(value_name = "CSV")]
+        file: Option<PathBuf>,
+    },
+    /// Show where the session, config, audit log and other files are kept.
+    Paths {
+        /// Print the paths as one JSON object.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Measure request latency against the configured server.
+    #[command(subcommand)]
+    Bench(BenchCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SessionCommand {
+    /// Write the saved session, encrypted with a passphrase, to a file or stdout.
+    Export {
+        /// Destination file; prints to stdout when omitted.
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Read an exported session from a file or stdin and save it locally.
+    Import {
+        /// Source file; reads stdin when omitted.
+        #[arg(long, short, value_name = "FILE")]
+        input: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BenchCommand {
+    /// Log in repeatedly with a test account and report min/max/avg/p95
+    /// latency. The password comes from `NEUMODIAG_PASSWORD` or a prompt.
+    Login {
+        /// E-mail of the test account.
+        #[arg(long, value_name = "CORREO")]
+        email: String,
+        /// Number of logins.
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        iterations: usize,
+        /// Print the report as one JSON object.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Minimum TLS version (`--min-tls`).
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MinTls {
+    #[value(name = "1.2")]
+    Tls12,
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+impl MinTls {
+    /// The matching reqwest setting.
+    pub fn version(self) -> reqwest::tls::Version {
+        match self {
+            MinTls::Tls12 => reqwest::tls::Version::TLS_1_2,
+            MinTls::Tls13 => reqwest::tls::Version::TLS_1_3,
+        }
+    }
+}
@@ -0,0 +1,256 @@
+// Subcommand handlers
+// -------------------
+// Non-interactive entry points selected from the command line (see
+// `cli::Command`). Each handler runs one operation and returns, so the
+// CLI can be scripted without going through the menu. Prompts that are
+// still needed (e.g. passphrases) go to stderr so stdout stays usable
+// for redirection.
+
+use crate::api::{ApiClient, AuthRequest, FieldLimits, TokenMeta};
+use crate::bench::bench_login;
+use crate::image::{extension_for_mime, read_image_capped, sniff_mime, ImagePolicy};
+use crate::cli::{BenchCommand, Command, SessionCommand};
+use crate::ui::{can_prompt, current_profile, login_error_message, print_profile, print_warnings, prompt_password, refresh_session, reset_local_data, verify_session, UiOptions};
+use crate::paths::StoragePaths;
+use crate::prompt::Output;
+use crate::session::{export_session, import_session, SessionBundle};
+use crate::validate::{validate_csv, RowReport};
+use anyhow::{Context, Result};
+use dialoguer::Password;
+use std::io::Read;
+
+/// Dispatch a parsed subcommand.
+pub fn run(mut api: ApiClient, command: Command, opts: &UiOptions) -> Result<()> {
+    crate::crash::set_operation(&format!("{:?}", command));
+    match command {
+        Command::Session(SessionCommand::Export { output }) => session_export(&api, output),
+        Command::Session(SessionCommand::Import { input }) => session_import(&api, input),
+        Command::Login { email, remember, no_remember: _ } => login(api, email, remember, opts),
+        Command::Upload { file, name } => upload(api, file, name, &opts.images),
+        Command::Whoami => whoami(api),
+        Command::Refresh => refresh(api),
+        Command::Verify => verify(api),
+        Command::Reset => reset_local_data(&mut api, opts).map(|_| ()),
+        Command::Usage => usage(&api),
+        Command::Validate { file } => validate(file),
+        Command::Paths { json } => paths(&api, json, opts),
+        Command::Bench(BenchCommand::Login { email, iterations, json }) => bench(&api, email, iterations, json),
+    }
+}
+
+/// Load the saved token into `api`, failing with a hint when there is none.
+fn restore_saved_token(api: &mut ApiClient) -> Result<()> {
+    match api.load_token_from_project()? {
+        Some(t) => {
+            api.set_token(t.trim());
+            Ok(())
+        }
+        None => anyhow::bail!("No hay una sesión guardada. Inicie sesión desde el menú y elija recordarla."),
+    }
+}
+
+/// Log in with `email` and the password from `NEUMODIAG_PASSWORD` (or a
+/// prompt). Nothing is asked about remembering: the session is saved
+/// only with `--remember`, and then restored by the next menu start.
+fn login(mut api: ApiClient, email: String, remember: bool, opts: &UiOptions) -> Result<()> {
+    let contrasena = read_password()?;
+    let resp = api
+        .login(&AuthRequest { correo: email, contrasena })
+        .map_err(|e| anyhow::anyhow!(login_error_message(&e, opts.auth_debug, api.is_verbose())))?;
+    api.set_token(&resp.token);
+    if remember {
+        api.persist_token_to_project(&resp.token, true)?;
+        api.set_clean_exit_meta(true)?;
+        eprintln!("Sesión iniciada como {} y guardada en este equipo.", resp.nombre);
+    } else {
+        eprintln!("Sesión iniciada como {} (no se guardó; use --remember para recordarla).", resp.nombre);
+    }
+    Ok(())
+}
+
+/// Password from `NEUMODIAG_PASSWORD` (for scripts) or a prompt.
+fn read_password() -> Result<String> {
+    match std::env::var("NEUMODIAG_PASSWORD") {
+        Ok(p) if !p.is_empty() => Ok(p),
+        _ => prompt_password("Contraseña", can_prompt()),
+    }
+}
+
+/// Run the login benchmark and print its report (JSON on stdout with
+/// `--json`). Fails when no login succeeded.
+fn bench(api: &ApiClient, email: String, iterations: usize, json: bool) -> Result<()> {
+    if iterations == 0 {
+        anyhow::bail!("--iterations debe ser mayor que 0");
+    }
+    let req = AuthRequest { correo: email, contrasena: read_password()? };
+    let report = bench_login(api, &req, iterations);
+    if json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else {
+        println!("{}", report.render_text());
+    }
+    if report.succeeded == 0 {
+        anyhow::bail!("Ningún inicio de sesión tuvo éxito");
+    }
+    Ok(())
+}
+
+fn paths(api: &ApiClient, json: bool, opts: &UiOptions) -> Result<()> {
+    let paths = StoragePaths::resolve(api, opts.config_path.as_deref())?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&paths)?);
+    } else {
+        println!("{}", paths.render_text());
+    }
+    Ok(())
+}
+
+fn whoami(mut api: ApiClient) -> Result<()> {
+    restore_saved_token(&mut api)?;
+    let (profile, err) = current_profile(&api);
+    print_profile(&Output::stdout(), &profile);
+    if let Some(e) = err {
+        eprintln!("(Datos del token; no se pudo consultar el servidor: {})", e);
+    }
+    Ok(())
+}
+
+/// Passphrase from `NEUMODIAG_SESSION_PASSPHRASE` (for scripts) or an
+/// interactive prompt. Exports ask twice to catch typos.
+fn read_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(p) = std::env::var("NEUMODIAG_SESSION_PASSPHRASE") {
+        if !p.is_empty() {
+            return Ok(p);
+        }
+    }
+    if !can_prompt() {
+        anyhow::bail!("No hay una terminal para pedir la frase de paso. Defina NEUMODIAG_SESSION_PASSPHRASE.");
+    }
+    let mut prompt = Password::new();
+    prompt.with_prompt("Frase de paso de la sesión");
+    if confirm {
+        prompt.with_confirmation("Confirmar frase de paso", "Las frases de paso no coinciden.");
+    }
+    Ok(prompt.interact()?)
+}
+
+fn session_export(api: &ApiClient, output: Option<std::path::PathBuf>) -> Result<()> {
+    let token = match api.load_token_from_project()? {
+        Some(t) => t.trim().to_string(),
+        None => anyhow::bail!("No hay una sesión guardada para exportar. Inicie sesión y elija recordarla."),
+    };
+    let meta = serde_json::to_value(api.load_token_meta()?.unwrap_or_else(|| TokenMeta::new(true)))?;
+    let passphrase = read_passphrase(true)?;
+    let blob = export_session(&SessionBundle { token, meta }, &passphrase)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, format!("{}\n", blob)).context("writing exported session")?;
+            eprintln!("Sesión exportada a {}", path.display());
+        }
+        None => println!("{}", blob),
+    }
+    Ok(())
+}
+
+fn session_import(api: &ApiClient, input: Option<std::path::PathBuf>) -> Result<()> {
+    if api.is_ephemeral() {
+        anyhow::bail!("Con --ephemeral no se guardan sesiones; no hay dónde importarla.");
+    }
+    let blob = match input {
+        Some(path) => std::fs::read_to_string(&path).context("reading exported session")?,
+        None => {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s).context("reading exported session from stdin")?;
+            s
+        }
+    };
+    let passphrase = read_passphrase(false)?;
+    let bundle = import_session(&blob, &passphrase)?;
+    api.persist_token_with_meta(&bundle.token, bundle.token_meta()?)?;
+    // Mark the imported session as cleanly closed so the next start
+    // restores it, which is the point of importing it.
+    api.set_clean_exit_meta(true)?;
+    eprintln!("Sesión importada. Se restaurará al iniciar la aplicación.");
+    Ok(())
+}
+
+fn refresh(mut api: ApiClient) -> Result<()> {
+    restore_saved_token(&mut api)?;
+    if !refresh_session(&Output::stdout(), &mut api) {
+        anyhow::bail!("La sesión no pudo renovarse");
+    }
+    Ok(())
+}
+
+/// Upload `file` as the profile picture; `-` reads the image from stdin.
+/// Either way the image must pass `images` (type and size).
+fn upload(mut api: ApiClient, file: std::path::PathBuf, name: Option<String>, images: &ImagePolicy) -> Result<()> {
+    restore_saved_token(&mut api)?;
+    if file.as_os_str() != "-" {
+        images.check_file(&file)?;
+        let resp = api.upload_profile_picture_with_progress(&file, |_, _| {})?;
+        eprintln!("Imagen de perfil cargada exitosamente.");
+        print_warnings(&mut *crate::terminal::stderr(), &resp.warnings());
+        return Ok(());
+    }
+    let bytes = read_image_capped(std::io::stdin().lock(), images.max_bytes())?;
+    let name = match (name, sniff_mime(&bytes)) {
+        (Some(name), _) => name,
+        (None, Some(mime)) => format!("imagen.{}", extension_for_mime(mime).unwrap_or("jpg")),
+        (None, None) => anyhow::bail!("No se reconoce el formato de la imagen; indique --name con su extensión."),
+    };
+    images.check_bytes(&name, &bytes)?;
+    let resp = api.upload_profile_picture_bytes(bytes, &name)?;
+    eprintln!("Imagen de perfil cargada exitosamente.");
+    print_warnings(&mut *crate::terminal::stderr(), &resp.warnings());
+    Ok(())
+}
+
+/// Exit status mirrors the answer: success only for a valid token, so
+/// scripts can tell "invalid" and "couldn't ask" apart from the message.
+fn verify(mut api: ApiClient) -> Result<()> {
+    restore_saved_token(&mut api)?;
+    match verify_session(&Output::stdout(), &api) {
+        Some(true) => Ok(()),
+        Some(false) => anyhow::bail!("La sesión no es válida"),
+        None => anyhow::bail!("No se pudo verificar la sesión"),
+    }
+}
+
+/// Check registration rows without registering anyone. Fails when any
+/// row has problems, so scripts can stop before a real batch.
+fn validate(file: Option<std::path::PathBuf>) -> Result<()> {
+    let csv = match &file {
+        Some(path) if path.as_os_str() != "-" => {
+            std::fs::read_to_string(path).with_context(|| format!("No se pudo leer {}", path.display()))?
+        }
+        _ => {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s).context("reading rows from stdin")?;
+            s
+        }
+    };
+    let reports = validate_csv(&csv, &FieldLimits::from_env())?;
+    let invalid: Vec<&RowReport> = reports.iter().filter(|r| !r.is_valid()).collect();
+    for report in &invalid {
+        println!("Fila {}:", report.line);
+        for (field, message) in &report.problems {
+            println!("  - {}: {}", field.label(), message);
+        }
+    }
+    println!("{} filas válidas, {} con errores.", reports.len() - invalid.len(), invalid.len());
+    if !invalid.is_empty() {
+        anyhow::bail!("Hay {} filas con errores", invalid.len());
+    }
+    Ok(())
+}
+
+/// Print the usage file as JSON so the user can inspect or share it.
+fn usage(api: &ApiClient) -> Result<()> {
+    let data = crate::telemetry::load_usage(&api.state_dir()?)?;
+    if data.counts.is_empty() {
+        eprintln!("No hay datos de uso (active `telemetry = true` en neumodiag.toml para registrarlos).");
+    }
+    println!("{}", serde_json::to_string_pretty(&data.counts)?);
+    Ok(())
+}
@@ -0,0 +1,329 @@
+// Configuration file
+// ------------------
+// Optional settings read from `neumodiag.toml` in the project folder
+// (next to `Cargo.toml`, the same place the token files live). Every
+// key is optional and a missing file means "all defaults"; command-line
+// flags and environment variables take precedence over the file.
+//
+// Example:
+//
+// ```toml
+// gateway_url = "http://localhost:8080"
+//
+// [extra_headers]
+// "X-Tenant" = "clinicA"
+// "X-Api-Key" = "..."
+//
+// retry_status = [408, 429]
+//
+// allowed_image_exts = ["jpg", "png"]
+// max_upload_mb = 5
+//
+// greeting_style = "first"
+// bell = true
+//
+// [health]
+// method = "POST"
+// path = "/api/ping"
+//
+// [env.staging]
+// gateway_url = "https://staging.example.com"
+// ```
+//
+// `[env.<name>]` sections override the base settings for one server
+// environment and are selected with `--env <name>` / `NEUMODIAG_ENV`.
+// Only the connection keys (`ENV_KEYS`) may be overridden; keys a
+// section leaves out are inherited from the base and its `extra_headers`
+// are added to the base ones.
+
+use crate::api::Role;
+use crate::health::HealthCheck;
+use crate::image::{image_extensions, ImagePolicy, DEFAULT_MAX_UPLOAD_MB};
+use crate::profile::GreetingStyle;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File name of the configuration file inside the project folder.
+pub const CONFIG_FILE_NAME: &str = "neumodiag.toml";
+// Prefix of environment variables that add a header to every request,
+// e.g. `NEUMODIAG_HEADER_X_TENANT=clinicA` sends `X-Tenant: clinicA`.
+const HEADER_ENV_PREFIX: &str = "NEUMODIAG_HEADER_";
+// Keys an `[env.<name>]` section may set (the fields of `EnvConfig`).
+const ENV_KEYS: &[&str] = &["gateway_url", "extra_headers", "token_store", "token_header", "health"];
+
+/// Config
+///
+/// Parsed `neumodiag.toml`. Unknown keys are rejected so typos surface
+/// instead of being silently ignored.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Gateway base URL; `API_GATEWAY_URL` takes precedence.
+    pub gateway_url: Option<String>,
+    /// Headers attached to every request (API keys, tenant ids, ...).
+    pub extra_headers: BTreeMap<String, String>,
+    /// Where the session token is kept (`--token-store` overrides it).
+    pub token_store: Option<TokenStore>,
+    /// Response header carrying the token for gateways that don't return
+    /// it in the login body (default: `Authorization`, then
+    /// `X-Auth-Token`).
+    pub token_header: Option<String>,
+    /// Count menu actions in a local file (see `telemetry.rs`). Off by
+    /// default.
+    pub telemetry: bool,
+    /// Health check request (see `health.rs`); `GET /health` by default.
+    pub health: HealthCheck,
+    /// Ask before deleting local data (the saved session on logout,
+    /// `reset`). On when absent; `--force` skips the questions for one
+    /// run. Read it through `Config::confirm_destructive`.
+    pub confirm_destructive: Option<bool>,
+    /// Statuses retried on top of 502/503/504 when `--retries` is set,
+    /// e.g. `[408, 425, 429]`. Only 408, 423, 425, 429 and 5xx are
+    /// accepted.
+    pub retry_status: Vec<u16>,
+    /// Role pre-selected in the registration form (`doctor` or
+    /// `paciente`). Read it through `Config::default_role`.
+    pub default_role: Option<String>,
+    /// Register everyone with `default_role` without showing the role
+    /// question, for single-role deployments.
+    pub hide_role_prompt: bool,
+    /// Picture extensions offered by the file dialog and accepted for
+    /// upload, e.g. `["jpg", "png"]`; every supported format when
+    /// absent. Read it through `Config::image_policy`.
+    pub allowed_image_exts: Option<Vec<String>>,
+    /// Largest picture accepted for upload, in MB (default 10).
+    pub max_upload_mb: Option<u64>,
+    /// How greetings address the user: `full`, `first` or `formal`.
+    /// Read it through `Config::greeting_style`.
+    pub greeting_style: Option<GreetingStyle>,
+    /// Ring the terminal bell when an upload, download or registration
+    /// finishes. Off by default; read it through `Config::bell_enabled`.
+    pub bell: bool,
+    /// Named environment sections (`[env.staging]`, ...).
+    pub env: BTreeMap<String, EnvConfig>,
+}
+
+/// Session token storage backends.
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStore {
+    /// Token files in the project folder.
+    #[default]
+    File,
+    /// The operating system keyring (not available in this build).
+    Keyring,
+    /// Memory only, like `--ephemeral`.
+    None,
+}
+
+/// EnvConfig
+///
+/// Overrides for one server environment: the connection settings
+/// listed in `ENV_KEYS`. Any other key in a section is rejected with a
+/// message naming it (see `check_env_keys`).
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvConfig {
+    pub gateway_url: Option<String>,
+    pub extra_headers: BTreeMap<String, String>,
+    pub token_store: Option<TokenStore>,
+    pub token_header: Option<String>,
+    pub health: Option<HealthCheck>,
+}
+
+impl Config {
+    /// Load `neumodiag.toml` from the project folder, or defaults when
+    /// the file does not exist.
+    pub fn load() -> Result<Config> {
+        Config::load_from(&Config::default_path()?)
+    }
+
+    /// Where `load` looks for the file: `neumodiag.toml` in the project
+    /// folder.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(crate::api::find_project_dir()?.join(CONFIG_FILE_NAME))
+    }
+
+    /// Load exactly `path` (`--config-path`); unlike `load_from`, a
+    /// missing file is an error.
+    pub fn load_explicit(path: &Path) -> Result<Config> {
+        if !path.is_file() {
+            anyhow::bail!("No se encontró el archivo de configuración {}", path.display());
+        }
+        Config::load_from(path)
+    }
+
+    /// Load a specific file, or defaults when it does not exist.
+    pub fn load_from(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let s = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Config::from_toml_str(&s).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Parse configuration from TOML text. Image settings are checked
+    /// here so a bad list stops the CLI at startup, not at the first
+    /// upload.
+    pub fn from_toml_str(s: &str) -> Result<Config> {
+        check_env_keys(s)?;
+        let config: Config = toml::from_str(s)?;
+        config.image_policy()?;
+        Ok(config)
+    }
+
+    /// Apply the `[env.<name>]` section on top of the base settings. `None`
+    /// keeps the base; an unknown name is an error listing the sections
+    /// that exist.
+    pub fn for_env(mut self, name: Option<&str>) -> Result<Config> {
+        let name = match name {
+            Some(n) => n,
+            None => return Ok(self),
+        };
+        let section = match self.env.remove(name) {
+            Some(section) => section,
+            None => {
+                let known: Vec<&str> = self.env.keys().map(String::as_str).collect();
+                let known = if known.is_empty() { "ninguno".to_string() } else { known.join(", ") };
+                anyhow::bail!("El entorno '{}' no existe en {} (disponibles: {})", name, CONFIG_FILE_NAME, known);
+            }
+        };
+        if section.gateway_url.is_some() {
+            self.gateway_url = section.gateway_url;
+        }
+        if section.token_store.is_some() {
+            self.token_store = section.token_store;
+        }
+        if section.token_header.is_some() {
+            self.token_header = section.token_header;
+        }
+        if let Some(health) = section.health {
+            self.health = health;
+        }
+        self.extra_headers.extend(section.extra_headers);
+        self.env.clear();
+        Ok(self)
+    }
+
+    /// Whether destructive local actions ask first (default `true`).
+    pub fn confirm_destructive(&self) -> bool {
+        self.confirm_destructive.unwrap_or(true)
+    }
+
+    /// `default_role` as one of the roles the registration form offers
+    /// (any casing). `hide_role_prompt` without a `default_role` is an
+    /// error, since the form would have no role.
+    pub fn default_role(&self) -> Result<Option<Role>> {
+        match &self.default_role {
+            Some(role) => role.parse().map(Some).map_err(|e| anyhow::anyhow!("default_role: {}", e)),
+            None if self.hide_role_prompt => {
+                anyhow::bail!("hide_role_prompt = true requiere default_role en {}", CONFIG_FILE_NAME)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Accepted picture types and size from `allowed_image_exts` and
+    /// `max_upload_mb` (see `ImagePolicy::new`).
+    pub fn image_policy(&self) -> Result<ImagePolicy> {
+        let exts = match &self.allowed_image_exts {
+            Some(exts) => exts.clone(),
+            None => image_extensions().into_iter().map(str::to_string).collect(),
+        };
+        ImagePolicy::new(&exts, self.max_upload_mb.unwrap_or(DEFAULT_MAX_UPLOAD_MB))
+    }
+
+    /// Greeting style from `NEUMODIAG_GREETING_STYLE`, else from the
+    /// file; the full name by default. An unknown value in the variable
+    /// is an error.
+    pub fn greeting_style(&self) -> Result<GreetingStyle> {
+        match std::env::var("NEUMODIAG_GREETING_STYLE") {
+            Ok(v) if !v.trim().is_empty() => v.parse().map_err(|e| anyhow::anyhow!("NEUMODIAG_GREETING_STYLE: {}", e)),
+            _ => Ok(self.greeting_style.unwrap_or_default()),
+        }
+    }
+
+    /// `bell`, unless `NEUMODIAG_NO_BELL` is set to anything non-empty.
+    pub fn bell_enabled(&self) -> bool {
+        self.bell && std::env::var_os("NEUMODIAG_NO_BELL").is_none_or(|v| v.is_empty())
+    }
+
+    /// Gateway URL from `API_GATEWAY_URL`, else from the file.
+    pub fn gateway_url_with_env(&self) -> Option<String> {
+        std::env::var("API_GATEWAY_URL").ok().or_else(|| self.gateway_url.clone())
+    }
+
+    /// Gateway URLs, primary first: the comma-separated
+    /// `API_GATEWAY_URLS` when set, else the single URL of
+    /// `gateway_url_with_env`. Empty when none is configured.
+    pub fn gateway_urls_with_env(&self) -> Vec<String> {
+        let listed = std::env::var("API_GATEWAY_URLS").map(|v| parse_gateway_urls(&v)).unwrap_or_default();
+        if !listed.is_empty() {
+            return listed;
+        }
+        self.gateway_url_with_env().into_iter().collect()
+    }
+
+    /// Extra headers from the file merged with `NEUMODIAG_HEADER_<NAME>`
+    /// environment variables; the environment wins on conflicts.
+    pub fn extra_headers_with_env(&self) -> BTreeMap<String, String> {
+        let mut headers = self.extra_headers.clone();
+        headers.extend(env_headers(std::env::vars()));
+        headers
+    }
+}
+
+// Reject keys an `[env.<name>]` section can't override with a message
+// that names the key and the section; serde's "unknown field" would
+// suggest the key is wrong everywhere. Syntax errors are left to the
+// typed parse.
+fn check_env_keys(s: &str) -> Result<()> {
+    let value: toml::Value = match toml::from_str(s) {
+        Ok(value) => value,
+        Err(_) => return Ok(()),
+    };
+    let Some(envs) = value.get("env").and_then(toml::Value::as_table) else {
+        return Ok(());
+    };
+    for (name, section) in envs {
+        for key in section.as_table().into_iter().flat_map(|t| t.keys()) {
+            if !ENV_KEYS.contains(&key.as_str()) {
+                anyhow::bail!(
+                    "La clave '{}' no se admite en [env.{}]; solo se pueden cambiar por entorno: {}",
+                    key,
+                    name,
+                    ENV_KEYS.join(", ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Collect `NEUMODIAG_HEADER_<NAME>` variables as headers. Underscores in
+/// `<NAME>` become dashes, since dashes are not allowed in variable names.
+pub fn env_headers(vars: impl Iterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    vars.filter_map(|(k, v)| {
+        let name = k.strip_prefix(HEADER_ENV_PREFIX)?;
+        if name.is_empty() {
+            return None;
+        }
+        Some((name.replace('_', "-"), v))
+    })
+    .collect()
+}
+
+/// Split a comma-separated gateway list, dropping blanks.
+pub fn parse_gateway_urls(list: &str) -> Vec<String> {
+    list.split(',').map(str::trim).filter(|u| !u.is_empty()).map(str::to_string).collect()
+}
+
+/// Whether a header likely carries a secret and must be masked in logs.
+pub fn is_sensitive_header(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    ["auth", "key", "token", "secret", "password", "cookie"]
+        .iter()
+        .any(|s| lower.contains(s))
+}
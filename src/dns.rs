@@ -0,0 +1,72 @@
+// DNS pre-flight
+// --------------
+// When the gateway host name doesn't resolve, reqwest only reports a
+// generic connection error. Before the first request of a run the client
+// resolves the host itself (see `ApiClient::preflight_dns`) so the user
+// sees "No se pudo resolver el host <host>" with the exact name. IP
+// literals are not looked up, and a successful lookup is not repeated.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// ResolveError
+///
+/// Why a host could not be resolved. `NotFound` means the name does not
+/// exist (NXDOMAIN, usually a typo in the URL); `Other` covers failures
+/// such as an unreachable DNS server, which may be temporary.
+#[derive(Debug, PartialEq)]
+pub enum ResolveError {
+    NotFound { host: String },
+    Other { host: String, detail: String },
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::NotFound { host } => {
+                write!(f, "No se pudo resolver el host {} (el nombre no existe; revise la URL del gateway)", host)
+            }
+            ResolveError::Other { host, detail } => {
+                write!(f, "No se pudo resolver el host {} ({}); revise la conexión o el servidor DNS", host, detail)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Resolve `host:port` with the system resolver.
+pub fn resolve_host(host: &str, port: u16) -> Result<(), ResolveError> {
+    resolve_with(host, port, |h, p| (h, p).to_socket_addrs().map(|addrs| addrs.collect()))
+}
+
+/// Resolve through `lookup`, classifying failures. Split out so the
+/// classification can be tested without a real DNS server.
+pub fn resolve_with<F>(host: &str, port: u16, lookup: F) -> Result<(), ResolveError>
+where
+    F: FnOnce(&str, u16) -> io::Result<Vec<SocketAddr>>,
+{
+    match lookup(host, port) {
+        Ok(addrs) if !addrs.is_empty() => Ok(()),
+        Ok(_) => Err(ResolveError::NotFound { host: host.to_string() }),
+        Err(e) => Err(classify(host, &e)),
+    }
+}
+
+// The standard library surfaces getaddrinfo failures as plain text, so
+// "name does not exist" is recognised by the platform messages for
+// EAI_NONAME / WSAHOST_NOT_FOUND.
+fn classify(host: &str, err: &io::Error) -> ResolveError {
+    let text = err.to_string().to_lowercase();
+    let not_found = [
+        "name or service not known",
+        "nodename nor servname provided",
+        "no such host is known",
+        "no address associated with hostname",
+    ];
+    if not_found.iter().any(|m| text.contains(m)) {
+        ResolveError::NotFound { host: host.to_string() }
+    } else {
+        ResolveError::Other { host: host.to_string(), detail: err.to_string() }
+    }
+}
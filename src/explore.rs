@@ -0,0 +1,82 @@
+// Endpoint explorer
+// -----------------
+// Developer tool behind the hidden `--explore` flag: a loop that asks
+// for a method, a path and an optional JSON body, sends them with
+// `ApiClient::request` (extra headers and the saved session's token
+// included) and prints the status and the pretty-printed response. Not
+// part of the normal menu.
+
+use crate::api::{ApiClient, RawResponse};
+use anyhow::{Context, Result};
+use dialoguer::{Input, Select};
+use reqwest::Method;
+
+/// Methods offered by the explorer.
+pub const EXPLORE_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+/// ExploreRequest
+///
+/// One request built in the explorer.
+#[derive(Debug, Clone)]
+pub struct ExploreRequest {
+    pub method: Method,
+    pub path: String,
+    pub body: Option<serde_json::Value>,
+}
+
+/// Parse the body typed by the user: empty means no body, anything else
+/// must be valid JSON.
+pub fn parse_body(input: &str) -> Result<Option<serde_json::Value>> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(input).map(Some).context("el cuerpo no es JSON válido")
+}
+
+/// Send `req` and return the response as printed by the explorer.
+/// A query string typed after the path (`/pacientes?correo=a@b.com`) is
+/// split off and re-encoded by `ApiClient::build_url`.
+pub fn send(api: &ApiClient, req: &ExploreRequest) -> Result<String> {
+    let (path, query) = req.path.split_once('?').unwrap_or((&req.path, ""));
+    let pairs: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let pairs: Vec<(&str, &str)> = pairs.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let raw = api.request_with_query(req.method.clone(), path, &pairs, req.body.as_ref())?;
+    Ok(render(&raw))
+}
+
+/// Status line, then the body: pretty-printed when it is JSON, as text
+/// otherwise.
+pub fn render(raw: &RawResponse) -> String {
+    let body = match raw.json::<serde_json::Value>() {
+        Ok(v) => serde_json::to_string_pretty(&v).unwrap_or_else(|_| raw.text()),
+        Err(_) => raw.text(),
+    };
+    format!("{}\n{}", raw.status, body)
+}
+
+/// Interactive loop; returns when the user picks "Salir".
+pub fn explore_menu(api: &ApiClient) -> Result<()> {
+    println!("Explorador de endpoints ({}). Solo para desarrollo.", api.base_url());
+    let mut choices: Vec<&str> = EXPLORE_METHODS.to_vec();
+    choices.push("Salir");
+    loop {
+        let pick = choices[Select::new().with_prompt("Método").items(&choices).default(0).interact()?];
+        if pick == "Salir" {
+            return Ok(());
+        }
+        let method = Method::from_bytes(pick.as_bytes())?;
+        let path: String = Input::new().with_prompt("Ruta (ej. /profile)").interact_text()?;
+        let body_text: String = Input::new()
+            .with_prompt("Cuerpo JSON (vacío para ninguno)")
+            .allow_empty(true)
+            .validate_with(|s: &String| parse_body(s).map(|_| ()).map_err(|e| e.to_string()))
+            .interact_text()?;
+        let req = ExploreRequest { method, path, body: parse_body(&body_text)? };
+        match send(api, &req) {
+            Ok(out) => println!("{}", out),
+            Err(e) => println!("Error: {:#}", e),
+        }
+        println!();
+    }
+}
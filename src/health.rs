@@ -0,0 +1,197 @@
+// Connection health
+// -----------------
+// The menu header shows whether the gateway is reachable ("[conectado]" /
+// "[sin conexión]"). A background thread probes `GET /health` every few
+// seconds and stores the result; the menu only reads the latest status,
+// so a slow or unreachable gateway never delays it.
+//
+// A gateway without a health endpoint (404) still counts as reachable.
+// One failed probe after a good one reports "[comprobando]" rather than
+// "[sin conexión]", so a single dropped request doesn't flip the header.
+//
+// `NEUMODIAG_HEALTH_INTERVAL_SECS` sets the interval (default 30 s); 0
+// turns the indicator off.
+//
+// Deployments whose health endpoint is different configure it in
+// `neumodiag.toml`:
+//
+// ```toml
+// [health]
+// method = "POST"
+// path = "/api/ping"
+// expected_status = [200, 503]
+// body = { probe = true }
+// ```
+
+use crate::api::{ApiClient, ApiError};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Default seconds between health probes.
+pub const DEFAULT_HEALTH_INTERVAL_SECS: u64 = 30;
+
+/// HealthCheck
+///
+/// How `ApiClient::health_check` asks the gateway whether it is up.
+/// Defaults to `GET /health` accepting any 2xx.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthCheck {
+    pub method: HealthMethod,
+    pub path: String,
+    /// Statuses that count as healthy; empty means any 2xx.
+    pub expected_status: Vec<u16>,
+    /// JSON body sent with the request (usually with `POST`).
+    pub body: Option<serde_json::Value>,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        HealthCheck { method: HealthMethod::Get, path: "/health".into(), expected_status: Vec::new(), body: None }
+    }
+}
+
+impl HealthCheck {
+    /// Reject settings that can never work: a path not starting with `/`
+    /// or a status outside 100-599.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.path.starts_with('/') {
+            anyhow::bail!("La ruta del health check debe empezar con '/': '{}'", self.path);
+        }
+        if let Some(bad) = self.expected_status.iter().find(|s| !(100..=599).contains(*s)) {
+            anyhow::bail!("Código de estado inválido en health.expected_status: {}", bad);
+        }
+        Ok(())
+    }
+
+    /// Whether a reply with `status` means the gateway is healthy.
+    pub fn accepts(&self, status: StatusCode) -> bool {
+        if self.expected_status.is_empty() {
+            status.is_success()
+        } else {
+            self.expected_status.contains(&status.as_u16())
+        }
+    }
+}
+
+/// HTTP methods a health check may use.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HealthMethod {
+    #[default]
+    #[serde(alias = "get")]
+    Get,
+    #[serde(alias = "post")]
+    Post,
+}
+
+impl HealthMethod {
+    pub fn as_method(&self) -> Method {
+        match self {
+            HealthMethod::Get => Method::GET,
+            HealthMethod::Post => Method::POST,
+        }
+    }
+}
+
+/// HealthStatus
+///
+/// What the header says about the gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HealthStatus {
+    /// No probe finished yet, or the last one failed after a good one.
+    #[default]
+    Unknown,
+    Connected,
+    Disconnected,
+}
+
+impl HealthStatus {
+    /// The status after a probe that succeeded (`ok`) or failed.
+    pub fn next(self, ok: bool) -> HealthStatus {
+        match (self, ok) {
+            (_, true) => HealthStatus::Connected,
+            (HealthStatus::Connected, false) => HealthStatus::Unknown,
+            (_, false) => HealthStatus::Disconnected,
+        }
+    }
+
+    /// Label shown in the menu header.
+    pub fn label(&self) -> &'static str {
+        match self {
+            HealthStatus::Unknown => "[comprobando]",
+            HealthStatus::Connected => "[conectado]",
+            HealthStatus::Disconnected => "[sin conexión]",
+        }
+    }
+}
+
+/// HealthMonitor
+///
+/// Background prober. Dropping it stops the thread after its current
+/// probe.
+pub struct HealthMonitor {
+    status: Arc<Mutex<HealthStatus>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl HealthMonitor {
+    /// Probe `api` now and then every `every`. Probes are quiet: no
+    /// verbose output, no retries and no debug dump. They run on a
+    /// detached copy of `api`, so they don't touch the correlation id
+    /// "Copiar último error" reports.
+    pub fn start(api: &ApiClient, every: Duration) -> HealthMonitor {
+        let status = Arc::new(Mutex::new(HealthStatus::Unknown));
+        let stop = Arc::new(AtomicBool::new(false));
+        let api = api.detached().map(|api| api.with_verbose(false).with_retries(0, 0));
+        let (shared, stopped) = (Arc::clone(&status), Arc::clone(&stop));
+        std::thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                let ok = api.as_ref().is_ok_and(probe);
+                if let Ok(mut s) = shared.lock() {
+                    *s = s.next(ok);
+                }
+                // Sleep in short slices so a stop request is seen promptly.
+                let mut waited = Duration::ZERO;
+                while waited < every && !stopped.load(Ordering::SeqCst) {
+                    let slice = Duration::from_millis(100).min(every - waited);
+                    std::thread::sleep(slice);
+                    waited += slice;
+                }
+            }
+        });
+        HealthMonitor { status, stop }
+    }
+
+    /// Latest status; never waits for a probe.
+    pub fn status(&self) -> HealthStatus {
+        self.status.lock().map(|s| *s).unwrap_or_default()
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+// Whether the gateway answered the health probe as reachable.
+fn probe(api: &ApiClient) -> bool {
+    match api.health_check() {
+        Ok(()) => true,
+        Err(e) => e.downcast_ref::<ApiError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND),
+    }
+}
+
+/// Probe interval from `NEUMODIAG_HEALTH_INTERVAL_SECS`, or `None` when
+/// set to 0 (indicator off).
+pub fn health_interval_from_env() -> Option<Duration> {
+    let secs = std::env::var("NEUMODIAG_HEALTH_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_HEALTH_INTERVAL_SECS);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
@@ -0,0 +1,231 @@
+// Image formats
+// -------------
+// Extensions accepted for profile pictures and the MIME type sent with
+// each in the multipart upload. The file dialog filter, the manual path
+// check and the upload all use this list, narrowed per deployment by
+// `allowed_image_exts` / `max_upload_mb` in the config (`ImagePolicy`).
+//
+// HEIC/HEIF photos (iPhone default) are accepted, but some backends
+// can't decode them. Builds with the `heic-transcode` feature convert
+// them to JPEG locally before uploading, using the `heif-convert` tool
+// from libheif (macOS falls back to the built-in `sips`).
+
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::Path;
+
+/// Largest picture accepted when `max_upload_mb` is not configured, in MB.
+pub const DEFAULT_MAX_UPLOAD_MB: u64 = 10;
+/// Largest image read from stdin (`upload --file -`) under the default
+/// policy, in bytes.
+pub const MAX_STDIN_IMAGE_BYTES: u64 = DEFAULT_MAX_UPLOAD_MB * 1024 * 1024;
+
+/// Accepted image extensions (lowercase) and their MIME types.
+pub const IMAGE_TYPES: &[(&str, &str)] = &[
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("webp", "image/webp"),
+    ("heic", "image/heic"),
+    ("heif", "image/heic"),
+];
+
+/// Accepted extensions, for the file dialog filter.
+pub fn image_extensions() -> Vec<&'static str> {
+    IMAGE_TYPES.iter().map(|(ext, _)| *ext).collect()
+}
+
+/// Accepted extensions in lower and upper case. The macOS file dialog
+/// matches filters case-sensitively and would otherwise hide
+/// `FOTO.JPG`.
+pub fn dialog_extensions() -> Vec<String> {
+    IMAGE_TYPES.iter().flat_map(|(ext, _)| [ext.to_string(), ext.to_ascii_uppercase()]).collect()
+}
+
+/// MIME type for `path` based on its extension (case-insensitive), or
+/// `None` when the extension is not an accepted image type.
+pub fn mime_for_path(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    IMAGE_TYPES.iter().find(|(e, _)| *e == ext).map(|(_, mime)| *mime)
+}
+
+/// ImagePolicy
+///
+/// The image types and size a deployment accepts for profile pictures
+/// (`allowed_image_exts` and `max_upload_mb` in the config). The default
+/// accepts every type in `IMAGE_TYPES` up to `DEFAULT_MAX_UPLOAD_MB`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePolicy {
+    exts: Vec<&'static str>,
+    max_bytes: u64,
+}
+
+impl Default for ImagePolicy {
+    fn default() -> Self {
+        ImagePolicy { exts: image_extensions(), max_bytes: MAX_STDIN_IMAGE_BYTES }
+    }
+}
+
+impl ImagePolicy {
+    /// Policy accepting `exts` (case-insensitive, a leading dot is
+    /// ignored) up to `max_mb` MB. Every extension must be one of
+    /// `IMAGE_TYPES`, since the upload needs its MIME type, and `max_mb`
+    /// must be at least 1.
+    pub fn new(exts: &[String], max_mb: u64) -> Result<ImagePolicy> {
+        if exts.is_empty() {
+            anyhow::bail!("allowed_image_exts no puede estar vacío");
+        }
+        let mut allowed = Vec::new();
+        for ext in exts {
+            let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+            match IMAGE_TYPES.iter().find(|(e, _)| *e == ext) {
+                Some((e, _)) if !allowed.contains(e) => allowed.push(*e),
+                Some(_) => {}
+                None => anyhow::bail!(
+                    "allowed_image_exts: '{}' no es un formato admitido (se admiten {})",
+                    ext,
+                    image_extensions().join(", ")
+                ),
+            }
+        }
+        if max_mb == 0 {
+            anyhow::bail!("max_upload_mb debe ser al menos 1");
+        }
+        Ok(ImagePolicy { exts: allowed, max_bytes: max_mb.saturating_mul(1024 * 1024) })
+    }
+
+    /// Accepted extensions (lowercase), in configured order.
+    pub fn extensions(&self) -> &[&'static str] {
+        &self.exts
+    }
+
+    /// Accepted extensions in lower and upper case, for the file dialog
+    /// filter (see `dialog_extensions`).
+    pub fn dialog_extensions(&self) -> Vec<String> {
+        self.exts.iter().flat_map(|ext| [ext.to_string(), ext.to_ascii_uppercase()]).collect()
+    }
+
+    /// Largest accepted picture, in bytes.
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Check a picked file before uploading it and return its MIME type.
+    /// Its extension, when it has one, must be accepted, its content (see
+    /// `detect_file_mime`) must be an accepted format whatever the
+    /// extension says, and it must fit in `max_bytes`.
+    pub fn check_file(&self, path: &Path) -> Result<&'static str> {
+        let meta = std::fs::metadata(path).with_context(|| format!("No se pudo leer el archivo {}", path.display()))?;
+        let mime = self.check_format(path, detect_file_mime(path))?;
+        self.check_size(meta.len())?;
+        Ok(mime)
+    }
+
+    /// Check an image already in memory that will be uploaded as `name`
+    /// (see `check_file`) and return its MIME type.
+    pub fn check_bytes(&self, name: &str, bytes: &[u8]) -> Result<&'static str> {
+        let name = Path::new(name);
+        let mime = self.check_format(name, sniff_mime(bytes))?;
+        self.check_size(bytes.len() as u64)?;
+        Ok(mime)
+    }
+
+    fn check_format(&self, path: &Path, mime: Option<&'static str>) -> Result<&'static str> {
+        let ext_allowed = match path.extension() {
+            Some(ext) => ext.to_str().is_some_and(|e| self.exts.contains(&e.to_ascii_lowercase().as_str())),
+            None => true,
+        };
+        let format_allowed = |mime: &str| self.exts.iter().any(|e| IMAGE_TYPES.contains(&(*e, mime)));
+        match mime {
+            Some(mime) if ext_allowed && format_allowed(mime) => Ok(mime),
+            _ => anyhow::bail!("Formato no soportado. Use una imagen {}.", self.exts.join(", ")),
+        }
+    }
+
+    fn check_size(&self, len: u64) -> Result<()> {
+        if len > self.max_bytes {
+            anyhow::bail!("La imagen supera el tamaño máximo de {} MB", self.max_bytes / (1024 * 1024));
+        }
+        Ok(())
+    }
+}
+
+/// Leading bytes `sniff_mime` needs to recognise every accepted format.
+pub const SNIFF_LEN: usize = 16;
+
+/// MIME type of an accepted image format recognised from its first
+/// bytes (magic numbers), or `None`.
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..]
+            if [b"heic", b"heix", b"heif", b"mif1"].iter().any(|b| brand.starts_with(*b)) =>
+        {
+            Some("image/heic")
+        }
+        _ => None,
+    }
+}
+
+/// MIME type of the image at `path`, sniffed from its first bytes. The
+/// extension is never trusted: `None` when the content is not an accepted
+/// format or the file can't be read.
+pub fn detect_file_mime(path: &Path) -> Option<&'static str> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    std::fs::File::open(path)
+        .and_then(|f| f.take(SNIFF_LEN as u64).read_to_end(&mut head))
+        .ok()
+        .and_then(|_| sniff_mime(&head))
+}
+
+/// File extension for `mime`, for naming an image that has no file.
+pub fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    IMAGE_TYPES.iter().find(|(_, m)| *m == mime).map(|(ext, _)| *ext)
+}
+
+/// Read an image from `reader`, failing once it exceeds `max` bytes
+/// instead of buffering an unbounded stream.
+pub fn read_image_capped<R: Read>(reader: R, max: u64) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(max + 1).read_to_end(&mut bytes).context("reading image data")?;
+    if bytes.len() as u64 > max {
+        anyhow::bail!("La imagen supera el tamaño máximo de {} MB", max / (1024 * 1024));
+    }
+    if bytes.is_empty() {
+        anyhow::bail!("No se recibió ninguna imagen");
+    }
+    Ok(bytes)
+}
+
+/// Whether `path` is a HEIC/HEIF photo.
+pub fn is_heic(path: &Path) -> bool {
+    mime_for_path(path) == Some("image/heic")
+}
+
+/// Convert a HEIC/HEIF photo to a JPEG in the temp folder. The JPEG gets
+/// a random name and is deleted when the returned file is dropped, so
+/// keep it until the upload has finished.
+#[cfg(feature = "heic-transcode")]
+pub fn transcode_heic_to_jpeg(path: &Path) -> anyhow::Result<tempfile::NamedTempFile> {
+    use anyhow::Context;
+    use std::process::Command;
+
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("foto");
+    let out = tempfile::Builder::new()
+        .prefix(&format!("{}-", stem))
+        .suffix(".jpg")
+        .tempfile()
+        .context("no se pudo crear el archivo temporal para la conversión")?;
+    let status = if cfg!(target_os = "macos") {
+        Command::new("sips").args(["-s", "format", "jpeg"]).arg(path).arg("--out").arg(out.path()).status()
+    } else {
+        Command::new("heif-convert").arg(path).arg(out.path()).status()
+    }
+    .context("no se pudo ejecutar el conversor HEIC (instale libheif / heif-convert)")?;
+    if !status.success() {
+        anyhow::bail!("La conversión de HEIC a JPEG falló ({})", status);
+    }
+    Ok(out)
+}
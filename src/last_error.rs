@@ -0,0 +1,81 @@
+// Last error
+// ----------
+// Error messages scroll off the screen quickly, so the UI records the
+// last failure it showed. "Copiar último error" in the menu prints it in
+// a block that is easy to copy into a support request, together with the
+// gateway's correlation id for the failing request when it sent one.
+// When the failure was an `ApiError` its status code and response body
+// are kept as fields, so the report has more than the formatted text.
+//
+// The store lives in memory only; it is empty in every new run.
+
+use crate::api::ApiError;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// LastError
+///
+/// A failure as shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastError {
+    pub message: String,
+    /// HTTP status of the failing response, for gateway errors.
+    pub status: Option<u16>,
+    /// Body of that response, for gateway errors.
+    pub body: Option<String>,
+    pub correlation_id: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+}
+
+impl LastError {
+    /// Text meant to be pasted into a support request.
+    pub fn to_report(&self) -> String {
+        let mut out = format!("Error: {}\n", self.message);
+        if let Some(status) = self.status {
+            out.push_str(&format!("Estado HTTP: {}\n", status));
+        }
+        if let Some(body) = self.body.as_deref().filter(|b| !b.trim().is_empty()) {
+            out.push_str(&format!("Respuesta del servidor: {}\n", body.trim()));
+        }
+        if let Some(id) = &self.correlation_id {
+            out.push_str(&format!("ID de correlación: {}\n", id));
+        }
+        out.push_str(&format!("Fecha (unix): {}\n", self.at));
+        out
+    }
+}
+
+/// ErrorStore
+///
+/// Holds the most recent error; each `record` replaces the previous one.
+#[derive(Debug, Default)]
+pub struct ErrorStore {
+    last: Mutex<Option<LastError>>,
+}
+
+impl ErrorStore {
+    pub const fn new() -> Self {
+        ErrorStore { last: Mutex::new(None) }
+    }
+
+    /// Remember `message` as the latest error. When `error` is an
+    /// `ApiError` its status and body are kept too.
+    pub fn record(&self, message: impl Into<String>, error: Option<&anyhow::Error>, correlation_id: Option<String>) {
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let api_error = error.and_then(|e| e.downcast_ref::<ApiError>());
+        let status = api_error.map(|e| e.status.as_u16());
+        let body = api_error.map(|e| e.body.clone());
+        if let Ok(mut last) = self.last.lock() {
+            *last = Some(LastError { message: message.into(), status, body, correlation_id, at });
+        }
+    }
+
+    /// The most recent error, if any.
+    pub fn last(&self) -> Option<LastError> {
+        self.last.lock().ok().and_then(|last| last.clone())
+    }
+}
+
+/// Process-wide store used by the interactive UI.
+pub static LAST_ERROR: ErrorStore = ErrorStore::new();
@@ -6,10 +6,74 @@
 // Module responsibilities:
 // - `api`: Encapsulates HTTP interactions with the backend (register,
 //   auth, upload) and token persistence helpers.
+// - `audit`: Local JSONL log of data-protection actions.
+// - `backend`: The gateway calls behind a trait, and an in-memory fake.
+// - `bench`: Login latency benchmark (`bench login`).
+// - `cache`: In-memory TTL cache for idempotent GETs.
+// - `cli`: Command-line flags and subcommands parsed by the binary.
+// - `crash`: Friendly panic handler that writes a redacted crash report.
+// - `config`: Optional `neumodiag.toml` settings (extra headers, ...).
+// - `commands`: Non-interactive subcommand handlers.
+// - `dns`: Pre-flight resolution of the gateway host with clear errors.
+// - `explore`: Hidden `--explore` endpoint explorer for developers.
+// - `health`: Background gateway health probe for the menu header.
+// - `image`: Accepted image extensions and their MIME types.
+// - `last_error`: In-memory record of the last error shown, for support.
+// - `paths`: Where the CLI's files are kept (`paths` subcommand).
+// - `profile`: Canonical `Profile` model built from JWT claims and the
+//   `/profile` endpoint.
+// - `prompt`: Where the menu's answers come from (terminal or script)
+//   and where its messages go.
+// - `proxy`: Which proxy a request goes through, for clearer errors.
+// - `query`: Typed paging/search/filter parameters for list endpoints.
+// - `reset`: Inventory and removal of every file the CLI persists.
+// - `retry`: Retry policy for transient failures and the per-process
+//   retry budget shared by all calls.
+// - `signing`: Optional HMAC signing of every request.
+// - `session`: Passphrase-encrypted export/import of a saved session.
+// - `state`: Versioned session state file (token + meta) and its store.
+// - `shutdown`: Clean exit on SIGTERM without torn token files.
+// - `spinner`: Progress spinner (plain text when stderr is not a TTY)
+//   and the background-thread runner used for blocking calls.
+// - `stats`: Per-session counters and the summary printed on exit.
+// - `telemetry`: Opt-in, local-only counts of menu actions.
+// - `terminal`: Cursor/clearing/banner output, skipped when not a TTY.
 // - `ui`: Implements the terminal-based user interface flows and
 //   delegates requests to `api`.
+// - `validate`: Offline check of registration rows from a CSV file.
+// - `yes_no`: Language-aware parsing of typed yes/no answers.
 //
 // Keeping this separation makes it easier to test the API logic or
 // replace the UI in the future (for example, adding a TUI or GUI).
 pub mod api;
+pub mod audit;
+pub mod backend;
+pub mod bench;
+pub mod cache;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod crash;
+pub mod dns;
+pub mod explore;
+pub mod health;
+pub mod image;
+pub mod last_error;
+pub mod paths;
+pub mod profile;
+pub mod prompt;
+pub mod proxy;
+pub mod query;
+pub mod reset;
+pub mod retry;
+pub mod session;
+pub mod shutdown;
+pub mod signing;
+pub mod state;
+pub mod spinner;
+pub mod stats;
+pub mod telemetry;
+pub mod terminal;
 pub mod ui;
+pub mod validate;
+pub mod yes_no;
@@ -5,18 +5,70 @@
 // us use the `?` operator for concise error propagation in this small
 // prototype.
 
-use neumodiag_cli::{ui::main_menu, api::ApiClient};
+use clap::Parser;
+use neumodiag_cli::{api::unclean_grace_from_env, ui::{main_menu, UiOptions}, cli::Cli, commands, crash, explore, shutdown, spinner};
 
 fn main() -> anyhow::Result<()> {
-    // Build an ApiClient. It reads `API_GATEWAY_URL` from the
-    // environment (if present) or falls back to http://localhost:8081.
-    // This lets you point the CLI at a different backend without
-    // recompiling.
-    let api = ApiClient::from_env()?;
+    let cli = Cli::parse();
+
+    // Optional settings from `neumodiag.toml` (or `--config-path`), with
+    // the `--env` section applied; flags and environment variables take
+    // precedence over the file.
+    let config = cli.load_config()?;
+
+    // Build an ApiClient from the flags and the config (gateway URL,
+    // redirects, timeouts, retries, token store, headers, ...).
+    let api = cli.build_api_client(&config)?;
+
+    // From here on a panic prints a short message and writes a crash
+    // report instead of a raw backtrace.
+    crash::install_panic_hook(api.base_url().to_string());
+
+    // SIGTERM exits through a watcher thread that never interrupts a
+    // token write. Only the menu marks the exit as clean, as "Salir" does.
+    shutdown::install_handler()?;
+    shutdown::spawn_watcher(api.clone(), cli.command.is_none());
+
+    // `--fast-results` keeps the spinner but drops its minimum display
+    // time.
+    if cli.fast_results {
+        spinner::set_min_display_ms(0);
+    }
+
+    let opts = UiOptions {
+        assume_yes: cli.yes,
+        summary: cli.summary,
+        telemetry: config.telemetry,
+        auth_debug: cli.auth_debug,
+        config_path: cli.config_path.clone(),
+        force: cli.force || !config.confirm_destructive(),
+        default_role: config.default_role()?,
+        hide_role_prompt: config.hide_role_prompt,
+        images: config.image_policy()?,
+        unclean_grace: unclean_grace_from_env(),
+        greeting_style: config.greeting_style()?,
+        bell: config.bell_enabled(),
+        ..UiOptions::default()
+    };
+
+    // `--explore` (developers only) replaces the menu with the endpoint
+    // explorer, authenticated with the saved session when there is one.
+    if cli.explore {
+        let mut api = api;
+        if let Some(token) = api.load_token_from_project()? {
+            api.set_token(token.trim());
+        }
+        return explore::explore_menu(&api);
+    }
+
+    // Subcommands run a single operation and skip the menu.
+    if let Some(command) = cli.command {
+        return commands::run(api, command, &opts);
+    }
 
     // Run the main interactive menu. This function blocks until the
     // user chooses to exit; it owns the UI loop and delegates network
     // actions to `ApiClient`.
-    main_menu(api)?;
+    main_menu(api, opts)?;
     Ok(())
 }
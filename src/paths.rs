@@ -0,0 +1,85 @@
+// Storage locations
+// -----------------
+// Where the CLI keeps its files, for the `paths` subcommand. Every path
+// comes from the resolver its writer uses (`ApiClient::state_dir`,
+// `Config::default_path`, the crash handler's temp folder), so what is
+// printed is where the files really go, `NEUMODIAG_CONFIG_DIR` and
+// `--config-path` included.
+
+use crate::api::{ApiClient, TOKEN_FILE_NAME, TOKEN_META_FILE_NAME};
+use crate::audit::AUDIT_FILE_NAME;
+use crate::config::Config;
+use crate::state::STATE_FILE_NAME;
+use crate::telemetry::USAGE_FILE_NAME;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// StoragePaths
+///
+/// The files and folders the CLI reads or writes. Files are listed even
+/// when they don't exist yet.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoragePaths {
+    pub project_dir: PathBuf,
+    /// `file`, or `none` when the session is kept in memory only.
+    pub token_store: &'static str,
+    pub session_file: PathBuf,
+    pub legacy_token_file: PathBuf,
+    pub legacy_meta_file: PathBuf,
+    pub config_file: PathBuf,
+    pub audit_log: PathBuf,
+    pub usage_file: PathBuf,
+    pub crash_report_dir: PathBuf,
+    /// Always `None`: cached responses live in memory only.
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl StoragePaths {
+    /// Resolve the paths for `api`. `config_path` is the `--config-path`
+    /// value, if any; otherwise the default config location is used.
+    pub fn resolve(api: &ApiClient, config_path: Option<&Path>) -> Result<StoragePaths> {
+        let dir = api.state_dir()?;
+        let config_file = match config_path {
+            Some(path) => path.to_path_buf(),
+            None => Config::default_path()?,
+        };
+        Ok(StoragePaths {
+            token_store: if api.is_ephemeral() { "none" } else { "file" },
+            session_file: dir.join(STATE_FILE_NAME),
+            legacy_token_file: dir.join(TOKEN_FILE_NAME),
+            legacy_meta_file: dir.join(TOKEN_META_FILE_NAME),
+            config_file,
+            audit_log: dir.join(AUDIT_FILE_NAME),
+            usage_file: dir.join(USAGE_FILE_NAME),
+            crash_report_dir: std::env::temp_dir(),
+            cache_dir: None,
+            project_dir: dir,
+        })
+    }
+
+    /// One "label: path" line per entry, for the terminal.
+    pub fn render_text(&self) -> String {
+        let session = if self.token_store == "none" {
+            format!("{} (no se usa: sesión solo en memoria)", self.session_file.display())
+        } else {
+            self.session_file.display().to_string()
+        };
+        let rows = [
+            ("Carpeta de datos", self.project_dir.display().to_string()),
+            ("Sesión guardada", session),
+            ("Token (formato anterior)", self.legacy_token_file.display().to_string()),
+            ("Metadatos (formato anterior)", self.legacy_meta_file.display().to_string()),
+            ("Configuración", self.config_file.display().to_string()),
+            ("Registro de auditoría", self.audit_log.display().to_string()),
+            ("Conteo de uso", self.usage_file.display().to_string()),
+            ("Informes de error", self.crash_report_dir.display().to_string()),
+            ("Caché", "solo en memoria".to_string()),
+        ];
+        let width = rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0) + 1;
+        rows.iter()
+            .map(|(label, value)| format!("{:width$} {}", format!("{}:", label), value, width = width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,259 @@
+// Profile model
+// -------------
+// One canonical shape for "who is logged in", filled from either the JWT
+// claims (available offline, possibly stale) or the backend's `/profile`
+// endpoint (authoritative). Display code (welcome banner, whoami, profile
+// view) works on `Profile` only, so it never pokes at raw JSON.
+
+use crate::api::Role;
+use base64::engine::general_purpose::STANDARD as base64_standard;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+
+/// Profile
+///
+/// Every field is optional because neither source is guaranteed to carry
+/// all of them. `nombre` is accepted as an alias of `nombre_completo`
+/// since the auth response uses the short name. `rol` is the user's
+/// role, `Role::Unknown` for names this client doesn't know; from a list
+/// (users with several roles) it is the first one, and `token_roles`
+/// gives them all.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    #[serde(default, alias = "nombre")]
+    pub nombre_completo: Option<String>,
+    #[serde(default)]
+    pub correo: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_role")]
+    pub rol: Option<Role>,
+    #[serde(default)]
+    pub identificacion: Option<String>,
+    #[serde(default)]
+    pub edad: Option<i32>,
+    #[serde(default)]
+    pub user_id: Option<serde_json::Value>,
+}
+
+impl Profile {
+    /// Build a profile from the (unverified) claims of a JWT. Returns
+    /// `None` when the token is not a decodable JWT.
+    pub fn from_jwt(token: &str) -> Option<Profile> {
+        let claims = decode_jwt_claims(token)?;
+        serde_json::from_value(claims).ok()
+    }
+
+    /// Combine server data with token data, preferring the server for
+    /// every field it provides and falling back to the token otherwise.
+    pub fn merge(server: Profile, token: Profile) -> Profile {
+        Profile {
+            nombre_completo: server.nombre_completo.or(token.nombre_completo),
+            correo: server.correo.or(token.correo),
+            rol: server.rol.or(token.rol),
+            identificacion: server.identificacion.or(token.identificacion),
+            edad: server.edad.or(token.edad),
+            user_id: server.user_id.or(token.user_id),
+        }
+    }
+}
+
+/// Message for a profile edit that changed nothing.
+pub const NO_PROFILE_CHANGES_MSG: &str = "No se realizaron cambios.";
+
+/// ProfilePatch
+///
+/// Body of a profile edit (`PATCH /profile`): only the fields the user
+/// changed are `Some`, and only those are serialized. An edit that
+/// changed nothing is empty and is not sent (see
+/// `ApiClient::update_profile`).
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ProfilePatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nombre_completo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identificacion: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edad: Option<i32>,
+}
+
+impl ProfilePatch {
+    /// The fields of `edited` that are set and differ from `current`.
+    pub fn diff(current: &Profile, edited: &Profile) -> ProfilePatch {
+        fn changed<T: PartialEq + Clone>(current: &Option<T>, edited: &Option<T>) -> Option<T> {
+            edited.as_ref().filter(|v| current.as_ref() != Some(*v)).cloned()
+        }
+        ProfilePatch {
+            nombre_completo: changed(&current.nombre_completo, &edited.nombre_completo),
+            correo: changed(&current.correo, &edited.correo),
+            identificacion: changed(&current.identificacion, &edited.identificacion),
+            edad: changed(&current.edad, &edited.edad),
+        }
+    }
+
+    /// Whether no field changed.
+    pub fn is_empty(&self) -> bool {
+        *self == ProfilePatch::default()
+    }
+}
+
+/// Name shown when a token carries nothing better to greet with.
+pub const FALLBACK_GREETING_NAME: &str = "usuario";
+
+// Claims tried, in order, for the name in greetings.
+const GREETING_NAME_CLAIMS: &[&str] = &["nombre_completo", "nombre", "sub", "email", "correo"];
+
+/// Name to greet the holder of `claims` with: `nombre_completo`, then
+/// `nombre`, then `sub`, then the e-mail, and "usuario" when none of
+/// them is a non-blank string.
+pub fn greeting_name(claims: &serde_json::Value) -> String {
+    GREETING_NAME_CLAIMS
+        .iter()
+        .find_map(|key| claims.get(*key)?.as_str().map(str::trim).filter(|v| !v.is_empty()))
+        .unwrap_or(FALLBACK_GREETING_NAME)
+        .to_string()
+}
+
+/// `greeting_name` for a JWT; "usuario" when it can't be decoded.
+pub fn greeting_name_from_jwt(token: &str) -> String {
+    greeting_from_jwt(token, GreetingStyle::Full)
+}
+
+/// GreetingStyle
+///
+/// How greetings address the user (`greeting_style` in the config, or
+/// `NEUMODIAG_GREETING_STYLE`): the whole name, its first word, or
+/// "Dr./Dra. <apellido>" for doctors. See `format_greeting`.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GreetingStyle {
+    #[default]
+    Full,
+    First,
+    Formal,
+}
+
+impl std::str::FromStr for GreetingStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GreetingStyle, String> {
+        match s.trim().to_lowercase().as_str() {
+            "full" => Ok(GreetingStyle::Full),
+            "first" => Ok(GreetingStyle::First),
+            "formal" => Ok(GreetingStyle::Formal),
+            _ => Err(format!("use full, first o formal (se indicó '{}')", s)),
+        }
+    }
+}
+
+/// The greeting form of `name` in `style`. `First` keeps the first word;
+/// `Formal` gives "Dr./Dra. <apellido>" (the last word) when `rol`
+/// includes `doctor`, and the whole name otherwise.
+pub fn format_greeting(name: &str, rol: Option<&str>, style: GreetingStyle) -> String {
+    let mut words = name.split_whitespace();
+    let is_doctor = rol.is_some_and(|r| r.split(',').any(|r| r.parse::<Role>() == Ok(Role::Doctor)));
+    match style {
+        GreetingStyle::First => words.next().map(str::to_string),
+        GreetingStyle::Formal if is_doctor => words.next_back().map(|apellido| format!("Dr./Dra. {}", apellido)),
+        _ => None,
+    }
+    .unwrap_or_else(|| name.trim().to_string())
+}
+
+/// `greeting_name` for a JWT in `style`, using its `rol` claim for
+/// `Formal`; "usuario" when it can't be decoded.
+pub fn greeting_from_jwt(token: &str, style: GreetingStyle) -> String {
+    match decode_jwt_claims(token) {
+        Some(claims) => {
+            let roles = claims.get("rol").map(roles_from_value).unwrap_or_default().join(", ");
+            format_greeting(&greeting_name(&claims), Some(&roles), style)
+        }
+        None => FALLBACK_GREETING_NAME.to_string(),
+    }
+}
+
+/// Roles in a `rol` value: a single string or a list of strings. Blank
+/// entries are skipped.
+pub fn roles_from_value(value: &serde_json::Value) -> Vec<String> {
+    let roles: Vec<&str> = match value {
+        serde_json::Value::String(rol) => vec![rol.as_str()],
+        serde_json::Value::Array(items) => items.iter().filter_map(|r| r.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    roles.into_iter().map(str::trim).filter(|r| !r.is_empty()).map(str::to_string).collect()
+}
+
+/// Roles in a JWT's `rol` claim, `Role::Unknown` for names this client
+/// doesn't know; empty when there is none.
+pub fn token_roles(token: &str) -> Vec<Role> {
+    token_role_names(token).iter().map(|name| Role::from_claim(name)).collect()
+}
+
+// Role names in a JWT's `rol` claim, as written.
+fn token_role_names(token: &str) -> Vec<String> {
+    decode_jwt_claims(token)
+        .and_then(|claims| claims.get("rol").map(roles_from_value))
+        .unwrap_or_default()
+}
+
+/// The `rol` claim of a JWT as written, a list joined as "doctor,
+/// paciente", for display; `None` when there is none.
+pub(crate) fn joined_roles(token: &str) -> Option<String> {
+    let names = token_role_names(token);
+    (!names.is_empty()).then(|| names.join(", "))
+}
+
+/// Serde helper for `Profile::rol`: a role name or a list of them (the
+/// first one is kept).
+fn deserialize_role<'de, D>(deserializer: D) -> Result<Option<Role>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    let roles = value.as_ref().map(roles_from_value).unwrap_or_default();
+    Ok(roles.first().map(|name| Role::from_claim(name)))
+}
+
+/// Serde helper for `rol` fields that may hold a string or a list of
+/// roles; a list becomes "doctor, paciente".
+pub(crate) fn deserialize_rol<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    let roles = value.as_ref().map(roles_from_value).unwrap_or_default();
+    Ok((!roles.is_empty()).then(|| roles.join(", ")))
+}
+
+/// Decode the payload (second part) of a JWT without verifying the
+/// signature. Only use the result for display purposes.
+pub fn decode_jwt_claims(token: &str) -> Option<serde_json::Value> {
+    // JWT is three base64url parts separated by '.'; we want the payload (2nd part)
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    // base64 in JWT is URL-safe without padding; standard engine accepts padded base64,
+    // try to add padding if necessary.
+    let mut s = parts[1].replace('-', "+").replace('_', "/");
+    while !s.len().is_multiple_of(4) {
+        s.push('=');
+    }
+    let decoded = base64_standard.decode(&s).ok()?;
+    serde_json::from_slice(&decoded).ok()
+}
+
+/// Expiry (`exp` claim, seconds since the Unix epoch) of a JWT, if any.
+pub fn token_expiry(token: &str) -> Option<u64> {
+    decode_jwt_claims(token)?.get("exp")?.as_u64()
+}
+
+/// Whether a JWT's `exp` is in the past. `None` when it has no `exp`.
+pub fn is_token_expired(token: &str) -> Option<bool> {
+    let exp = token_expiry(token)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Some(exp <= now)
+}
@@ -0,0 +1,340 @@
+// Prompt source
+// -------------
+// Every question the menu asks goes through the `Prompter` trait instead
+// of calling dialoguer directly. `TerminalPrompter` is the real thing
+// (arrow-key selects, text inputs, hidden passwords); `ScriptedPrompter`
+// answers from a fixed list, so `main_menu` can be driven end to end in
+// tests and CI without a terminal. The prompter in use travels in
+// `UiOptions::prompts`.
+//
+// Scripted answers are plain strings:
+// - a select takes the label of the entry to pick ("Iniciar sesión");
+// - an input or password takes the text typed; an empty answer keeps
+//   the pre-filled or default text, like pressing Enter.
+// A scripted run fails instead of blocking when it runs out of answers,
+// names an entry the select doesn't show, or gives an answer the prompt's
+// validator rejects.
+//
+// The other direction goes through `Output`: every message the menu
+// prints, banners included, lands on stdout or, for a scripted run, in a
+// buffer the caller reads back (`UiOptions::output`).
+
+use crate::terminal::{self, PlainTerminal, Terminal};
+use anyhow::Result;
+use dialoguer::{Input, Password, Select};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Validator for typed answers: `Err` carries the message shown to the
+/// user, as with dialoguer's `validate_with`.
+pub type Validator<'a> = &'a dyn Fn(&str) -> std::result::Result<(), String>;
+
+/// TextPrompt
+///
+/// A free-text question. Build it with the `with_*` methods:
+/// `TextPrompt::new("Edad").with_validator(&check)`.
+#[derive(Clone, Copy)]
+pub struct TextPrompt<'a> {
+    pub prompt: &'a str,
+    /// Text already typed in the field, editable by the user.
+    pub initial: Option<&'a str>,
+    /// Answer used when the user just presses Enter.
+    pub default: Option<&'a str>,
+    /// Accept an empty answer (otherwise the question is asked again).
+    pub allow_empty: bool,
+    pub validator: Option<Validator<'a>>,
+}
+
+impl<'a> TextPrompt<'a> {
+    pub fn new(prompt: &'a str) -> Self {
+        TextPrompt { prompt, initial: None, default: None, allow_empty: false, validator: None }
+    }
+
+    pub fn with_initial(mut self, initial: Option<&'a str>) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    pub fn with_default(mut self, default: &'a str) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    pub fn with_allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+
+    pub fn with_validator(mut self, validator: Validator<'a>) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    fn check(&self, answer: &str) -> std::result::Result<(), String> {
+        self.validator.map_or(Ok(()), |validate| validate(answer))
+    }
+}
+
+/// Prompter
+///
+/// Where the UI's answers come from.
+pub trait Prompter: Send {
+    /// Pick one of `items` (index into it). `prompt` is the question
+    /// shown above the list, if any.
+    fn select(&mut self, prompt: Option<&str>, items: &[&str], default: usize) -> Result<usize>;
+    /// Read a line of text.
+    fn input(&mut self, text: TextPrompt<'_>) -> Result<String>;
+    /// Read a password without echoing it.
+    fn password(&mut self, prompt: &str, validator: Option<Validator<'_>>) -> Result<String>;
+    /// Whether `password` can work (a terminal for hidden input, or a
+    /// script).
+    fn can_read_password(&self) -> bool;
+    /// Whether answers come from a person at a keyboard; terminal-only
+    /// chores such as discarding stray keypresses are skipped otherwise.
+    fn is_interactive(&self) -> bool;
+}
+
+/// Prompts on the terminal with dialoguer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn select(&mut self, prompt: Option<&str>, items: &[&str], default: usize) -> Result<usize> {
+        let mut select = Select::new();
+        if let Some(prompt) = prompt {
+            select.with_prompt(prompt);
+        }
+        Ok(select.items(items).default(default).interact()?)
+    }
+
+    fn input(&mut self, text: TextPrompt<'_>) -> Result<String> {
+        let mut input = Input::<String>::new();
+        input.with_prompt(text.prompt).allow_empty(text.allow_empty);
+        if let Some(initial) = text.initial {
+            input.with_initial_text(initial);
+        }
+        if let Some(default) = text.default {
+            input.default(default.to_string());
+        }
+        if text.validator.is_some() {
+            input.validate_with(|v: &String| text.check(v));
+        }
+        Ok(input.interact_text()?)
+    }
+
+    fn password(&mut self, prompt: &str, validator: Option<Validator<'_>>) -> Result<String> {
+        let mut password = Password::new();
+        password.with_prompt(prompt);
+        if let Some(validate) = validator {
+            password.validate_with(move |v: &String| validate(v));
+        }
+        Ok(password.interact()?)
+    }
+
+    fn can_read_password(&self) -> bool {
+        crate::ui::can_prompt()
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// Answers prompts from a fixed list, in order (see the module notes for
+/// the format).
+#[derive(Debug, Default, Clone)]
+pub struct ScriptedPrompter {
+    answers: VecDeque<String>,
+}
+
+impl ScriptedPrompter {
+    pub fn new<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ScriptedPrompter { answers: answers.into_iter().map(Into::into).collect() }
+    }
+
+    fn next(&mut self, prompt: &str) -> Result<String> {
+        self.answers
+            .pop_front()
+            .ok_or_else(|| anyhow::anyhow!("El guion no tiene más respuestas (pregunta: {})", prompt))
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn select(&mut self, prompt: Option<&str>, items: &[&str], _default: usize) -> Result<usize> {
+        let prompt = prompt.unwrap_or("menú");
+        let answer = self.next(prompt)?;
+        items.iter().position(|item| *item == answer).ok_or_else(|| {
+            anyhow::anyhow!("El guion eligió '{}', que no está entre las opciones de '{}': {}", answer, prompt, items.join(", "))
+        })
+    }
+
+    fn input(&mut self, text: TextPrompt<'_>) -> Result<String> {
+        let mut answer = self.next(text.prompt)?;
+        if answer.is_empty() {
+            answer = text.initial.or(text.default).unwrap_or_default().to_string();
+        }
+        if answer.is_empty() && !text.allow_empty {
+            anyhow::bail!("El guion dejó vacía la respuesta a '{}'", text.prompt);
+        }
+        text.check(&answer)
+            .map_err(|e| anyhow::anyhow!("Respuesta del guion inválida para '{}': {}", text.prompt, e))?;
+        Ok(answer)
+    }
+
+    fn password(&mut self, prompt: &str, validator: Option<Validator<'_>>) -> Result<String> {
+        let answer = self.next(prompt)?;
+        if let Some(validate) = validator {
+            validate(&answer).map_err(|e| anyhow::anyhow!("Respuesta del guion inválida para '{}': {}", prompt, e))?;
+        }
+        Ok(answer)
+    }
+
+    fn can_read_password(&self) -> bool {
+        true
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Prompts
+///
+/// The prompter shared by the UI flows; clones use the same one. The
+/// default asks on the terminal.
+#[derive(Clone)]
+pub struct Prompts(Arc<Mutex<dyn Prompter>>);
+
+impl Prompts {
+    pub fn new(prompter: impl Prompter + 'static) -> Self {
+        Prompts(Arc::new(Mutex::new(prompter)))
+    }
+
+    /// Answer from `answers` instead of the terminal.
+    pub fn scripted<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Prompts::new(ScriptedPrompter::new(answers))
+    }
+
+    pub fn select(&self, prompt: Option<&str>, items: &[&str], default: usize) -> Result<usize> {
+        self.lock().select(prompt, items, default)
+    }
+
+    pub fn input(&self, text: TextPrompt<'_>) -> Result<String> {
+        self.lock().input(text)
+    }
+
+    pub fn password(&self, prompt: &str, validator: Option<Validator<'_>>) -> Result<String> {
+        self.lock().password(prompt, validator)
+    }
+
+    pub fn can_read_password(&self) -> bool {
+        self.lock().can_read_password()
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.lock().is_interactive()
+    }
+
+    // A prompter that panicked mid-question is still usable for the next.
+    fn lock(&self) -> std::sync::MutexGuard<'_, dyn Prompter + 'static> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for Prompts {
+    fn default() -> Self {
+        Prompts::new(TerminalPrompter)
+    }
+}
+
+impl std::fmt::Debug for Prompts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.is_interactive() { "Prompts(terminal)" } else { "Prompts(scripted)" })
+    }
+}
+
+/// Output
+///
+/// Where the UI's messages go; clones share it. The default prints to
+/// stdout; `Output::buffer` keeps the text (without terminal control
+/// sequences) for `contents` to return.
+#[derive(Clone, Default)]
+pub struct Output(Option<Arc<Mutex<Vec<u8>>>>);
+
+impl Output {
+    /// Print to stdout.
+    pub fn stdout() -> Self {
+        Output(None)
+    }
+
+    /// Collect everything in memory.
+    pub fn buffer() -> Self {
+        Output(Some(Arc::default()))
+    }
+
+    /// Print `text` and a newline.
+    pub fn line(&self, text: impl std::fmt::Display) {
+        match &self.0 {
+            None => println!("{}", text),
+            Some(buffer) => {
+                let _ = writeln!(SharedBuffer(Arc::clone(buffer)), "{}", text);
+            }
+        }
+    }
+
+    /// Print `text` as is.
+    pub fn text(&self, text: impl std::fmt::Display) {
+        match &self.0 {
+            None => print!("{}", text),
+            Some(buffer) => {
+                let _ = write!(SharedBuffer(Arc::clone(buffer)), "{}", text);
+            }
+        }
+    }
+
+    /// Where banners and line clearing go: the stdout terminal, or a
+    /// plain one writing into the buffer.
+    pub fn terminal(&self) -> Box<dyn Terminal> {
+        match &self.0 {
+            None => terminal::stdout(),
+            Some(buffer) => Box::new(PlainTerminal::new(SharedBuffer(Arc::clone(buffer)))),
+        }
+    }
+
+    /// Everything collected so far; empty when printing to stdout.
+    pub fn contents(&self) -> String {
+        match &self.0 {
+            None => String::new(),
+            Some(buffer) => String::from_utf8_lossy(&buffer.lock().unwrap_or_else(|e| e.into_inner())).into_owned(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(if self.0.is_some() { "Output(buffer)" } else { "Output(stdout)" })
+    }
+}
+
+// Writer appending to an `Output` buffer.
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+// Proxy detection
+// ---------------
+// reqwest sends requests through the proxy named by `--proxy` or by the
+// usual environment variables (`HTTPS_PROXY`/`HTTP_PROXY` for the URL's
+// scheme, then `ALL_PROXY`, upper or lower case, minus hosts listed in
+// `NO_PROXY`). A dead or mistyped proxy then shows up as a plain
+// connection error on every request. Working out which proxy applies
+// lets the client say so (see `ApiClient::send_once`) and skip the DNS
+// pre-flight, since the proxy resolves the gateway host itself.
+
+use url::Url;
+
+/// ProxySetting
+///
+/// The proxy a request goes through and where it was configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxySetting {
+    /// Proxy URL with any password replaced by `***`.
+    pub url: String,
+    /// `--proxy` or the environment variable that set it.
+    pub source: String,
+}
+
+impl std::fmt::Display for ProxySetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (configurado en {})", self.url, self.source)
+    }
+}
+
+/// The proxy used for `target`: `explicit` (`--proxy`) if set, else the
+/// first of the proxy variables read through `env` that applies.
+pub fn proxy_for(target: &Url, explicit: Option<&str>, env: impl Fn(&str) -> Option<String>) -> Option<ProxySetting> {
+    if let Some(url) = explicit {
+        return Some(ProxySetting { url: redact_password(url), source: "--proxy / NEUMODIAG_PROXY".into() });
+    }
+    let lookup = |name: &str| {
+        let lower = name.to_ascii_lowercase();
+        [name.to_string(), lower].into_iter().find_map(|n| env(&n).filter(|v| !v.trim().is_empty()).map(|v| (n, v)))
+    };
+    if let (Some(host), Some((_, no_proxy))) = (target.host_str(), lookup("NO_PROXY")) {
+        if bypasses_proxy(host, &no_proxy) {
+            return None;
+        }
+    }
+    let scheme_var = if target.scheme() == "https" { "HTTPS_PROXY" } else { "HTTP_PROXY" };
+    let (source, url) = lookup(scheme_var).or_else(|| lookup("ALL_PROXY"))?;
+    Some(ProxySetting { url: redact_password(url.trim()), source })
+}
+
+/// `proxy_for` with the real environment.
+pub fn proxy_from_env(target: &Url, explicit: Option<&str>) -> Option<ProxySetting> {
+    proxy_for(target, explicit, |name| std::env::var(name).ok())
+}
+
+// Whether `NO_PROXY` (comma-separated hosts or domain suffixes, `*` for
+// all) exempts `host`.
+fn bypasses_proxy(host: &str, no_proxy: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    no_proxy.split(',').map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase()).any(|entry| {
+        entry == "*" || (!entry.is_empty() && (host == entry || host.ends_with(&format!(".{}", entry))))
+    })
+}
+
+// Proxy credentials must not end up on screen or in error reports.
+fn redact_password(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("***"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
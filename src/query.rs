@@ -0,0 +1,77 @@
+// Query parameters for list endpoints
+// -----------------------------------
+// List and search endpoints share the same paging and filtering
+// parameters. `QueryParams` collects them with typed setters so callers
+// never format query strings by hand; parameters that were not set are
+// left out of the URL entirely.
+//
+// Names on the wire: `page`, `per_page`, `search`, then each filter
+// under its own key, in the order they were added. Values are
+// percent-encoded by `ApiClient::build_url` (or `to_query_string`).
+
+/// Paging, search and filters for a list request. Build it with the
+/// chained setters: `QueryParams::new().page(2).search("ana")`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryParams {
+    page: Option<u32>,
+    per_page: Option<u32>,
+    search: Option<String>,
+    filters: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 1-based page number.
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// Items per page.
+    pub fn per_page(mut self, per_page: u32) -> Self {
+        self.per_page = Some(per_page);
+        self
+    }
+
+    /// Free-text search. Blank text is the same as no search.
+    pub fn search(mut self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        self.search = if text.trim().is_empty() { None } else { Some(text) };
+        self
+    }
+
+    /// Filter on `key`; setting the same key again replaces its value.
+    pub fn filter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let (key, value) = (key.into(), value.into());
+        match self.filters.iter_mut().find(|(k, _)| *k == key) {
+            Some(slot) => slot.1 = value,
+            None => self.filters.push((key, value)),
+        }
+        self
+    }
+
+    /// The parameters that were set, as raw (unencoded) name/value pairs.
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        if let Some(page) = self.page {
+            pairs.push(("page".to_string(), page.to_string()));
+        }
+        if let Some(per_page) = self.per_page {
+            pairs.push(("per_page".to_string(), per_page.to_string()));
+        }
+        if let Some(search) = &self.search {
+            pairs.push(("search".to_string(), search.clone()));
+        }
+        pairs.extend(self.filters.iter().cloned());
+        pairs
+    }
+
+    /// The encoded query string without the leading `?`; empty when
+    /// nothing was set.
+    pub fn to_query_string(&self) -> String {
+        url::form_urlencoded::Serializer::new(String::new()).extend_pairs(self.pairs()).finish()
+    }
+}
@@ -0,0 +1,59 @@
+// Local data reset
+// ----------------
+// Everything the CLI writes to disk is listed here so `reset` (menu entry
+// "Borrar datos locales" and the `reset` subcommand) can remove it in one
+// go. Paths come from `StoragePaths`, the same source `paths` prints, so
+// a relocated token folder and `--config-path` are honoured. New
+// persisted files must be added to `StoragePaths` and `local_data_files`.
+//
+// The configuration file is user-authored, so it is listed separately
+// and only removed after its own confirmation.
+
+use crate::paths::StoragePaths;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+/// Word the user must type to confirm a reset.
+pub const RESET_CONFIRMATION_WORD: &str = "BORRAR";
+
+/// DataFile
+///
+/// A file the application may have created, with a short description
+/// used when reporting what was removed.
+#[derive(Debug, Clone)]
+pub struct DataFile {
+    pub path: PathBuf,
+    pub description: &'static str,
+}
+
+/// Files written by the application itself (session state, legacy token
+/// files, audit log and usage counts).
+pub fn local_data_files(paths: &StoragePaths) -> Vec<DataFile> {
+    vec![
+        DataFile { path: paths.session_file.clone(), description: "sesión guardada" },
+        DataFile { path: paths.legacy_token_file.clone(), description: "token de sesión (formato anterior)" },
+        DataFile { path: paths.legacy_meta_file.clone(), description: "metadatos de la sesión (formato anterior)" },
+        DataFile { path: paths.audit_log.clone(), description: "registro de auditoría" },
+        DataFile { path: paths.usage_file.clone(), description: "conteo de uso" },
+    ]
+}
+
+/// The configuration file in use (`--config-path` or the default one),
+/// removed only on explicit request.
+pub fn config_file(paths: &StoragePaths) -> DataFile {
+    DataFile { path: paths.config_file.clone(), description: "configuración" }
+}
+
+/// Delete the files that exist and return those removed. Missing files
+/// are skipped; any other failure aborts with the offending path.
+pub fn remove_data_files(files: &[DataFile]) -> Result<Vec<DataFile>> {
+    let mut removed = Vec::new();
+    for file in files {
+        if !file.path.exists() {
+            continue;
+        }
+        std::fs::remove_file(&file.path).with_context(|| format!("removing {}", file.path.display()))?;
+        removed.push(file.clone());
+    }
+    Ok(removed)
+}
@@ -0,0 +1,137 @@
+// Retry layer
+// -----------
+// Transient failures (connection refused/reset, timeouts, 502/503/504)
+// are retried a few times with a short linear backoff. Retries are off
+// by default; enable them with `--retries N` / `NEUMODIAG_RETRIES`.
+//
+// Every retry draws from a `RetryBudget` shared by all clones of the
+// `ApiClient`, i.e. by the whole CLI invocation. The budget keeps a burst
+// of failing operations from multiplying load on a struggling backend:
+// once it is spent, further failures are reported immediately.
+//
+// `retry_status` in the config adds statuses to the default set, for
+// gateways that answer 408/425/429 to requests that are safe to repeat.
+// Only statuses that can mean "try again" are accepted there (see
+// `validate_retry_statuses`).
+//
+// A policy may carry a progress channel: each retry sends a short status
+// line ("Reintentando (2/3)...") that the spinner shows in place of its
+// message. Nothing is sent when a call succeeds on the first attempt.
+
+use reqwest::StatusCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 4xx statuses `retry_status` may list; any 5xx is accepted too.
+pub const RETRYABLE_CLIENT_STATUSES: &[u16] = &[408, 423, 425, 429];
+
+/// Default number of retries allowed across the whole process.
+pub const DEFAULT_RETRY_BUDGET: usize = 10;
+// Base delay between attempts; attempt `n` waits `n` times this.
+const RETRY_BACKOFF_MS: u64 = 200;
+
+/// Process-wide pool of retries. Cloning shares the same pool.
+#[derive(Clone, Debug)]
+pub struct RetryBudget {
+    remaining: Arc<AtomicUsize>,
+}
+
+impl RetryBudget {
+    pub fn new(total: usize) -> Self {
+        RetryBudget { remaining: Arc::new(AtomicUsize::new(total)) }
+    }
+
+    /// Take one retry from the pool. Returns `false` once it is empty.
+    pub fn try_take(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Retries still available.
+    pub fn remaining(&self) -> usize {
+        self.remaining.load(Ordering::SeqCst)
+    }
+}
+
+/// How a single call retries: up to `max_retries` extra attempts, each
+/// paid for from the shared `budget`. Retries are announced on
+/// `progress` when set. `extra_statuses` are retried on top of the
+/// default 502/503/504.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: usize,
+    pub budget: RetryBudget,
+    pub progress: Option<Sender<String>>,
+    pub extra_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            budget: RetryBudget::new(DEFAULT_RETRY_BUDGET),
+            progress: None,
+            extra_statuses: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a response with `status` is retried under this policy.
+    pub fn retries_status(&self, status: StatusCode) -> bool {
+        is_retryable_status(status) || self.extra_statuses.contains(&status.as_u16())
+    }
+
+    /// Delay before retry number `attempt` (1-based).
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        Duration::from_millis(RETRY_BACKOFF_MS * attempt as u64)
+    }
+
+    /// Announce retry number `attempt` on the progress channel, if any.
+    /// A closed channel (spinner already gone) is ignored.
+    pub fn report(&self, attempt: usize) {
+        if let Some(tx) = &self.progress {
+            let _ = tx.send(retry_message(attempt, self.max_retries));
+        }
+    }
+}
+
+/// Status line shown while retry `attempt` of `max` is in flight.
+pub fn retry_message(attempt: usize, max: usize) -> String {
+    format!("Reintentando ({}/{})...", attempt, max)
+}
+
+/// Gateway statuses that usually mean "try again shortly".
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Check a `retry_status` list: each entry must be one of
+/// `RETRYABLE_CLIENT_STATUSES` or a 5xx. Statuses such as 400 or 401
+/// would fail the same way on every attempt.
+pub fn validate_retry_statuses(statuses: &[u16]) -> anyhow::Result<()> {
+    for &status in statuses {
+        if !(RETRYABLE_CLIENT_STATUSES.contains(&status) || (500..=599).contains(&status)) {
+            anyhow::bail!(
+                "retry_status: {} no es un estado reintentable (se admiten {} y 5xx)",
+                status,
+                RETRYABLE_CLIENT_STATUSES.iter().map(u16::to_string).collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Transport failures worth retrying: the connection could not be made
+/// or the request timed out.
+pub fn is_retryable_error(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<reqwest::Error>())
+        .any(|e| e.is_connect() || e.is_timeout())
+}
@@ -0,0 +1,100 @@
+// Session transfer
+// ----------------
+// Helpers to move a logged-in session between machines. A session is
+// exported as a single line of text that can be copied or redirected to
+// a file; the token and its meta are encrypted with AES-256-GCM under a
+// key derived from a user passphrase (PBKDF2-HMAC-SHA256), so the blob is
+// safe to pass through chat or shared folders as long as the passphrase
+// travels separately.
+//
+// Blob layout: `neumodiag-session-v1:` + base64(salt | nonce | ciphertext).
+
+use crate::api::TokenMeta;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as base64_standard;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+
+const BLOB_PREFIX: &str = "neumodiag-session-v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+// OWASP's 2023 recommendation for PBKDF2-HMAC-SHA256 is 600k; the blob
+// is short-lived, so a lower count keeps export/import snappy.
+const PBKDF2_ROUNDS: u32 = 200_000;
+
+/// SessionBundle
+///
+/// What travels inside an exported blob: the raw token plus the meta JSON
+/// stored next to it (`persist`, `clean_exit`, ...).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionBundle {
+    pub token: String,
+    #[serde(default)]
+    pub meta: serde_json::Value,
+}
+
+impl SessionBundle {
+    /// The bundled meta as a `TokenMeta`. A bundle without meta, or
+    /// without `persist`, is imported as a remembered session, as it was
+    /// before the meta was typed.
+    pub fn token_meta(&self) -> Result<TokenMeta> {
+        if self.meta.is_null() {
+            return Ok(TokenMeta::new(true).migrate());
+        }
+        let mut meta: TokenMeta = serde_json::from_value(self.meta.clone()).context("parsing exported session meta")?;
+        if self.meta.get("persist").is_none() {
+            meta.persist = true;
+        }
+        Ok(meta.migrate())
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key<Aes256Gcm> {
+    let mut key = Key::<Aes256Gcm>::default();
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Encrypt `bundle` with `passphrase` and return the exportable blob.
+pub fn export_session(bundle: &SessionBundle, passphrase: &str) -> Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, &salt));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let plain = serde_json::to_vec(bundle).context("serializing session")?;
+    let sealed = cipher
+        .encrypt(&nonce, plain.as_slice())
+        .map_err(|_| anyhow::anyhow!("No se pudo cifrar la sesión"))?;
+
+    let mut raw = Vec::with_capacity(SALT_LEN + NONCE_LEN + sealed.len());
+    raw.extend_from_slice(&salt);
+    raw.extend_from_slice(&nonce);
+    raw.extend_from_slice(&sealed);
+    Ok(format!("{}{}", BLOB_PREFIX, base64_standard.encode(raw)))
+}
+
+/// Decrypt a blob produced by `export_session`. A wrong passphrase and a
+/// tampered blob are indistinguishable by design and yield the same error.
+pub fn import_session(blob: &str, passphrase: &str) -> Result<SessionBundle> {
+    let encoded = blob
+        .trim()
+        .strip_prefix(BLOB_PREFIX)
+        .context("El contenido no es una sesión exportada de NeumoDiagnostics")?;
+    let raw = base64_standard.decode(encoded).context("La sesión exportada está dañada")?;
+    if raw.len() <= SALT_LEN + NONCE_LEN {
+        anyhow::bail!("La sesión exportada está dañada");
+    }
+    let (salt, rest) = raw.split_at(SALT_LEN);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, salt));
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), sealed)
+        .map_err(|_| anyhow::anyhow!("Frase de paso incorrecta o sesión dañada"))?;
+    let bundle: SessionBundle = serde_json::from_slice(&plain).context("parsing session json")?;
+    Ok(bundle)
+}
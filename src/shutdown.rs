@@ -0,0 +1,85 @@
+// Clean shutdown on SIGTERM
+// -------------------------
+// A supervisor (systemd, a container runtime, `kill`) stops the CLI with
+// SIGTERM. By default that kills the process wherever it is, possibly in
+// the middle of writing the token files. On Unix `install_handler`
+// replaces that with an atomic flag, and `spawn_watcher` polls it from a
+// background thread: once set, it clears the spinner line, restores the
+// terminal, optionally marks `clean_exit` in the token meta and exits.
+//
+// Session state writes hold `STATE_WRITE` and replace the file with a
+// rename (see `state.rs`), and the watcher takes the same lock before
+// exiting, so the file is never left half written.
+
+use crate::api::ApiClient;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+// How often the watcher looks at the flag.
+const WATCH_INTERVAL_MS: u64 = 100;
+// Exit status for a SIGTERM shutdown (128 + 15, as shells report it).
+pub const SIGTERM_EXIT_CODE: i32 = 143;
+
+/// Held while the token or meta file is being written.
+pub static STATE_WRITE: Mutex<()> = Mutex::new(());
+
+static REQUESTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+fn flag() -> &'static Arc<AtomicBool> {
+    REQUESTED.get_or_init(|| Arc::new(AtomicBool::new(false)))
+}
+
+/// Route SIGTERM to the shutdown flag instead of killing the process.
+/// Does nothing on platforms without Unix signals.
+pub fn install_handler() -> Result<()> {
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(flag()))?;
+    Ok(())
+}
+
+/// Ask for a shutdown, exactly as the SIGTERM handler does.
+pub fn request() {
+    flag().store(true, Ordering::SeqCst);
+}
+
+/// Whether a shutdown has been requested.
+pub fn requested() -> bool {
+    flag().load(Ordering::SeqCst)
+}
+
+/// Lock out token/meta writes, recovering the lock if a writer panicked.
+pub fn lock_state_writes() -> MutexGuard<'static, ()> {
+    STATE_WRITE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Everything the watcher does before exiting: restore the terminal, wait
+/// for any token write in progress, set `clean_exit` when `mark_clean` is
+/// true, and return the write lock so no new write can start.
+pub fn prepare_exit(api: &ApiClient, mark_clean: bool) -> MutexGuard<'static, ()> {
+    let _ = crossterm::terminal::disable_raw_mode();
+    // Drop a half-drawn spinner line and bring the cursor back.
+    let mut term = crate::terminal::stderr();
+    term.clear_line();
+    term.show_cursor();
+    let guard = lock_state_writes();
+    if mark_clean {
+        let _ = api.write_clean_exit_meta(true);
+    }
+    guard
+}
+
+/// Start the background thread that exits the process once a shutdown is
+/// requested. `mark_clean` is passed on to `prepare_exit`.
+pub fn spawn_watcher(api: ApiClient, mark_clean: bool) {
+    thread::spawn(move || loop {
+        if requested() {
+            let _guard = prepare_exit(&api, mark_clean);
+            eprintln!("Señal de terminación recibida: cerrando la aplicación.");
+            std::process::exit(SIGTERM_EXIT_CODE);
+        }
+        thread::sleep(Duration::from_millis(WATCH_INTERVAL_MS));
+    });
+}
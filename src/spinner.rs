@@ -0,0 +1,317 @@
+// Progress feedback
+// -----------------
+// All "work in progress" feedback goes through `Spinner`, and blocking
+// calls are run with `run_with_spinner`, which moves the work to a
+// background thread and polls for the result while ticking the spinner
+// on the calling thread (this keeps the animation alive on Windows
+// cmd.exe, where a blocked main thread freezes it).
+//
+// When stderr is not a terminal (piped or redirected to a log) the
+// animated spinner would write carriage returns and ANSI sequences into
+// the log, so a plain variant is used instead: one line when the work
+// starts and a "mensaje (Ns)" line every few seconds after that.
+//
+// `run_with_progress` also hands the work a channel for status updates
+// (e.g. retry attempts from the API client); each update replaces the
+// spinner message.
+//
+// `run_with_deadline` stops waiting after a limit and abandons the worker
+// thread. The worker gets a flag that is set at that point, so it can
+// stay silent if it ever finishes; its result is dropped.
+//
+// `--fast-results` drops the minimum display time (see
+// `set_min_display_ms`): the spinner still shows while work runs, but
+// results appear as soon as it finishes. Errors that point at a broken
+// setup (see `is_fail_fast_error`) skip it too, so a mistyped URL is
+// reported at once instead of after the artificial delay.
+//
+// `NEUMODIAG_SPINNER_TEMPLATE` replaces the animated spinner's indicatif
+// template; an invalid one falls back to the default with a warning.
+
+use anyhow::Result;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Minimum spinner display time in milliseconds so short operations still
+// show a visible spinner for the user.
+pub const MIN_SPINNER_MS: u64 = 1500;
+// Minimum display time in effect (see `set_min_display_ms`).
+static MIN_DISPLAY_MS: AtomicU64 = AtomicU64::new(MIN_SPINNER_MS);
+// Interval between spinner ticks / result polls.
+const TICK_MS: u64 = 80;
+// How often the plain (non-TTY) variant reports elapsed time.
+const PLAIN_REPORT_EVERY_SECS: u64 = 3;
+/// Template of the animated spinner.
+pub const DEFAULT_SPINNER_TEMPLATE: &str = "{spinner} {msg}";
+// Overrides the animated spinner's indicatif template.
+const SPINNER_TEMPLATE_ENV: &str = "NEUMODIAG_SPINNER_TEMPLATE";
+
+/// Spinner
+///
+/// Either an animated indicatif spinner on stderr or, for non-terminal
+/// output, plain progress lines written to `out`.
+pub enum Spinner {
+    Animated(ProgressBar),
+    Plain {
+        out: Box<dyn Write + Send>,
+        message: String,
+        started: Instant,
+        reported_secs: u64,
+    },
+}
+
+impl Spinner {
+    /// Pick the variant for the current stderr: animated on a terminal,
+    /// plain lines otherwise.
+    pub fn new(message: &str) -> Spinner {
+        if std::io::stderr().is_terminal() {
+            Spinner::animated(message)
+        } else {
+            Spinner::plain(message, Box::new(std::io::stderr()))
+        }
+    }
+
+    /// Animated spinner drawn on stderr.
+    pub fn animated(message: &str) -> Spinner {
+        let template = std::env::var(SPINNER_TEMPLATE_ENV).unwrap_or_else(|_| DEFAULT_SPINNER_TEMPLATE.into());
+        let (style, warning) = spinner_style(&template);
+        if let Some(warning) = warning {
+            eprintln!("Aviso: {}", warning);
+        }
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(style);
+        pb.set_draw_target(ProgressDrawTarget::stderr());
+        pb.set_message(message.to_string());
+        Spinner::Animated(pb)
+    }
+
+    /// Plain-text progress written to `out`, free of control sequences.
+    pub fn plain(message: &str, mut out: Box<dyn Write + Send>) -> Spinner {
+        let _ = writeln!(out, "{}", message);
+        Spinner::Plain { out, message: message.to_string(), started: Instant::now(), reported_secs: 0 }
+    }
+
+    /// Advance the animation, or report elapsed time when due.
+    pub fn tick(&mut self) {
+        match self {
+            Spinner::Animated(pb) => pb.tick(),
+            Spinner::Plain { out, message, started, reported_secs } => {
+                let secs = started.elapsed().as_secs();
+                if secs >= *reported_secs + PLAIN_REPORT_EVERY_SECS {
+                    *reported_secs = secs;
+                    let _ = writeln!(out, "{} ({}s)", message, secs);
+                }
+            }
+        }
+    }
+
+    /// Replace the message. The plain variant prints the new message on
+    /// its own line.
+    pub fn set_message(&mut self, new_message: &str) {
+        match self {
+            Spinner::Animated(pb) => pb.set_message(new_message.to_string()),
+            Spinner::Plain { out, message, .. } => {
+                *message = new_message.to_string();
+                let _ = writeln!(out, "{}", message);
+            }
+        }
+    }
+
+    /// Stop and remove the spinner.
+    pub fn finish(self) {
+        match self {
+            Spinner::Animated(pb) => pb.finish_and_clear(),
+            Spinner::Plain { mut out, .. } => {
+                let _ = out.flush();
+            }
+        }
+    }
+}
+
+/// Keep the animated spinner up for at least `ms` milliseconds, for
+/// every later run (`MIN_SPINNER_MS` by default). 0 returns results as
+/// soon as the work finishes (`--fast-results`).
+pub fn set_min_display_ms(ms: u64) {
+    MIN_DISPLAY_MS.store(ms, Ordering::SeqCst);
+}
+
+/// Minimum display time currently in effect, in milliseconds.
+pub fn min_display_ms() -> u64 {
+    MIN_DISPLAY_MS.load(Ordering::SeqCst)
+}
+
+/// Style for an indicatif `template`. An invalid template falls back to
+/// `DEFAULT_SPINNER_TEMPLATE` (and, should even that fail, indicatif's
+/// own default) and returns a warning to show, so a bad custom template
+/// never crashes the CLI.
+pub fn spinner_style(template: &str) -> (ProgressStyle, Option<String>) {
+    match ProgressStyle::with_template(template) {
+        Ok(style) => (style, None),
+        Err(e) => {
+            let fallback = ProgressStyle::with_template(DEFAULT_SPINNER_TEMPLATE).unwrap_or_else(|_| ProgressStyle::default_spinner());
+            (fallback, Some(format!("plantilla de spinner inválida ({}); se usa la predeterminada", e)))
+        }
+    }
+}
+
+/// Whether `err` shows the request could not even start because of the
+/// setup: an invalid gateway URL, a host name that doesn't resolve, or a
+/// refused connection (nothing listening on that port). Waiting longer
+/// would not change these.
+pub fn is_fail_fast_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        e.is::<url::ParseError>()
+            || e.is::<crate::dns::ResolveError>()
+            || e.downcast_ref::<reqwest::Error>().is_some_and(reqwest::Error::is_builder)
+            || e.downcast_ref::<std::io::Error>().is_some_and(|io| io.kind() == std::io::ErrorKind::ConnectionRefused)
+    })
+}
+
+// `Err` results that skip the minimum display time.
+fn fails_fast<T>(res: &Result<T>) -> bool {
+    res.as_ref().err().is_some_and(is_fail_fast_error)
+}
+
+/// Run `work` on a background thread while showing a spinner with
+/// `message`. The animated spinner stays up for at least
+/// `min_display_ms()` so fast operations remain visible, unless `work`
+/// fails fast (see `is_fail_fast_error`). Returns `None` if the worker
+/// thread died without producing a result.
+pub fn run_with_spinner<T, F>(message: &str, work: F) -> Option<Result<T>>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    run_with_progress(message, move |_| work())
+}
+
+/// Like `run_with_spinner`, but `work` receives a sender whose messages
+/// replace the spinner text as they arrive.
+pub fn run_with_progress<T, F>(message: &str, work: F) -> Option<Result<T>>
+where
+    F: FnOnce(Sender<String>) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    run_result_on(Spinner::new(message), work)
+}
+
+/// `run_with_progress` with an explicit spinner.
+pub fn run_result_on<T, F>(spinner: Spinner, work: F) -> Option<Result<T>>
+where
+    F: FnOnce(Sender<String>) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match drive(spinner, None, fails_fast, move |progress, _| work(progress)) {
+        Outcome::Finished(res) => Some(res),
+        Outcome::Died | Outcome::TimedOut => None,
+    }
+}
+
+/// Drive `spinner` until `work` finishes; like `run_result_on` for work
+/// of any type, which always gets the minimum display time.
+pub fn run_on<T, F>(spinner: Spinner, work: F) -> Option<T>
+where
+    F: FnOnce(Sender<String>) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match drive(spinner, None, |_| false, move |progress, _| work(progress)) {
+        Outcome::Finished(res) => Some(res),
+        Outcome::Died | Outcome::TimedOut => None,
+    }
+}
+
+/// Outcome
+///
+/// How a background job run under a spinner ended.
+#[derive(Debug, PartialEq)]
+pub enum Outcome<T> {
+    Finished(T),
+    /// The worker thread died (panicked) without a result.
+    Died,
+    /// The deadline passed first; the worker was abandoned.
+    TimedOut,
+}
+
+/// Run `work` under a spinner, giving up after `limit`. `work` receives
+/// a flag that turns `true` once it has been abandoned.
+pub fn run_with_deadline<T, F>(message: &str, limit: Duration, work: F) -> Outcome<T>
+where
+    F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    run_on_with_deadline(Spinner::new(message), limit, work)
+}
+
+/// `run_with_deadline` with an explicit spinner.
+pub fn run_on_with_deadline<T, F>(spinner: Spinner, limit: Duration, work: F) -> Outcome<T>
+where
+    F: FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    drive(spinner, Some(limit), |_| false, move |_, abandoned| work(abandoned))
+}
+
+/// `run_with_deadline` whose work also gets the status-update channel of
+/// `run_with_progress` (and, like it, may fail fast).
+pub fn run_with_progress_and_deadline<T, F>(message: &str, limit: Duration, work: F) -> Outcome<Result<T>>
+where
+    F: FnOnce(Sender<String>, Arc<AtomicBool>) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    drive(Spinner::new(message), Some(limit), fails_fast, work)
+}
+
+// Run `work` under `spinner`. Results for which `skip_min` is true are
+// returned without waiting out the minimum display time.
+fn drive<T, F>(mut spinner: Spinner, limit: Option<Duration>, skip_min: fn(&T) -> bool, work: F) -> Outcome<T>
+where
+    F: FnOnce(Sender<String>, Arc<AtomicBool>) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = channel();
+    let (progress_tx, progress_rx) = channel::<String>();
+    let abandoned = Arc::new(AtomicBool::new(false));
+    let worker_flag = Arc::clone(&abandoned);
+    thread::spawn(move || {
+        let _ = tx.send(work(progress_tx, worker_flag));
+    });
+
+    let start = Instant::now();
+    loop {
+        while let Ok(update) = progress_rx.try_recv() {
+            spinner.set_message(&update);
+        }
+        match rx.try_recv() {
+            Ok(res) => {
+                // if result arrived too quickly, keep spinning until min time
+                if matches!(spinner, Spinner::Animated(_)) && !skip_min(&res) {
+                    let min = Duration::from_millis(min_display_ms());
+                    while start.elapsed() < min {
+                        spinner.tick();
+                        thread::sleep(Duration::from_millis(TICK_MS));
+                    }
+                }
+                spinner.finish();
+                return Outcome::Finished(res);
+            }
+            Err(TryRecvError::Empty) => {
+                if limit.is_some_and(|limit| start.elapsed() >= limit) {
+                    abandoned.store(true, Ordering::SeqCst);
+                    spinner.finish();
+                    return Outcome::TimedOut;
+                }
+                spinner.tick();
+                thread::sleep(Duration::from_millis(TICK_MS));
+            }
+            Err(TryRecvError::Disconnected) => {
+                spinner.finish();
+                return Outcome::Died;
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// Persisted session state
+// -----------------------
+// The saved session used to live in two files, `.neumodiag_token` (raw
+// token) and `.neumodiag_token.meta` (JSON flags). Both now live in one
+// versioned file, `.neumodiag_state.json`, holding an `AppState`
+// serialized with serde. `StateStore` reads and writes it, always
+// replacing the whole file atomically, so adding a field or changing the
+// layout is a matter of bumping `STATE_VERSION` and migrating in `load`.
+//
+// Legacy two-file layouts are read transparently; the next save writes
+// the new file and deletes the old ones.
+//
+// The audit log and the usage counts stay separate: the first is an
+// append-only record and the second is meant to be exported by the user.
+//
+// `StateStore` does no locking of its own; `ApiClient` holds
+// `shutdown::STATE_WRITE` around every write.
+
+use crate::api::{write_atomically, TokenMeta, TOKEN_FILE_NAME, TOKEN_META_FILE_NAME};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Session state file, next to the other CLI files.
+pub const STATE_FILE_NAME: &str = ".neumodiag_state.json";
+/// Current `AppState` layout.
+pub const STATE_VERSION: u32 = 1;
+
+/// AppState
+///
+/// Everything persisted about the saved session.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AppState {
+    pub version: u32,
+    /// The saved session token, if any.
+    pub token: Option<String>,
+    /// Flags about the saved session (`persist`, `clean_exit`).
+    pub meta: Option<TokenMeta>,
+}
+
+/// StateStore
+///
+/// Reads and writes the `AppState` of one folder.
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(dir: &Path) -> StateStore {
+        StateStore { dir: dir.to_path_buf() }
+    }
+
+    /// Path of the state file.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(STATE_FILE_NAME)
+    }
+
+    fn legacy_paths(&self) -> [PathBuf; 2] {
+        [self.dir.join(TOKEN_FILE_NAME), self.dir.join(TOKEN_META_FILE_NAME)]
+    }
+
+    /// Read the state: the state file, else the legacy two-file layout,
+    /// else an empty state.
+    pub fn load(&self) -> Result<AppState> {
+        let path = self.path();
+        if path.exists() {
+            let s = std::fs::read_to_string(&path).context("reading state file")?;
+            let mut state: AppState = serde_json::from_str(&s).context("parsing state file")?;
+            state.meta = state.meta.map(TokenMeta::migrate);
+            state.version = STATE_VERSION;
+            return Ok(state);
+        }
+        self.load_legacy()
+    }
+
+    fn load_legacy(&self) -> Result<AppState> {
+        let [token_path, meta_path] = self.legacy_paths();
+        let token = match token_path.exists() {
+            true => Some(std::fs::read_to_string(&token_path).context("reading token file")?),
+            false => None,
+        };
+        let meta = match meta_path.exists() {
+            true => Some(TokenMeta::from_json(&std::fs::read_to_string(&meta_path).context("reading meta file")?)?),
+            false => None,
+        };
+        Ok(AppState { version: STATE_VERSION, token, meta })
+    }
+
+    /// Replace the state file with `state` and drop any legacy files.
+    pub fn save(&self, state: &AppState) -> Result<()> {
+        let state = AppState { version: STATE_VERSION, ..state.clone() };
+        let s = serde_json::to_string(&state).context("serializing state")?;
+        write_atomically(&self.path(), s.as_bytes()).context("writing state file")?;
+        for legacy in self.legacy_paths() {
+            let _ = std::fs::remove_file(legacy);
+        }
+        Ok(())
+    }
+
+    /// Load, change and save the state in one step.
+    pub fn update(&self, change: impl FnOnce(&mut AppState)) -> Result<()> {
+        let mut state = self.load()?;
+        change(&mut state);
+        self.save(&state)
+    }
+
+    /// Delete the state file and any legacy files.
+    pub fn clear(&self) {
+        let _ = std::fs::remove_file(self.path());
+        for legacy in self.legacy_paths() {
+            let _ = std::fs::remove_file(legacy);
+        }
+    }
+}
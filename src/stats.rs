@@ -0,0 +1,98 @@
+// Session statistics
+// ------------------
+// Counters for one interactive session (logins, uploads, errors shown),
+// printed as a short summary when the menu exits. Handlers update the
+// process-wide `SESSION_STATS`; the summary format is chosen with
+// `--summary text|json|none`.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How the exit summary is printed.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SummaryFormat {
+    /// A few human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object on stdout.
+    Json,
+    /// No summary.
+    None,
+}
+
+/// SessionStats
+///
+/// Thread-safe counters; background workers may update them too.
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    logins: AtomicUsize,
+    uploads_attempted: AtomicUsize,
+    uploads_succeeded: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+/// StatsSnapshot
+///
+/// Counter values at one point in time.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub logins: usize,
+    pub uploads_attempted: usize,
+    pub uploads_succeeded: usize,
+    pub errors: usize,
+}
+
+impl SessionStats {
+    pub const fn new() -> Self {
+        SessionStats {
+            logins: AtomicUsize::new(0),
+            uploads_attempted: AtomicUsize::new(0),
+            uploads_succeeded: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+        }
+    }
+
+    /// A successful login.
+    pub fn record_login(&self) {
+        self.logins.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// An upload attempt and whether it succeeded.
+    pub fn record_upload(&self, succeeded: bool) {
+        self.uploads_attempted.fetch_add(1, Ordering::SeqCst);
+        if succeeded {
+            self.uploads_succeeded.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// An error shown to the user.
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            logins: self.logins.load(Ordering::SeqCst),
+            uploads_attempted: self.uploads_attempted.load(Ordering::SeqCst),
+            uploads_succeeded: self.uploads_succeeded.load(Ordering::SeqCst),
+            errors: self.errors.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl StatsSnapshot {
+    /// The summary in `format`, or `None` when nothing should be printed.
+    pub fn render(&self, format: SummaryFormat) -> Option<String> {
+        match format {
+            SummaryFormat::None => None,
+            SummaryFormat::Json => serde_json::to_string(self).ok(),
+            SummaryFormat::Text => Some(format!(
+                "Resumen de la sesión: {} inicio(s) de sesión, {}/{} subida(s) exitosa(s), {} error(es).",
+                self.logins, self.uploads_succeeded, self.uploads_attempted, self.errors
+            )),
+        }
+    }
+}
+
+/// Counters for the current process.
+pub static SESSION_STATS: SessionStats = SessionStats::new();
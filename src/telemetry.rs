@@ -0,0 +1,95 @@
+// Usage counts (opt-in)
+// ---------------------
+// With `telemetry = true` in `neumodiag.toml` the menu counts how often
+// each entry is chosen, in `.neumodiag_usage.json` next to the token
+// files. Only menu entry names are stored: no user data, no payloads.
+// Nothing is ever sent over the network; `neumodiag-cli usage` prints
+// the file so the user can share it if they want to.
+//
+// Off by default. The first run with it enabled prints a notice once.
+// `--ephemeral` disables it, and `reset` deletes the file.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Usage file, next to the token files.
+pub const USAGE_FILE_NAME: &str = ".neumodiag_usage.json";
+
+/// Shown the first time usage counting is enabled.
+pub const TELEMETRY_NOTICE: &str = "Aviso: el conteo anónimo de uso está activado (telemetry = true). \
+Solo se cuenta qué opciones del menú se usan, en un archivo local; nada se envía por la red. \
+Vea los datos con `neumodiag-cli usage`.";
+
+/// Contents of the usage file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct UsageData {
+    pub notice_shown: bool,
+    pub counts: BTreeMap<String, u64>,
+}
+
+/// Telemetry
+///
+/// Handle used by the menu. A disabled handle ignores every call.
+#[derive(Debug, Clone)]
+pub struct Telemetry {
+    path: Option<PathBuf>,
+}
+
+impl Telemetry {
+    /// Counting into `dir` when `enabled`, otherwise a no-op handle.
+    pub fn new(enabled: bool, dir: &Path) -> Telemetry {
+        Telemetry { path: enabled.then(|| dir.join(USAGE_FILE_NAME)) }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Returns `true` the first time it is called with telemetry enabled
+    /// (the caller then prints `TELEMETRY_NOTICE`), `false` afterwards.
+    pub fn first_run_notice(&self) -> Result<bool> {
+        let path = match &self.path {
+            Some(p) => p,
+            None => return Ok(false),
+        };
+        let mut data = read_usage(path)?;
+        if data.notice_shown {
+            return Ok(false);
+        }
+        data.notice_shown = true;
+        write_usage(path, &data)?;
+        Ok(true)
+    }
+
+    /// Count one use of `action` (a menu entry name). Best-effort: a
+    /// failure to write never interrupts the user.
+    pub fn record(&self, action: &str) {
+        if let Some(path) = &self.path {
+            if let Ok(mut data) = read_usage(path) {
+                *data.counts.entry(action.to_string()).or_insert(0) += 1;
+                let _ = write_usage(path, &data);
+            }
+        }
+    }
+}
+
+/// Read the usage file in `dir`; an absent file means no data.
+pub fn load_usage(dir: &Path) -> Result<UsageData> {
+    read_usage(&dir.join(USAGE_FILE_NAME))
+}
+
+fn read_usage(path: &Path) -> Result<UsageData> {
+    if !path.exists() {
+        return Ok(UsageData::default());
+    }
+    let s = std::fs::read_to_string(path).context("reading usage file")?;
+    serde_json::from_str(&s).context("parsing usage file")
+}
+
+fn write_usage(path: &Path, data: &UsageData) -> Result<()> {
+    let s = serde_json::to_string_pretty(data).context("serializing usage data")?;
+    std::fs::write(path, s).context("writing usage file")
+}
@@ -0,0 +1,174 @@
+// Terminal control
+// ----------------
+// Cursor movement, line clearing and the `=====` banners only make sense
+// on a terminal; written to a pipe or a log file they show up as escape
+// sequences and decoration. Everything of that kind goes through the
+// `Terminal` trait: `AnsiTerminal` issues the crossterm commands and
+// `PlainTerminal`, picked when the stream is not a TTY, skips them and
+// keeps only the text (banner titles).
+
+use crossterm::cursor::{MoveToColumn, MoveUp, Show};
+use crossterm::execute;
+use crossterm::style::{Color, ResetColor, SetForegroundColor};
+use crossterm::terminal::{Clear, ClearType};
+use std::io::{IsTerminal, Write};
+
+/// Terminal
+///
+/// Terminal manipulation used by the UI. Every method is best-effort:
+/// write errors are ignored.
+pub trait Terminal {
+    /// Erase the `n` lines above the cursor.
+    fn clear_previous_lines(&mut self, n: u16);
+    /// Erase the current line (e.g. a half-drawn spinner).
+    fn clear_line(&mut self);
+    /// Make the cursor visible again.
+    fn show_cursor(&mut self);
+    /// A `=` rule `width` columns wide.
+    fn rule(&mut self, width: usize);
+    /// `text` centered within `width` columns, wrapped onto more lines
+    /// when it is wider (see `wrap_title`).
+    fn centered(&mut self, text: &str, width: usize);
+    /// An advisory line, `Aviso: <text>`, in yellow where colour is
+    /// available.
+    fn warning(&mut self, text: &str);
+    /// The terminal bell (`\x07`), to flag a finished operation.
+    fn bell(&mut self);
+}
+
+/// Crossterm-backed implementation for real terminals.
+pub struct AnsiTerminal<W: Write> {
+    out: W,
+}
+
+impl<W: Write> AnsiTerminal<W> {
+    pub fn new(out: W) -> Self {
+        AnsiTerminal { out }
+    }
+}
+
+impl<W: Write> Terminal for AnsiTerminal<W> {
+    fn clear_previous_lines(&mut self, n: u16) {
+        for _ in 0..n {
+            let _ = execute!(self.out, MoveUp(1), MoveToColumn(0), Clear(ClearType::CurrentLine));
+        }
+    }
+
+    fn clear_line(&mut self) {
+        let _ = execute!(self.out, Clear(ClearType::CurrentLine), MoveToColumn(0));
+    }
+
+    fn show_cursor(&mut self) {
+        let _ = execute!(self.out, Show);
+    }
+
+    fn rule(&mut self, width: usize) {
+        let _ = writeln!(self.out, "{}", "=".repeat(width));
+    }
+
+    fn centered(&mut self, text: &str, width: usize) {
+        for line in wrap_title(text, width) {
+            let padding = width.saturating_sub(line.chars().count()) / 2;
+            let _ = writeln!(self.out, "{:padding$}{}{:padding$}", "", line, "", padding = padding);
+        }
+    }
+
+    fn warning(&mut self, text: &str) {
+        let _ = execute!(self.out, SetForegroundColor(Color::Yellow));
+        let _ = write!(self.out, "Aviso: {}", text);
+        let _ = execute!(self.out, ResetColor);
+        let _ = writeln!(self.out);
+    }
+
+    fn bell(&mut self) {
+        let _ = write!(self.out, "\x07");
+        let _ = self.out.flush();
+    }
+}
+
+/// Implementation for pipes and files: no control sequences and no
+/// decoration, only the text itself.
+pub struct PlainTerminal<W: Write> {
+    out: W,
+}
+
+impl<W: Write> PlainTerminal<W> {
+    pub fn new(out: W) -> Self {
+        PlainTerminal { out }
+    }
+}
+
+impl<W: Write> Terminal for PlainTerminal<W> {
+    fn clear_previous_lines(&mut self, _n: u16) {}
+
+    fn clear_line(&mut self) {}
+
+    fn show_cursor(&mut self) {}
+
+    fn rule(&mut self, _width: usize) {}
+
+    fn centered(&mut self, text: &str, _width: usize) {
+        let _ = writeln!(self.out, "{}", text);
+    }
+
+    fn warning(&mut self, text: &str) {
+        let _ = writeln!(self.out, "Aviso: {}", text);
+    }
+
+    fn bell(&mut self) {}
+}
+
+/// Ring `term`'s bell when `enabled` (`bell = true` in the config, see
+/// `Config::bell_enabled`). Pipes and files never get the byte.
+pub fn ring_bell(term: &mut dyn Terminal, enabled: bool) {
+    if enabled {
+        term.bell();
+    }
+}
+
+/// Split `text` into lines of at most `width` characters, breaking at
+/// spaces; a single word longer than `width` is cut. Text that fits is
+/// one line, so a long banner title never runs past its `=` rules.
+pub fn wrap_title(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        let used = line.chars().count();
+        if used > 0 && used + 1 + word.len() <= width {
+            line.push(' ');
+            line.extend(&word);
+            continue;
+        }
+        if used > 0 {
+            lines.push(std::mem::take(&mut line));
+        }
+        while word.len() > width {
+            lines.push(word.drain(..width).collect());
+        }
+        line.extend(&word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The terminal for stdout: ANSI when it is a TTY, plain otherwise.
+pub fn stdout() -> Box<dyn Terminal> {
+    if std::io::stdout().is_terminal() {
+        Box::new(AnsiTerminal::new(std::io::stdout()))
+    } else {
+        Box::new(PlainTerminal::new(std::io::stdout()))
+    }
+}
+
+/// The terminal for stderr (where the spinner draws).
+pub fn stderr() -> Box<dyn Terminal> {
+    if std::io::stderr().is_terminal() {
+        Box::new(AnsiTerminal::new(std::io::stderr()))
+    } else {
+        Box::new(PlainTerminal::new(std::io::stderr()))
+    }
+}
@@ -1,7 +1,8 @@
 // UI layer
 // -------
 // This module implements the interactive command-line interface for
-// NeumoDiagnostics. It uses `dialoguer` for prompts and `indicatif`
+// NeumoDiagnostics. It asks its questions through `prompt::Prompts`
+// (dialoguer on a terminal, or a script in tests) and uses `indicatif`
 // for simple progress spinners. The UI is organized around a single
 // blocking menu loop (`main_menu`) which delegates network work to the
 // `ApiClient` in `api.rs`.
@@ -9,101 +10,612 @@
 // Important implementation notes:
 // - Network calls are performed using the blocking `reqwest::blocking`
 //   client inside `ApiClient`. To keep spinners animated on Windows
-//   (cmd.exe) and avoid blocking the main thread, the CLI spawns a
-//   short-lived background thread for each blocking call and polls the
-//   result via an `mpsc` channel while ticking the spinner on the main
-//   thread.
-// - Token persistence helpers in `ApiClient` read/write two files
-//   next to the project's `Cargo.toml`: `.neumodiag_token` (raw JWT)
-//   and `.neumodiag_token.meta` (JSON with fields like `persist` and
-//   `clean_exit`). The CLI reads the meta on startup to decide whether
-//   to auto-restore a session.
+//   (cmd.exe) and avoid blocking the main thread, each blocking call is
+//   run through the `spinner` runners (`run_with_progress`,
+//   `run_with_progress_and_deadline`), which spawn a short-lived
+//   background thread and poll the result via an `mpsc` channel while
+//   ticking the spinner on the main thread. Uploads show a percentage and
+//   give up after the request timeout plus a grace period.
+// - Token persistence helpers in `ApiClient` read/write the session
+//   state file next to the project's `Cargo.toml` (`state.rs`): the raw
+//   JWT plus meta flags like `persist` and `clean_exit`. The CLI reads
+//   the meta on startup to decide whether to auto-restore a session.
 // - All UI strings are in Spanish for this prototype and the menus are
 //   intentionally minimal and keyboard-driven (arrow keys + Enter).
 
-use crate::api::{ApiClient, RegisterRequest, AuthRequest};
+use crate::prompt::{Output, Prompter, Prompts, TerminalPrompter, TextPrompt};
+use crate::profile::{greeting_from_jwt, is_token_expired, token_expiry, token_roles, GreetingStyle, Profile, ProfilePatch, NO_PROFILE_CHANGES_MSG};
+use crate::api::{
+    check_age, check_email, check_identificacion, check_length, ApiClient, ApiError, AuthRequest, Capabilities, FieldError,
+    FieldLimits, RegisterDraft, RegisterField, RegisterRequest, RememberFor, Role, UploadFields, READ_ONLY_MSG,
+};
 use anyhow::Result;
-use dialoguer::{Input, Select, Password};
-use indicatif::{ProgressBar, ProgressStyle, ProgressDrawTarget};
-use std::path::PathBuf;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crate::audit;
+use crate::health::{health_interval_from_env, HealthMonitor, HealthStatus};
+#[cfg(feature = "heic-transcode")]
+use crate::image::{is_heic, transcode_heic_to_jpeg};
+use crate::image::ImagePolicy;
+use crate::last_error::LAST_ERROR;
+use crate::paths::StoragePaths;
+use crate::reset::{config_file, local_data_files, remove_data_files, DataFile, RESET_CONFIRMATION_WORD};
+use crate::stats::{SummaryFormat, SESSION_STATS};
+use crate::telemetry::{Telemetry, TELEMETRY_NOTICE};
+use crate::terminal::{self, Terminal};
+use crate::spinner::{run_with_progress, run_with_progress_and_deadline, Outcome};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
-use std::thread;
-use base64::engine::general_purpose::STANDARD as base64_standard;
-use base64::Engine as _;
 
 // Optional file dialog support
 use rfd::FileDialog;
 
+// `println!` / `print!` through an `Output` (see `prompt.rs`), so a
+// scripted run can read back what the menu showed.
+macro_rules! say {
+    ($out:expr) => {
+        $out.line("")
+    };
+    ($out:expr, $($arg:tt)*) => {
+        $out.line(format_args!($($arg)*))
+    };
+}
+
+macro_rules! say_text {
+    ($out:expr, $($arg:tt)*) => {
+        $out.text(format_args!($($arg)*))
+    };
+}
+
 // small helper to clear previous terminal lines; used to hide the
-// initial "Continuar/Cancelar" prompt when the user chooses to continue.
-fn clear_previous_lines(mut n: u16) {
-    use std::io::stdout;
-    use crossterm::{execute, cursor::MoveUp, terminal::{Clear, ClearType}, cursor::MoveToColumn};
-    let mut out = stdout();
-    // safety: loop a bounded number of times; ignore errors — clearing is best-effort
-    while n > 0 {
-        let _ = execute!(out, MoveUp(1), MoveToColumn(0), Clear(ClearType::CurrentLine));
-        n -= 1;
-    }
+// initial "Continuar/Cancelar" prompt when the user chooses to continue
+// (see `confirm_start`). Does nothing when stdout is not a terminal.
+fn clear_previous_lines(out: &Output, n: u16) {
+    out.terminal().clear_previous_lines(n);
 }
 
 // Shared header width used by the banner and separators so they match.
 const HEADER_WIDTH: usize = 80;
-// Minimum spinner display time in milliseconds so short operations still
-// show a visible spinner for the user.
-const MIN_SPINNER_MS: u64 = 1500;
-
-fn print_header() {
-    let width = HEADER_WIDTH;
-    let line = "=".repeat(width);
-    let title = "NeumoDiagnostics - Interfaz de línea de comandos";
-    // center title
-    let padding = if width > title.len() { (width - title.len()) / 2 } else { 0 };
-    let centered = format!("{:padding$}{}{:padding$}", "", title, "", padding = padding);
-    println!("{}", line);
-    println!("{}", centered);
-    println!("{}", line);
-}
-
-fn print_separator() {
+// How often the idle wait re-checks its deadline while polling for keys.
+const IDLE_POLL_SLICE_MS: u64 = 200;
+// Extra wait on top of the request timeout before the upload spinner
+// gives up on a background upload that never returns.
+const UPLOAD_GRACE_SECS: u64 = 5;
+
+/// Optional idle auto-exit for shared terminals, configured through
+/// `NEUMODIAG_IDLE_TIMEOUT_SECS` (seconds, disabled when unset or 0) and
+/// `NEUMODIAG_IDLE_LOGOUT` (`1`/`true` also clears the session on exit).
+struct IdleTimeout {
+    after: Duration,
+    logout: bool,
+}
+
+fn idle_timeout_from_env() -> Option<IdleTimeout> {
+    let secs: u64 = std::env::var("NEUMODIAG_IDLE_TIMEOUT_SECS").ok()?.trim().parse().ok()?;
+    if secs == 0 {
+        return None;
+    }
+    let logout = std::env::var("NEUMODIAG_IDLE_LOGOUT")
+        .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "si" | "sí"))
+        .unwrap_or(false);
+    Some(IdleTimeout { after: Duration::from_secs(secs), logout })
+}
+
+/// Clock
+///
+/// Source of the current time for the idle countdown. Tests pass a fake
+/// one that only moves when they say so.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// InputEvents
+///
+/// Where terminal input comes from: crossterm on a real terminal, a
+/// prepared queue in tests.
+pub trait InputEvents {
+    /// The next input event, waiting at most `timeout`; `None` when
+    /// nothing arrived in time.
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<Event>>;
+}
+
+/// Terminal input read through crossterm. Single key presses are only
+/// delivered while the terminal is in raw mode.
+pub struct TerminalEvents;
+
+impl InputEvents for TerminalEvents {
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<Event>> {
+        if crossterm::event::poll(timeout)? {
+            return Ok(Some(crossterm::event::read()?));
+        }
+        Ok(None)
+    }
+}
+
+/// Wait for a key press until `timeout` has passed on `clock` without
+/// one. Other events (resizes, mouse) are not activity. Returns
+/// `Ok(None)` when the window expired.
+pub fn wait_for_key(clock: &dyn Clock, events: &mut dyn InputEvents, timeout: Duration) -> Result<Option<KeyEvent>> {
+    let deadline = clock.now() + timeout;
+    loop {
+        let now = clock.now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        let slice = (deadline - now).min(Duration::from_millis(IDLE_POLL_SLICE_MS));
+        if let Some(Event::Key(key)) = events.next_event(slice)? {
+            if key.kind != KeyEventKind::Release {
+                return Ok(Some(key));
+            }
+        }
+    }
+}
+
+/// Arrow-key select over `items` that gives up when no key is pressed
+/// for `timeout`; every key press starts the window again. `draw` shows
+/// the list with the highlighted entry. Returns the chosen index, or
+/// `None` when the window expired.
+pub fn select_until_idle(
+    clock: &dyn Clock,
+    events: &mut dyn InputEvents,
+    items: &[&str],
+    timeout: Duration,
+    mut draw: impl FnMut(usize) -> Result<()>,
+) -> Result<Option<usize>> {
+    let mut current = 0;
+    loop {
+        draw(current)?;
+        let key = match wait_for_key(clock, events, timeout)? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        match key.code {
+            KeyCode::Up | KeyCode::BackTab => current = (current + items.len() - 1) % items.len(),
+            KeyCode::Down | KeyCode::Tab => current = (current + 1) % items.len(),
+            KeyCode::Enter => return Ok(Some(current)),
+            // Raw mode turns Ctrl+C into a plain key press.
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Err(std::io::Error::from(std::io::ErrorKind::Interrupted).into());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The main menu's select while the idle timeout is on. dialoguer's
+/// `Select` can't give up on its own, so the list is drawn here in raw
+/// mode and read key by key. Returns `None` after `timeout` without a key
+/// press.
+fn menu_select_until_idle(items: &[&str], timeout: Duration) -> Result<Option<usize>> {
+    use crossterm::{cursor, queue, terminal};
+    use std::io::Write;
+    let mut out = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    let _ = queue!(out, cursor::Hide);
+    let mut drawn = false;
+    let picked = select_until_idle(&SystemClock, &mut TerminalEvents, items, timeout, |current| {
+        if drawn {
+            queue!(out, cursor::MoveUp(items.len() as u16))?;
+        }
+        for (i, item) in items.iter().enumerate() {
+            let marker = if i == current { ">" } else { " " };
+            queue!(out, cursor::MoveToColumn(0), terminal::Clear(terminal::ClearType::CurrentLine))?;
+            write!(out, "{} {}\r\n", marker, item)?;
+        }
+        drawn = true;
+        Ok(out.flush()?)
+    });
+    // Like dialoguer's `Select`, leave no trace of the list.
+    if drawn {
+        let _ = queue!(out, cursor::MoveUp(items.len() as u16), terminal::Clear(terminal::ClearType::FromCursorDown));
+    }
+    let _ = queue!(out, cursor::Show);
+    let _ = out.flush();
+    let _ = terminal::disable_raw_mode();
+    picked
+}
+
+// Upper bound on events discarded in one drain, so a stream of input
+// (e.g. a held-down key) can't keep the menu from appearing.
+pub const MAX_DRAINED_EVENTS: usize = 256;
+
+/// Discard the input events already queued in `events`, without waiting
+/// for new ones. Returns how many were dropped, at most
+/// `MAX_DRAINED_EVENTS`.
+pub fn drain_input(events: &mut dyn InputEvents) -> Result<usize> {
+    let mut drained = 0;
+    while drained < MAX_DRAINED_EVENTS && events.next_event(Duration::ZERO)?.is_some() {
+        drained += 1;
+    }
+    Ok(drained)
+}
+
+/// Shown instead of dialoguer's bare "not a terminal" when a password is
+/// needed and nothing can be typed (CI, piped input).
+pub const NO_TTY_PASSWORD_MSG: &str =
+    "No hay una terminal para pedir la contraseña. Use `neumodiag-cli login --email <correo>` con NEUMODIAG_PASSWORD definido.";
+
+/// Whether hidden prompts can be shown. dialoguer refuses them unless
+/// stderr is a terminal.
+pub fn can_prompt() -> bool {
+    use std::io::IsTerminal;
+    std::io::stderr().is_terminal()
+}
+
+/// Fail with `NO_TTY_PASSWORD_MSG` when `terminal` is false; callers pass
+/// `can_prompt()`.
+pub fn require_password_terminal(terminal: bool) -> Result<()> {
+    if !terminal {
+        anyhow::bail!(NO_TTY_PASSWORD_MSG);
+    }
+    Ok(())
+}
+
+/// Ask for a password with a hidden prompt, or fail with
+/// `NO_TTY_PASSWORD_MSG` when there is no terminal.
+pub fn prompt_password(prompt: &str, terminal: bool) -> Result<String> {
+    require_password_terminal(terminal)?;
+    TerminalPrompter.password(prompt, None)
+}
+
+/// Drop keypresses typed while a sub-flow was running (e.g. Enter pressed
+/// twice) so they don't select a menu entry on their own. Best-effort:
+/// skipped when stdin is not a terminal.
+fn flush_pending_input() {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() || crossterm::terminal::enable_raw_mode().is_err() {
+        return;
+    }
+    let _ = drain_input(&mut TerminalEvents);
+    let _ = crossterm::terminal::disable_raw_mode();
+}
+
+fn print_header(out: &Output, health: Option<HealthStatus>) {
+    let mut term = out.terminal();
+    term.rule(HEADER_WIDTH);
+    let title = match health {
+        Some(status) => format!("NeumoDiagnostics - Interfaz de línea de comandos {}", status.label()),
+        None => "NeumoDiagnostics - Interfaz de línea de comandos".to_string(),
+    };
+    term.centered(&title, HEADER_WIDTH);
+    term.rule(HEADER_WIDTH);
+}
+
+fn print_separator(out: &Output) {
     // Use the same width as the header so separators align visually.
-    let sep = "=".repeat(HEADER_WIDTH);
-    println!("{}", sep);
+    out.terminal().rule(HEADER_WIDTH);
 }
 
 /// Print a titled section with a centered title and a separator line below it.
-fn print_section(title: &str) {
-    // center the title according to header width
-    let width = HEADER_WIDTH;
-    let padding = if width > title.len() { (width - title.len()) / 2 } else { 0 };
-    let centered = format!("{:padding$}{}{:padding$}", "", title, "", padding = padding);
-    println!("{}", centered);
-    print_separator();
+fn print_section(out: &Output, title: &str) {
+    let mut term = out.terminal();
+    term.centered(title, HEADER_WIDTH);
+    term.rule(HEADER_WIDTH);
+}
+
+/// UiOptions
+///
+/// Behaviour switches for the interactive flows, filled from the command
+/// line by `main.rs`.
+#[derive(Debug, Clone, Default)]
+pub struct UiOptions {
+    /// `--yes`: auto-confirm non-destructive prompts (continue, confirm
+    /// registration, remember session). Destructive confirmations must
+    /// still be asked.
+    pub assume_yes: bool,
+    /// `--summary`: how the session summary is printed on exit.
+    pub summary: SummaryFormat,
+    /// `telemetry = true` in the config: count menu actions locally.
+    pub telemetry: bool,
+    /// `--auth-debug`: show the server's reason for a failed login.
+    pub auth_debug: bool,
+    /// `--config-path`: the config file in use, when not the default one.
+    pub config_path: Option<std::path::PathBuf>,
+    /// `--force` or `confirm_destructive = false`: delete local data
+    /// without asking (see `confirm_destructive`).
+    pub force: bool,
+    /// `default_role` in the config: the role pre-selected when
+    /// registering.
+    pub default_role: Option<Role>,
+    /// `hide_role_prompt` in the config: register with `default_role`
+    /// without asking.
+    pub hide_role_prompt: bool,
+    /// `allowed_image_exts` / `max_upload_mb` in the config: the pictures
+    /// the upload offers and accepts.
+    pub images: ImagePolicy,
+    /// `NEUMODIAG_UNCLEAN_GRACE_SECS`: restore the saved session after an
+    /// unclean exit this recent (see `TokenMeta::may_auto_login`).
+    pub unclean_grace: Option<Duration>,
+    /// `greeting_style` in the config (or `NEUMODIAG_GREETING_STYLE`):
+    /// how the welcome banner addresses the user.
+    pub greeting_style: GreetingStyle,
+    /// `bell = true` in the config (and no `NEUMODIAG_NO_BELL`): ring the
+    /// terminal bell when an upload, download or registration finishes.
+    pub bell: bool,
+    /// Where answers come from: the terminal, or a script when the menu
+    /// is driven headlessly (see `prompt.rs`).
+    pub prompts: Prompts,
+    /// Where messages go: stdout, or a buffer for a scripted run.
+    pub output: Output,
+}
+
+/// Whether a destructive local action may go ahead: `true` right away
+/// under `opts.force`, otherwise the answer from `ask`. `--yes` does not
+/// count; deleting data needs `--force`.
+pub fn confirm_destructive(opts: &UiOptions, ask: impl FnOnce() -> Result<bool>) -> Result<bool> {
+    if opts.force {
+        return Ok(true);
+    }
+    ask()
+}
+
+/// Log out: forget the token and delete the saved session, asking first
+/// (see `confirm_destructive`) when there is one. Returns whether a saved
+/// session is left behind, which the next run will restore.
+pub fn logout(api: &mut ApiClient, opts: &UiOptions, ask: impl FnOnce() -> Result<bool>) -> Result<bool> {
+    api.clear_token();
+    let saved = api.load_token_from_project().ok().flatten().is_some();
+    if saved && !confirm_destructive(opts, ask)? {
+        return Ok(true);
+    }
+    api.clear_persisted_token_in_project();
+    Ok(false)
+}
+
+/// Answer a non-destructive question: `default` right away under
+/// `--yes`, otherwise whatever `ask` returns. `default` is the choice the
+/// prompt itself preselects, so `--yes` never picks more than Enter would.
+pub fn confirm_routine(opts: &UiOptions, default: bool, ask: impl FnOnce() -> Result<bool>) -> Result<bool> {
+    if opts.assume_yes {
+        return Ok(default);
+    }
+    ask()
+}
+
+/// Ask the "Continuar/Cancelar" question that opens a flow. Returns `true`
+/// to continue. Skipped (continuing) under `--yes`; otherwise the
+/// selector is cleared after continuing so the form appears cleanly.
+fn confirm_start(opts: &UiOptions, prompt: &str) -> Result<bool> {
+    confirm_routine(opts, true, || {
+        if opts.prompts.select(Some(prompt), &["Continuar", "Cancelar"], 0)? == 1 {
+            return Ok(false);
+        }
+        clear_previous_lines(&opts.output, 1);
+        Ok(true)
+    })
+}
+
+/// Print an error and remember it (with the gateway's correlation id for
+/// the last request, and the status and body when `err` is an
+/// `ApiError`) for "Copiar último error".
+fn show_error(out: &Output, api: &ApiClient, message: String, err: &anyhow::Error) {
+    say!(out, "{}", message);
+    SESSION_STATS.record_error();
+    LAST_ERROR.record(message, Some(err), api.last_correlation_id());
+}
+
+/// Text of `err` for an error message: the top-level error, and with
+/// `verbose` (`--verbose`) every underlying cause on its own line, down
+/// to the reqwest/serde/I/O error, so bug reports carry the root cause.
+pub fn error_message(err: &anyhow::Error, verbose: bool) -> String {
+    let mut text = err.to_string();
+    if verbose {
+        for cause in err.chain().skip(1) {
+            text.push_str(&format!("\n  causa: {}", cause));
+        }
+    }
+    text
+}
+
+/// Append a session event for "Historial de sesión", tagged with the
+/// user id and e-mail from the token. The history is a convenience, so a
+/// log that can't be written is not reported.
+fn record_event(api: &ApiClient, action: &str) {
+    let profile = api.token().and_then(Profile::from_jwt).unwrap_or_default();
+    let details = serde_json::json!({"user_id": profile.user_id, "correo": profile.correo});
+    let _ = audit::record(api, action, details);
+}
+
+/// Page through the audit log's events, newest first, with identifiers
+/// masked (see `audit::AuditEvent::describe`).
+fn show_session_history(api: &ApiClient, opts: &UiOptions) -> Result<()> {
+    let events = match audit::read_events(api) {
+        Ok(events) => events,
+        Err(e) => {
+            show_error(&opts.output, api, format!("No se pudo leer el historial: {}", error_message(&e, api.is_verbose())), &e);
+            return Ok(());
+        }
+    };
+    if events.is_empty() {
+        say!(opts.output, "No hay eventos registrados en este equipo.");
+        return Ok(());
+    }
+    let pages = events.len().div_ceil(audit::HISTORY_PAGE_SIZE);
+    let mut page = 0;
+    loop {
+        for event in events.iter().skip(page * audit::HISTORY_PAGE_SIZE).take(audit::HISTORY_PAGE_SIZE) {
+            say!(opts.output, "{}", event.describe());
+        }
+        if pages == 1 {
+            return Ok(());
+        }
+        say!(opts.output, "Página {} de {}", page + 1, pages);
+        let mut choices = Vec::new();
+        if page + 1 < pages {
+            choices.push("Página siguiente");
+        }
+        if page > 0 {
+            choices.push("Página anterior");
+        }
+        choices.push("Volver al menú");
+        match choices[opts.prompts.select(None, &choices, 0)?] {
+            "Página siguiente" => page += 1,
+            "Página anterior" => page -= 1,
+            _ => return Ok(()),
+        }
+        print_separator(&opts.output);
+    }
+}
+
+/// Print the session summary in the `--summary` format, if any.
+fn print_session_summary(opts: &UiOptions) {
+    if let Some(summary) = SESSION_STATS.snapshot().render(opts.summary) {
+        say!(opts.output, "{}", summary);
+    }
+}
+
+/// MenuAction
+///
+/// What the main menu does for the picked entry. Decided by
+/// `next_action` so the choice can be tested without a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    Register,
+    Login,
+    Logout,
+    VerifySession,
+    RefreshSession,
+    ViewProfile,
+    UploadPicture,
+    DownloadPicture,
+    RevokeConsent,
+    CopyLastError,
+    SessionHistory,
+    ResetLocalData,
+    Exit,
+    /// A session-only entry was picked without a session.
+    LoginRequired,
+    /// Out-of-range selection or unknown entry; the menu is shown again.
+    Ignore,
+}
+
+impl MenuAction {
+    /// Whether the action needs a logged-in session.
+    pub fn requires_login(&self) -> bool {
+        matches!(
+            self,
+            MenuAction::Logout
+                | MenuAction::VerifySession
+                | MenuAction::RefreshSession
+                | MenuAction::ViewProfile
+                | MenuAction::UploadPicture
+                | MenuAction::DownloadPicture
+                | MenuAction::RevokeConsent
+        )
+    }
+}
+
+/// The action for entry `selection` of `items` (as built by
+/// `menu_items`), given whether a session is active.
+pub fn next_action(items: &[&str], selection: usize, is_logged: bool) -> MenuAction {
+    let action = match items.get(selection).copied() {
+        Some("Registrarse") => MenuAction::Register,
+        Some("Iniciar sesión") => MenuAction::Login,
+        Some("Cerrar sesión") => MenuAction::Logout,
+        Some("Verificar sesión") => MenuAction::VerifySession,
+        Some("Renovar sesión") => MenuAction::RefreshSession,
+        Some("Ver perfil") => MenuAction::ViewProfile,
+        Some("Subir foto de perfil") => MenuAction::UploadPicture,
+        Some("Descargar mi foto") => MenuAction::DownloadPicture,
+        Some("Revocar consentimiento") => MenuAction::RevokeConsent,
+        Some("Copiar último error") => MenuAction::CopyLastError,
+        Some("Historial de sesión") => MenuAction::SessionHistory,
+        Some("Borrar datos locales") => MenuAction::ResetLocalData,
+        Some("Salir") => MenuAction::Exit,
+        _ => MenuAction::Ignore,
+    };
+    if action.requires_login() && !is_logged {
+        return MenuAction::LoginRequired;
+    }
+    action
+}
+
+/// Build the main menu entries for the current session. Optional
+/// entries are hidden when the backend's capabilities do not list them;
+/// `None` (discovery failed or unsupported) shows every entry.
+/// "Copiar último error" appears once an error has been shown;
+/// "Historial de sesión" is always there, since it only reads local data.
+/// With `read_only` (`--read-only`) entries that change server data are
+/// hidden.
+pub fn menu_items(is_logged: bool, caps: Option<&Capabilities>, has_error: bool, read_only: bool) -> Vec<&'static str> {
+    let supports = |feature: &str| caps.map(|c| c.supports(feature)).unwrap_or(true);
+    let writes = |feature: &str| !read_only && supports(feature);
+    let mut items = Vec::new();
+    if is_logged {
+        items.push("Ver perfil");
+        if writes("upload") {
+            items.push("Subir foto de perfil");
+        }
+        if supports("download") {
+            items.push("Descargar mi foto");
+        }
+        if supports("verify") {
+            items.push("Verificar sesión");
+        }
+        if supports("refresh") {
+            items.push("Renovar sesión");
+        }
+        if writes("consent") {
+            items.push("Revocar consentimiento");
+        }
+        items.push("Cerrar sesión");
+    } else {
+        if writes("register") {
+            items.push("Registrarse");
+        }
+        items.push("Iniciar sesión");
+    }
+    if has_error {
+        items.push("Copiar último error");
+    }
+    items.push("Historial de sesión");
+    items.push("Borrar datos locales");
+    items.push("Salir");
+    items
+}
+
+// Current time in unix seconds.
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
 }
 
 /// Main interactive menu. Receives an `ApiClient` instance and runs a
 /// simple select loop until the user chooses "Exit".
 ///
-/// Note: `Select::interact()` is keyboard-driven: you can use arrow keys
-/// and Enter to choose an option.
-pub fn main_menu(mut api: ApiClient) -> Result<()> {
-    // Attempt auto-login only when a persisted token exists and the
-    // token meta indicates the previous session exited cleanly.
+/// Note: on a terminal the menus are keyboard-driven: you can use arrow
+/// keys and Enter to choose an option. With a scripted `opts.prompts` the
+/// whole loop runs headlessly.
+pub fn main_menu(mut api: ApiClient, opts: UiOptions) -> Result<()> {
+    // Attempt auto-login only when a persisted token exists, the token
+    // meta indicates the previous session exited cleanly (or uncleanly
+    // within `unclean_grace`, with a token that hasn't expired) and the
+    // remember window chosen at login hasn't ended.
     if let Ok(Some(meta)) = api.load_token_meta() {
-        // meta example: {"persist": true, "clean_exit": true}
-        if meta.get("clean_exit").and_then(|v| v.as_bool()).unwrap_or(false) {
-            if let Ok(Some(t)) = api.load_token_from_project() {
+        let now = now_secs();
+        if !meta.within_remember_window(now) {
+            api.clear_persisted_token_in_project();
+            say!(opts.output, "La sesión recordada caducó; inicie sesión nuevamente.");
+        } else if meta.may_auto_login(now, opts.unclean_grace) {
+            let token = api.load_token_from_project().ok().flatten();
+            let usable = |t: &String| meta.clean_exit || is_token_expired(t.trim()) != Some(true);
+            if let Some(t) = token.filter(usable) {
                 let tok = t.trim().to_string();
                 api.set_token(&tok);
-                // Try to decode token payload and extract nombre_completo for nicer message
-                println!();
-                print_separator();
-                if let Some(name) = extract_name_from_jwt(&tok) {
-                    let title = format!("Bienvenido de vuelta: {}", name);
-                    print_section(&title);
-                } else {
-                    print_section("Sesión restaurada automáticamente desde la sesión guardada.");
-                }
+                say!(opts.output);
+                print_separator(&opts.output);
+                print_section(&opts.output, &format!("Bienvenido de vuelta: {}", greeting_from_jwt(&tok, opts.greeting_style)));
+                choose_active_role(&mut api, &tok, &opts)?;
             }
         }
     }
@@ -113,225 +625,523 @@ pub fn main_menu(mut api: ApiClient) -> Result<()> {
     // leaves clean_exit=false so the next run will not auto-login.
     let _ = api.set_clean_exit_meta(false);
 
+    // Opt-in local usage counts; never in memory-only mode, which must
+    // not write anything.
+    let telemetry = match api.state_dir() {
+        Ok(dir) if !api.is_ephemeral() => Telemetry::new(opts.telemetry, &dir),
+        _ => Telemetry::new(false, Path::new(".")),
+    };
+    if telemetry.first_run_notice().unwrap_or(false) {
+        say!(opts.output, "{}", TELEMETRY_NOTICE);
+    }
+
+    if api.is_read_only() {
+        say!(opts.output, "Modo solo lectura: registrarse, subir fotos y revocar el consentimiento están deshabilitados.");
+    }
+
+    let idle = idle_timeout_from_env();
+    if let Some(idle) = &idle {
+        say!(opts.output, "La aplicación se cerrará tras {} s sin actividad en el menú.", idle.after.as_secs());
+    }
+    // Connection indicator for the header, refreshed in the background.
+    let health = health_interval_from_env().map(|every| HealthMonitor::start(&api, every));
+    // Discover optional backend features once per session; on failure the
+    // menu falls back to showing every option.
+    let caps = api.get_capabilities().ok();
+    // Fields of a cancelled registration, offered again next time.
+    let mut register_draft = RegisterDraft::default();
+
     loop {
-        print_header();
+        // Keeps a saved session restorable for `unclean_grace` if this
+        // run crashes.
+        let _ = api.touch_last_seen(now_secs());
+        print_header(&opts.output, health.as_ref().map(HealthMonitor::status));
         // Build menu items; show upload only when a token is present.
-        let mut items = Vec::new();
-        let is_logged = api.has_token();
-        if is_logged {
-            items.push("Subir foto de perfil");
-            items.push("Cerrar sesión");
-        } else {
-            items.push("Registrarse");
-            items.push("Iniciar sesión");
-        }
-        items.push("Salir");
+        let items = menu_items(api.has_token(), caps.as_ref(), LAST_ERROR.last().is_some(), api.is_read_only());
 
-        let selection = Select::new().items(&items).default(0).interact()?;
+        // Stray keypresses from the previous flow must not pick an entry.
+        if opts.prompts.is_interactive() {
+            flush_pending_input();
+        }
+        let selection = match &idle {
+            // The idle countdown watches the keyboard, so a scripted run
+            // (see `prompt.rs`) skips it.
+            Some(idle) if opts.prompts.is_interactive() => match menu_select_until_idle(&items, idle.after)? {
+                Some(selection) => selection,
+                None => {
+                    say!(opts.output, "Sin actividad: cerrando la aplicación.");
+                    print_session_summary(&opts);
+                    if idle.logout {
+                        api.clear_token();
+                        api.clear_persisted_token_in_project();
+                        say!(opts.output, "Sesión cerrada por inactividad.");
+                    } else {
+                        // An idle exit is orderly, so a remembered session may
+                        // still be restored on the next run.
+                        let _ = api.set_clean_exit_meta(true);
+                    }
+                    break;
+                }
+            },
+            _ => opts.prompts.select(None, &items, 0)?,
+        };
         let choice = items[selection];
+        crate::crash::set_operation(choice);
+        telemetry.record(choice);
 
-        match choice {
-            "Registrarse" => {
+        match next_action(&items, selection, api.has_token()) {
+            MenuAction::Register => {
                 // Show a titled section for registration
-                print_section("NeumoDiagnostics - Registro");
+                print_section(&opts.output, "NeumoDiagnostics - Registro");
                 // Allow user to cancel registration and return to the main menu
-                if let Err(e) = handle_register(&api) {
+                if let Err(e) = handle_register(&api, caps.as_ref(), &opts, &mut register_draft) {
                     // If the handler returned an error, surface it; otherwise continue
-                    println!("Error en el flujo de registro: {}", e);
+                    show_error(&opts.output, &api, format!("Error en el flujo de registro: {}", error_message(&e, api.is_verbose())), &e);
                 }
-                print_separator();
+                print_separator(&opts.output);
             }
-            "Iniciar sesión" => {
+            MenuAction::Login => {
                 // Show a titled section for login
-                print_section("NeumoDiagnostics - Iniciar sesión");
-                // handle_login returns Ok(Some(token)) on success, Ok(None) when cancelled or failed
-                if let Some(token) = handle_login(&api)? {
-                    api.set_token(&token);
-                    // Preguntar si se recuerda la sesión (Sí/No en español)
-                    let remember_idx = Select::new()
-                        .with_prompt("¿Recordar esta sesión en este equipo?")
-                        .items(&["Sí", "No"]) 
-                        .default(1)
-                        .interact()?;
-                    let remember = remember_idx == 0;
-                    if remember {
-                        api.persist_token_to_project(&token, true)?;
-                    } else {
-                        api.persist_token_to_project(&token, false)?;
-                    }
-                    println!("Sesión iniciada.");
+                print_section(&opts.output, "NeumoDiagnostics - Iniciar sesión");
+                login_and_remember(&mut api, &opts)?;
+            }
+            MenuAction::Logout => {
+                register_draft = RegisterDraft::default();
+                // The saved session is deleted too (once confirmed) so the
+                // next run will not restore it.
+                let ask = || {
+                    let idx = opts.prompts.select(Some("¿Borrar también la sesión guardada en este equipo?"), &["Sí", "No"], 0)?;
+                    Ok(idx == 0)
+                };
+                record_event(&api, "logout");
+                if logout(&mut api, &opts, ask)? {
+                    say!(opts.output, "Sesión cerrada. La sesión guardada se conserva y se restaurará al volver a abrir la aplicación.");
+                } else {
+                    say!(opts.output, "Sesión cerrada.");
                 }
             }
-            "Cerrar sesión" => {
-                api.clear_token();
-                // Always clear persisted token on explicit logout so the next run will not restore.
-                api.clear_persisted_token_in_project();
-                println!("Sesión cerrada.");
+            MenuAction::VerifySession => {
+                print_section(&opts.output, "NeumoDiagnostics - Verificar sesión");
+                verify_session(&opts.output, &api);
             }
-            "Subir foto de perfil" => {
-                // Show a titled section for uploading
-                print_section("NeumoDiagnostics - Subir foto de perfil");
-                if !api.has_token() {
-                    println!("Debe iniciar sesión antes de subir una foto de perfil.");
-                    continue;
-                }
-
-                // Provide an explicit cancel option so the user can return to the menu
-                let pick_methods = vec!["Seleccionar archivo (GUI)", "Ingresar ruta manualmente", "Cancelar"];
-                let pick = pick_methods[Select::new().items(&pick_methods).default(0).interact()?];
-
-                if pick == "Cancelar" {
-                    println!("Operación cancelada. Volviendo al menú.");
-                    continue;
+            MenuAction::RefreshSession => {
+                print_section(&opts.output, "NeumoDiagnostics - Renovar sesión");
+                refresh_session(&opts.output, &mut api);
+            }
+            MenuAction::ViewProfile => {
+                let (profile, err) = current_profile(&api);
+                print_profile(&opts.output, &profile);
+                if let Some(e) = err {
+                    say!(opts.output, "(Datos del token; no se pudo consultar el servidor: {})", e);
                 }
-
-                let pb_opt: Option<PathBuf> = if pick == "Seleccionar archivo (GUI)" {
-                    match FileDialog::new().add_filter("Imagen", &["jpg", "jpeg", "png"]).pick_file() {
-                        Some(p) => Some(p),
-                        None => {
-                            println!("No se seleccionó un archivo o el diálogo no está disponible.");
-                            None
-                        }
-                    }
-                } else {
-                    let raw_path: String = Input::new().with_prompt("Ruta del archivo de imagen").interact_text()?;
-                    let trimmed = raw_path.trim();
-                    if trimmed.is_empty() {
-                        println!("Ruta vacía: operación cancelada.");
-                        None
-                    } else {
-                        let path = trimmed.trim_matches('"').trim_matches('\'').to_string();
-                        Some(PathBuf::from(path))
-                    }
-                };
-
-                if pb_opt.is_none() {
-                    continue;
+            }
+            MenuAction::UploadPicture => handle_upload(&api, caps.as_ref(), &opts)?,
+            MenuAction::DownloadPicture => handle_download(&api, &opts)?,
+            MenuAction::LoginRequired => {
+                say!(opts.output, "Debe iniciar sesión antes de usar esta opción.");
+                continue;
+            }
+            MenuAction::CopyLastError => {
+                print_section(&opts.output, "NeumoDiagnostics - Último error");
+                if let Some(last) = LAST_ERROR.last() {
+                    say!(opts.output, "Copie el siguiente texto en su solicitud de soporte:");
+                    print_separator(&opts.output);
+                    say_text!(opts.output, "{}", last.to_report());
+                    print_separator(&opts.output);
                 }
-                let pb = pb_opt.unwrap();
-
-                use std::sync::mpsc::{channel, TryRecvError};
-                let spinner = ProgressBar::new_spinner();
-                spinner.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap());
-                spinner.set_draw_target(ProgressDrawTarget::stderr());
-                spinner.set_message("Subiendo la imagen...");
-
-                // Run the blocking upload in a background thread and poll for the result
-                let (tx, rx) = channel();
-                let api_cloned = api.clone();
-                let pb_clone = pb.clone();
-                std::thread::spawn(move || {
-                    let r = api_cloned.upload_profile_picture(&pb_clone);
-                    let _ = tx.send(r);
-                });
-
-                // Poll for the result while ticking the spinner and ensure minimum display time
-                let start = Instant::now();
-                loop {
-                    match rx.try_recv() {
-                        Ok(res) => {
-                            // if result arrived too quickly, keep spinning until min time
-                            while start.elapsed().as_millis() < MIN_SPINNER_MS as u128 {
-                                spinner.tick();
-                                thread::sleep(Duration::from_millis(80));
-                            }
-                            spinner.finish_and_clear();
-                            match res {
-                                Ok(_) => println!("Imagen de perfil cargada exitosamente."),
-                                Err(e) => println!("Fallo la subida: {}", e),
-                            }
-                            break;
-                        }
-                        Err(TryRecvError::Empty) => {
-                            spinner.tick();
-                            thread::sleep(Duration::from_millis(80));
-                        }
-                        Err(_) => {
-                            spinner.finish_and_clear();
-                            println!("Fallo interno: no se pudo obtener el resultado de la subida.");
-                            break;
-                        }
-                    }
+            }
+            MenuAction::SessionHistory => {
+                print_section(&opts.output, "NeumoDiagnostics - Historial de sesión");
+                show_session_history(&api, &opts)?;
+            }
+            MenuAction::RevokeConsent => {
+                print_section(&opts.output, "NeumoDiagnostics - Revocar consentimiento");
+                handle_revoke_consent(&api, &opts)?;
+            }
+            MenuAction::ResetLocalData => {
+                print_section(&opts.output, "NeumoDiagnostics - Borrar datos locales");
+                if reset_local_data(&mut api, &opts)? {
+                    // Leave right away: staying would write the session
+                    // metadata again on exit.
+                    print_session_summary(&opts);
+                    say!(opts.output, "Saliendo...");
+                    break;
                 }
             }
-            "Salir" => {
+            MenuAction::Exit => {
                 let _ = api.set_clean_exit_meta(true);
-                println!("Saliendo...");
+                print_session_summary(&opts);
+                say!(opts.output, "Saliendo...");
                 break
             }
-            _ => {}
+            MenuAction::Ignore => {}
         }
-        println!("");
+        say!(opts.output);
     }
     Ok(())
 }
 
-/// Collect input fields for registration and call `ApiClient::register`.
-fn handle_register(api: &ApiClient) -> Result<()> {
-    // Allow immediate cancel of the registration flow
-    let start_idx = Select::new()
-        .with_prompt("¿Desea continuar con el registro o cancelar?")
-        .items(&["Continuar", "Cancelar"]) 
-        .default(0)
-        .interact()?;
-    if start_idx == 1 {
-        println!("Registro cancelado. Volviendo al menú.");
+/// Log in through `handle_login` and, on success, ask how long to
+/// remember the session and save it accordingly. If saving fails the
+/// session stays in memory and the user is warned.
+fn login_and_remember(api: &mut ApiClient, opts: &UiOptions) -> Result<()> {
+    // handle_login returns Ok(Some(token)) on success, Ok(None) when cancelled or failed
+    if let Some(token) = handle_login(api, opts)? {
+        SESSION_STATS.record_login();
+        api.set_token(&token);
+        record_event(api, "login");
+        choose_active_role(api, &token, opts)?;
+        // Preguntar por cuánto tiempo se recuerda la sesión;
+        // `--yes` takes the preselected "No". In `--ephemeral`
+        // mode nothing is saved, so don't ask.
+        let remember = if api.is_ephemeral() {
+            RememberFor::Always
+        } else if opts.assume_yes {
+            RememberFor::No
+        } else {
+            let labels: Vec<String> = RememberFor::CHOICES.iter().map(|c| c.label()).collect();
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+            let picked =
+                opts.prompts.select(Some("¿Recordar esta sesión en este equipo?"), &labels, RememberFor::CHOICES.len() - 1)?;
+            RememberFor::CHOICES[picked]
+        };
+        // A session that can't be written to disk (read-only folder, full
+        // disk) still works for this run; losing it over that would throw
+        // away a successful login.
+        if let Err(e) = api.persist_token_with_meta(&token, remember.meta(now_secs())) {
+            say!(opts.output, 
+                "Aviso: no se pudo guardar la sesión en este equipo ({:#}); seguirá activa hasta cerrar la aplicación.",
+                e
+            );
+        }
+        say!(opts.output, "Sesión iniciada.");
+    }
+    Ok(())
+}
+
+/// For tokens carrying several roles, ask which one to act as (the
+/// first with `--yes`); requests then carry it as `X-Active-Role`.
+fn choose_active_role(api: &mut ApiClient, token: &str, opts: &UiOptions) -> Result<()> {
+    // Only a role the client knows can be sent back.
+    let roles: Vec<Role> = token_roles(token).into_iter().filter(|r| *r != Role::Unknown).collect();
+    if roles.len() < 2 {
+        api.set_active_role(None);
         return Ok(());
     }
-    // If the user chose to continue, clean up the prompt lines so the
-    // terminal doesn't keep showing the temporary selector. 6 lines is
-    // a conservative clearance for the prompt + selector display.
-    clear_previous_lines(1);
-
-    // `Input::interact_text()` prompts the user for input and returns it.
-    let nombre: String = Input::new().with_prompt("Nombre completo").interact_text()?;
-    let edad: i32 = Input::new().with_prompt("Edad").interact_text()?;
-    // Show role choices with capitalized first letter
-    let rol_choices = vec!["Doctor", "Paciente"];
-    let rol_idx = Select::new().with_prompt("Rol").items(&rol_choices).default(1).interact()?;
-    let rol = rol_choices[rol_idx].to_lowercase();
-    let identificacion: String = Input::new().with_prompt("Identificación").interact_text()?;
-    let correo: String = Input::new().with_prompt("Correo electrónico").interact_text()?;
-    // `Password` hides input in terminal for passwords. Request confirmation.
-    // If the passwords don't match, allow the user to retry entering only
-    // the passwords or cancel the registration — do not force restarting
-    // the whole form.
-    let contrasena: String = loop {
-        let p = Password::new().with_prompt("Contraseña").interact()?;
-        let pc = Password::new().with_prompt("Confirmar contraseña").interact()?;
+    let picked = if opts.assume_yes {
+        0
+    } else {
+        let labels: Vec<&str> = roles.iter().map(Role::as_str).collect();
+        opts.prompts.select(Some("¿Con qué rol desea continuar?"), &labels, 0)?
+    };
+    api.set_active_role(Some(roles[picked].as_str()));
+    say!(opts.output, "Rol activo: {}", roles[picked]);
+    Ok(())
+}
+
+/// Pick an image (file dialog or typed path) and upload it as the
+/// profile picture, showing progress while it is sent.
+fn handle_upload(api: &ApiClient, caps: Option<&Capabilities>, opts: &UiOptions) -> Result<()> {
+    // Show a titled section for uploading
+    print_section(&opts.output, "NeumoDiagnostics - Subir foto de perfil");
+    if !api.has_token() {
+        say!(opts.output, "Debe iniciar sesión antes de subir una foto de perfil.");
+        return Ok(());
+    }
+    if api.is_read_only() {
+        say!(opts.output, "{}", READ_ONLY_MSG);
+        return Ok(());
+    }
+
+    // Provide an explicit cancel option so the user can return to the menu
+    let pick_methods = vec!["Seleccionar archivo (GUI)", "Ingresar ruta manualmente", "Cancelar"];
+    let pick = pick_methods[opts.prompts.select(None, &pick_methods, 0)?];
+
+    if pick == "Cancelar" {
+        say!(opts.output, "Operación cancelada. Volviendo al menú.");
+        return Ok(());
+    }
+
+    let pb_opt: Option<PathBuf> = if pick == "Seleccionar archivo (GUI)" {
+        match FileDialog::new().add_filter("Imagen", &opts.images.dialog_extensions()).pick_file() {
+            Some(p) => Some(p),
+            None => {
+                say!(opts.output, "No se seleccionó un archivo o el diálogo no está disponible.");
+                None
+            }
+        }
+    } else {
+        let raw_path = opts.prompts.input(TextPrompt::new("Ruta del archivo de imagen"))?;
+        let trimmed = raw_path.trim();
+        if trimmed.is_empty() {
+            say!(opts.output, "Ruta vacía: operación cancelada.");
+            None
+        } else {
+            let path = trimmed.trim_matches('"').trim_matches('\'').to_string();
+            Some(PathBuf::from(path))
+        }
+    };
+
+    if pb_opt.is_none() {
+        return Ok(());
+    }
+    let pb = pb_opt.unwrap();
+    // Check the file's content, not just its extension, and its size.
+    if let Err(e) = opts.images.check_file(&pb) {
+        say!(opts.output, "{}", e);
+        return Ok(());
+    }
+    // The converted JPEG is removed when `converted` is dropped, which the
+    // upload worker does once it is done with the file.
+    #[cfg(feature = "heic-transcode")]
+    let (pb, converted) = if is_heic(&pb) {
+        say!(opts.output, "Convirtiendo la foto HEIC a JPEG...");
+        match transcode_heic_to_jpeg(&pb) {
+            Ok(jpeg) => (jpeg.path().to_path_buf(), Some(jpeg)),
+            Err(e) => {
+                show_error(&opts.output, api, format!("No se pudo convertir la imagen: {}", error_message(&e, api.is_verbose())), &e);
+                return Ok(());
+            }
+        }
+    } else {
+        (pb, None)
+    };
+
+    // Extra form fields the backend advertised; empty answers are not sent.
+    let mut fields = UploadFields::new();
+    for name in caps.map(|c| c.upload_fields.as_slice()).unwrap_or_default() {
+        let prompt = format!("{} (opcional)", name);
+        let value = opts.prompts.input(TextPrompt::new(&prompt).with_allow_empty(true))?;
+        if let Err(e) = fields.insert(name, &value) {
+            say!(opts.output, "Se omite el campo '{}': {}", name, e);
+        }
+    }
+
+    // Run the blocking upload in a background thread while the
+    // spinner ticks. The request times out on its own; the
+    // grace period covers a worker that still doesn't return.
+    let api_cloned = api.clone();
+    let limit = api.timeout() + Duration::from_secs(UPLOAD_GRACE_SECS);
+    let outcome = run_with_progress_and_deadline("Subiendo la imagen...", limit, move |progress, abandoned| {
+        #[cfg(feature = "heic-transcode")]
+        let _converted = converted;
+        // Update the spinner only when the percentage changes. A signed
+        // upload is buffered before sending, so its percentage would jump
+        // straight to 100%; it keeps the plain spinner instead.
+        let mut last_pct = None;
+        let api = api_cloned.with_abandon_flag(abandoned).with_upload_fields(fields);
+        let show_pct = !api.signs_requests();
+        api.upload_profile_picture_with_progress(&pb, move |sent, total| {
+            let pct = (sent * 100).checked_div(total).unwrap_or(100);
+            if show_pct && last_pct != Some(pct) {
+                last_pct = Some(pct);
+                let _ = progress.send(format!("Subiendo la imagen... {}%", pct));
+            }
+        })
+    });
+    // Done either way: flag it for a user who switched windows.
+    terminal::ring_bell(&mut *opts.output.terminal(), opts.bell);
+    SESSION_STATS.record_upload(matches!(outcome, Outcome::Finished(Ok(_))));
+    match outcome {
+        Outcome::Finished(Ok(resp)) => {
+            record_event(api, "upload");
+            say!(opts.output, "Imagen de perfil cargada exitosamente.");
+            print_warnings(&mut *opts.output.terminal(), &resp.warnings());
+        }
+        Outcome::Finished(Err(e)) => show_error(&opts.output, api, format!("Fallo la subida: {}", error_message(&e, api.is_verbose())), &e),
+        Outcome::Died => say!(opts.output, "Fallo interno: no se pudo obtener el resultado de la subida."),
+        Outcome::TimedOut => say!(opts.output, 
+            "La subida no respondió en {} s; se dejó de esperar. Intente de nuevo más tarde.",
+            limit.as_secs()
+        ),
+    }
+    Ok(())
+}
+
+/// Ask where to save the profile picture and download it with a
+/// progress spinner. An interrupted download continues on the next try.
+fn handle_download(api: &ApiClient, opts: &UiOptions) -> Result<()> {
+    print_section(&opts.output, "NeumoDiagnostics - Descargar mi foto");
+    let raw = opts.prompts.input(
+        TextPrompt::new("Guardar como (sin extensión se usa la del formato de la imagen)").with_default("foto_perfil"),
+    )?;
+    let dest = PathBuf::from(raw.trim().trim_matches('"').trim_matches('\''));
+
+    // Replacing a file is destructive: ask first, unless `--force`.
+    let existing = existing_picture_paths(&dest);
+    let overwrite = !existing.is_empty();
+    for path in &existing {
+        let replace = confirm_destructive(opts, || {
+            let prompt = format!("Ya existe {}. ¿Reemplazarlo?", path.display());
+            Ok(opts.prompts.select(Some(&prompt), &["No", "Sí"], 0)? == 1)
+        })?;
+        if !replace {
+            say!(opts.output, "Descarga cancelada; no se reemplazó {}.", path.display());
+            return Ok(());
+        }
+    }
+
+    let api_cloned = api.clone();
+    let outcome = run_with_progress("Descargando la foto...", move |progress| {
+        let mut last = None;
+        api_cloned.download_profile_picture_with_progress(&dest, overwrite, move |received, total| {
+            let text = match (received * 100).checked_div(total) {
+                Some(pct) => format!("Descargando la foto... {}%", pct),
+                None => format!("Descargando la foto... {} KB", received / 1024),
+            };
+            if last.as_ref() != Some(&text) {
+                let _ = progress.send(text.clone());
+                last = Some(text);
+            }
+        })
+    });
+    terminal::ring_bell(&mut *opts.output.terminal(), opts.bell);
+    match outcome {
+        Some(Ok(path)) => say!(opts.output, "Foto guardada en {}", path.display()),
+        Some(Err(e)) => show_error(&opts.output, api, format!("Fallo la descarga: {}", error_message(&e, api.is_verbose())), &e),
+        None => say!(opts.output, "Fallo interno: no se pudo obtener el resultado de la descarga."),
+    }
+    Ok(())
+}
+
+// Files a download to `dest` may replace: `dest` itself, or with no
+// extension every name the server's Content-Type could give it.
+fn existing_picture_paths(dest: &Path) -> Vec<PathBuf> {
+    let candidates: Vec<PathBuf> = match dest.extension() {
+        Some(_) => vec![dest.to_path_buf()],
+        None => crate::image::IMAGE_TYPES
+            .iter()
+            .filter_map(|(_, mime)| crate::image::extension_for_mime(mime))
+            .map(|ext| dest.with_extension(ext))
+            .collect(),
+    };
+    let mut existing: Vec<PathBuf> = candidates.into_iter().filter(|p| p.exists()).collect();
+    existing.dedup();
+    existing
+}
+
+/// Validator for the age field: a whole number accepted by `check_age`.
+fn check_age_text(value: &str) -> std::result::Result<(), String> {
+    let edad: i32 = value.trim().parse().map_err(|_| "Ingrese la edad en números".to_string())?;
+    check_age(edad)
+}
+
+/// Ask for the registration password twice until both match. `None` when
+/// the user gives up after a mismatch.
+fn ask_new_password(out: &Output, prompts: &Prompts, limits: &FieldLimits) -> Result<Option<String>> {
+    let label = limits.prompt(RegisterField::Contrasena);
+    loop {
+        let p = prompts.password(&label, Some(&|v: &str| limits.check_password(v)))?;
+        let pc = prompts.password("Confirmar contraseña", None)?;
         if p == pc {
-            break p;
-        }
-        println!("Las contraseñas no coinciden.");
-        let retry = Select::new()
-            .with_prompt("¿Desea reintentar la contraseña o cancelar el registro?")
-            .items(&["Reintentar", "Cancelar"]) 
-            .default(0)
-            .interact()?;
+            return Ok(Some(p));
+        }
+        say!(out, "Las contraseñas no coinciden.");
+        let retry = prompts.select(
+            Some("¿Desea reintentar la contraseña o cancelar el registro?"),
+            &["Reintentar", "Cancelar"],
+            0,
+        )?;
         if retry == 1 {
-            println!("Registro cancelado. Volviendo al menú.");
+            return Ok(None);
+        }
+    }
+}
+
+/// Collect input fields for registration and call `ApiClient::register`.
+/// Non-secret fields are recorded in `draft` as they are entered, so if
+/// the registration is cancelled they pre-fill the next attempt; the
+/// draft is cleared once registration succeeds. Field rules advertised in
+/// `caps` are shown in the prompts and checked before sending.
+fn handle_register(api: &ApiClient, caps: Option<&Capabilities>, opts: &UiOptions, draft: &mut RegisterDraft) -> Result<()> {
+    if api.is_read_only() {
+        say!(opts.output, "{}", READ_ONLY_MSG);
+        return Ok(());
+    }
+    // Allow immediate cancel of the registration flow
+    if !confirm_start(opts, "¿Desea continuar con el registro o cancelar?")? {
+        say!(opts.output, "Registro cancelado. Volviendo al menú.");
+        return Ok(());
+    }
+
+    // Length caps and password policy, from the backend when it
+    // advertises them; invalid input is re-prompted by the dialoguer
+    // validators below.
+    let limits = FieldLimits::from_env().with_constraints(caps);
+
+    if !draft.is_empty() {
+        say!(opts.output, "Se recuperaron los datos del registro anterior; pulse Enter para conservarlos.");
+    }
+
+    // Text fields start from the draft's value, if any.
+    let prompts = &opts.prompts;
+    let nombre_label = limits.prompt(RegisterField::NombreCompleto);
+    let nombre = prompts.input(
+        TextPrompt::new(&nombre_label)
+            .with_initial(draft.initial_text(RegisterField::NombreCompleto).as_deref())
+            .with_validator(&|v: &str| check_length(v, limits.nombre_completo)),
+    )?;
+    draft.nombre_completo = Some(nombre.clone());
+    let edad: i32 = prompts
+        .input(
+            TextPrompt::new("Edad")
+                .with_initial(draft.initial_text(RegisterField::Edad).as_deref())
+                .with_validator(&check_age_text),
+        )?
+        .trim()
+        .parse()?;
+    draft.edad = Some(edad);
+    // The configured `default_role` is pre-selected, or used without
+    // asking under `hide_role_prompt`.
+    let rol = match opts.default_role {
+        Some(role) if opts.hide_role_prompt => role,
+        configured => select_role(prompts, draft.rol.or(configured))?,
+    };
+    draft.rol = Some(rol);
+    let identificacion_label = limits.prompt(RegisterField::Identificacion);
+    let identificacion = prompts.input(
+        TextPrompt::new(&identificacion_label)
+            .with_initial(draft.initial_text(RegisterField::Identificacion).as_deref())
+            .with_validator(&|v: &str| check_identificacion(v).and_then(|_| check_length(v, limits.identificacion))),
+    )?;
+    draft.identificacion = Some(identificacion.clone());
+    let correo_label = limits.prompt(RegisterField::Correo);
+    let correo = prompts.input(
+        TextPrompt::new(&correo_label)
+            .with_initial(draft.initial_text(RegisterField::Correo).as_deref())
+            .with_validator(&|v: &str| check_email(v).and_then(|_| check_length(v, limits.correo))),
+    )?;
+    draft.correo = Some(correo.clone());
+    // Passwords are hidden and asked twice. If they don't match, the user
+    // may retry only the passwords or cancel the registration — do not
+    // force restarting the whole form.
+    require_password_terminal(prompts.can_read_password())?;
+    let contrasena = match ask_new_password(&opts.output, prompts, &limits)? {
+        Some(p) => p,
+        None => {
+            say!(opts.output, "Registro cancelado. Volviendo al menú.");
             return Ok(());
         }
-        // otherwise loop and ask for passwords again
     };
     // Keep the consent choice visible and persistent. Use Spanish Sí/No selection
-    let acepta_idx = Select::new()
-        .with_prompt("¿Acepta el tratamiento de datos?")
-        .items(&["Sí", "No"]) 
-        .default(1)
-        .interact()?;
+    let acepta_idx = prompts.select(
+        Some("¿Acepta el tratamiento de datos?"),
+        &["Sí", "No"],
+        if draft.acepta_tratamiento_datos == Some(true) { 0 } else { 1 },
+    )?;
     let acepta = acepta_idx == 0;
+    draft.acepta_tratamiento_datos = Some(acepta);
 
-    print_separator();
-    print_section("NeumoDiagnostics - Resumen de registro");
-    println!("Nombre: {}", nombre);
-    println!("Edad: {}", edad);
-    println!("Rol: {}", rol_choices[rol_idx]);
-    println!("Identificación: {}", identificacion);
-    println!("Correo: {}", correo);
-    println!("Acepta tratamiento de datos: {}", if acepta { "Sí" } else { "No" });
+    print_separator(&opts.output);
+    print_section(&opts.output, "NeumoDiagnostics - Resumen de registro");
+    say!(opts.output, "Nombre: {}", nombre);
+    say!(opts.output, "Edad: {}", edad);
+    say!(opts.output, "Rol: {}", rol.label());
+    say!(opts.output, "Identificación: {}", identificacion);
+    say!(opts.output, "Correo: {}", correo);
+    say!(opts.output, "Acepta tratamiento de datos: {}", if acepta { "Sí" } else { "No" });
 
-    let req = RegisterRequest {
+    let mut req = RegisterRequest {
         nombre_completo: nombre,
         edad,
         rol,
@@ -340,127 +1150,226 @@ fn handle_register(api: &ApiClient) -> Result<()> {
         contrasena,
         acepta_tratamiento_datos: acepta,
     };
+    if let Err(e) = req.validate_lengths(&limits) {
+        say!(opts.output, "Datos inválidos: {}", e);
+        return Ok(());
+    }
 
     // Final confirmation before registering — show data and ask Sí/No
-    print_separator();
-    println!("¿Confirmar registro con los datos mostrados? ");
-    let confirm_idx = Select::new().items(&["Sí", "No"]).default(0).interact()?;
-    if confirm_idx == 0 {
+    print_separator(&opts.output);
+    let confirmed = confirm_routine(opts, true, || {
+        say!(opts.output, "¿Confirmar registro con los datos mostrados? ");
+        Ok(prompts.select(None, &["Sí", "No"], 0)? == 0)
+    })?;
+    if !confirmed {
+        say!(opts.output, "Registro cancelado. Revise sus datos e intente de nuevo.");
+        return Ok(());
+    }
+    loop {
         // show spinner for UX, then call the API
-        use std::sync::mpsc::{channel, TryRecvError};
+        let api_cloned = api.clone();
+        let attempt = req.clone();
+        let outcome = run_with_progress("Registrando...", move |progress| api_cloned.with_retry_progress(progress).register(&attempt));
+        terminal::ring_bell(&mut *opts.output.terminal(), opts.bell);
+        let err = match outcome {
+            Some(Ok(_)) => {
+                *draft = RegisterDraft::default();
+                say!(opts.output, "Registrado exitosamente, por favor inicie sesión.");
+                return Ok(());
+            }
+            Some(Err(e)) => e,
+            None => {
+                say!(opts.output, "Fallo interno: no se pudo obtener el resultado del registro.");
+                return Ok(());
+            }
+        };
+        // Validation errors that name our fields: show them all and ask
+        // only for those fields again.
+        let errors = err.downcast_ref::<ApiError>().map(ApiError::field_errors).unwrap_or_default();
+        let (routed, other) = route_register_errors(&errors);
+        if routed.is_empty() {
+            show_error(&opts.output, api, format!("Fallo el registro: {}", error_message(&err, api.is_verbose())), &err);
+            return Ok(());
+        }
+        say!(opts.output, "El servidor rechazó algunos datos:");
+        for (field, message) in &routed {
+            say!(opts.output, "  - {}: {}", field.label(), message);
+        }
+        for e in &other {
+            say!(opts.output, "  - {}", e);
+        }
+        // Under `--yes` nobody is there to correct them: stop here
+        // instead of waiting for an answer.
+        if opts.assume_yes {
+            show_error(&opts.output, api, "Registro no completado: corrija estos datos y vuelva a intentarlo.".to_string(), &err);
+            return Ok(());
+        }
+        let fix = prompts.select(Some("¿Corregir estos campos y reintentar?"), &["Corregir", "Cancelar"], 0)?;
+        if fix == 1 {
+            say!(opts.output, "Registro cancelado. Volviendo al menú.");
+            return Ok(());
+        }
+        let mut asked = Vec::new();
+        for (field, _) in routed {
+            if !asked.contains(&field) {
+                reprompt_register_field(&opts.output, prompts, &mut req, field, &limits)?;
+                asked.push(field);
+            }
+        }
+        draft.remember(&req);
+    }
+}
 
-        let spinner = ProgressBar::new_spinner();
-        spinner.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap());
-        spinner.set_draw_target(ProgressDrawTarget::stderr());
-        spinner.set_message("Registrando...");
+/// Ask for the role, starting on `preset` (`Paciente` when `None`).
+fn select_role(prompts: &Prompts, preset: Option<Role>) -> Result<Role> {
+    let labels: Vec<&str> = Role::ALL.iter().map(Role::label).collect();
+    let current = Role::ALL.iter().position(|r| Some(*r) == preset).unwrap_or(1);
+    Ok(Role::ALL[prompts.select(Some("Rol"), &labels, current)?])
+}
 
-        let (tx, rx) = channel();
-        let api_cloned = api.clone();
-        let req_clone = req.clone();
-        std::thread::spawn(move || {
-            let r = api_cloned.register(&req_clone);
-            let _ = tx.send(r);
-        });
-
-        let start = Instant::now();
-        loop {
-            match rx.try_recv() {
-                Ok(res) => {
-                    while start.elapsed().as_millis() < MIN_SPINNER_MS as u128 {
-                        spinner.tick();
-                        thread::sleep(Duration::from_millis(80));
-                    }
-                    spinner.finish_and_clear();
-                    match res {
-                        Ok(_) => println!("Registrado exitosamente, por favor inicie sesión."),
-                        Err(e) => println!("Fallo el registro: {}", e),
-                    }
-                    break;
-                }
-                Err(TryRecvError::Empty) => {
-                    spinner.tick();
-                    thread::sleep(Duration::from_millis(80));
-                }
-                Err(_) => {
-                    spinner.finish_and_clear();
-                    println!("Fallo interno: no se pudo obtener el resultado del registro.");
-                    break;
+/// Split validation errors into those naming a registration field (with
+/// their message) and the rest, keeping the server's order.
+pub fn route_register_errors(errors: &[FieldError]) -> (Vec<(RegisterField, String)>, Vec<FieldError>) {
+    let mut routed = Vec::new();
+    let mut other = Vec::new();
+    for e in errors {
+        match e.field.as_deref().and_then(RegisterField::from_name) {
+            Some(field) => routed.push((field, e.message.clone())),
+            None => other.push(e.clone()),
+        }
+    }
+    (routed, other)
+}
+
+/// Ask again for one registration field, starting from its current value
+/// where that makes sense.
+fn reprompt_register_field(
+    out: &Output,
+    prompts: &Prompts,
+    req: &mut RegisterRequest,
+    field: RegisterField,
+    limits: &FieldLimits,
+) -> Result<()> {
+    let limits = *limits;
+    let label = limits.prompt(field);
+    match field {
+        RegisterField::NombreCompleto => {
+            req.nombre_completo = prompts.input(
+                TextPrompt::new(&label)
+                    .with_initial(Some(&req.nombre_completo))
+                    .with_validator(&|v: &str| check_length(v, limits.nombre_completo)),
+            )?;
+        }
+        RegisterField::Edad => {
+            let current = req.edad.to_string();
+            req.edad = prompts
+                .input(TextPrompt::new(&label).with_initial(Some(&current)).with_validator(&check_age_text))?
+                .trim()
+                .parse()?;
+        }
+        RegisterField::Rol => req.rol = select_role(prompts, Some(req.rol))?,
+        RegisterField::Identificacion => {
+            req.identificacion = prompts.input(
+                TextPrompt::new(&label)
+                    .with_initial(Some(&req.identificacion))
+                    .with_validator(&|v: &str| check_identificacion(v).and_then(|_| check_length(v, limits.identificacion))),
+            )?;
+        }
+        RegisterField::Correo => {
+            req.correo = prompts.input(
+                TextPrompt::new(&label)
+                    .with_initial(Some(&req.correo))
+                    .with_validator(&|v: &str| check_email(v).and_then(|_| check_length(v, limits.correo))),
+            )?;
+        }
+        RegisterField::Contrasena => {
+            require_password_terminal(prompts.can_read_password())?;
+            req.contrasena = loop {
+                let p = prompts.password(&label, Some(&|v: &str| limits.check_password(v)))?;
+                if prompts.password("Confirmar contraseña", None)? == p {
+                    break p;
                 }
-            }
+                say!(out, "Las contraseñas no coinciden.");
+            };
+        }
+        RegisterField::AceptaTratamientoDatos => {
+            let idx = prompts.select(
+                Some("¿Acepta el tratamiento de datos?"),
+                &["Sí", "No"],
+                if req.acepta_tratamiento_datos { 0 } else { 1 },
+            )?;
+            req.acepta_tratamiento_datos = idx == 0;
         }
-    } else {
-        println!("Registro cancelado. Revise sus datos e intente de nuevo.");
     }
     Ok(())
 }
 
 /// Collect credentials and perform login, returning the JWT token if OK.
-fn handle_login(api: &ApiClient) -> Result<Option<String>> {
+fn handle_login(api: &ApiClient, opts: &UiOptions) -> Result<Option<String>> {
     // Allow immediate cancel of the login flow
-    let start_idx = Select::new()
-        .with_prompt("¿Desea continuar con el inicio de sesión o cancelar?")
-        .items(&["Continuar", "Cancelar"]) 
-        .default(0)
-        .interact()?;
-    if start_idx == 1 {
-        println!("Inicio de sesión cancelado. Volviendo al menú.");
+    if !confirm_start(opts, "¿Desea continuar con el inicio de sesión o cancelar?")? {
+        say!(opts.output, "Inicio de sesión cancelado. Volviendo al menú.");
         return Ok(None);
     }
-    // Hide the initial selector when continuing so the form appears cleanly.
-    clear_previous_lines(1);
 
-    let correo: String = Input::new().with_prompt("Correo electrónico").interact_text()?;
-    let contrasena: String = Password::new().with_prompt("Contraseña").interact()?;
+    let correo = opts.prompts.input(TextPrompt::new("Correo electrónico"))?;
+    require_password_terminal(opts.prompts.can_read_password())?;
+    let contrasena = opts.prompts.password("Contraseña", None)?;
     let req = AuthRequest { correo, contrasena };
 
-    use std::sync::mpsc::{channel, TryRecvError};
+    let api_cloned = api.clone();
+    match run_with_progress("Iniciando sesión...", move |progress| api_cloned.with_retry_progress(progress).login(&req)) {
+        Some(Ok(resp)) => Ok(Some(resp.token)),
+        Some(Err(e)) => {
+            show_error(&opts.output, api, login_error_message(&e, opts.auth_debug, api.is_verbose()), &e);
+            Ok(None)
+        }
+        None => {
+            say!(opts.output, "Fallo interno: no se pudo obtener el resultado del inicio de sesión.");
+            Ok(None)
+        }
+    }
+}
 
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(ProgressStyle::with_template("{spinner} {msg}").unwrap());
-    spinner.set_draw_target(ProgressDrawTarget::stderr());
-    spinner.set_message("Iniciando sesión...");
+/// Submit a profile edit and return the message to show: "No se
+/// realizaron cambios." when nothing changed (no request is made), else
+/// a confirmation. The server's advisories go to `term`.
+pub fn save_profile_changes(api: &ApiClient, patch: &ProfilePatch, term: &mut dyn Terminal) -> Result<String> {
+    match api.update_profile(patch)? {
+        None => Ok(NO_PROFILE_CHANGES_MSG.to_string()),
+        Some(warnings) => {
+            print_warnings(term, &warnings);
+            Ok("Perfil actualizado.".to_string())
+        }
+    }
+}
 
-    let (tx, rx) = channel();
-    let api_cloned = api.clone();
-    let req_clone = req.clone();
-    std::thread::spawn(move || {
-        let r = api_cloned.login(&req_clone);
-        let _ = tx.send(r);
-    });
+/// Show the advisories of a successful operation, one `Aviso:` line
+/// each. They don't make the operation fail.
+pub fn print_warnings(term: &mut dyn Terminal, warnings: &[String]) {
+    for warning in warnings {
+        term.warning(warning);
+    }
+}
 
-    let start = Instant::now();
-    loop {
-        match rx.try_recv() {
-            Ok(res) => {
-                while start.elapsed().as_millis() < MIN_SPINNER_MS as u128 {
-                    spinner.tick();
-                    thread::sleep(Duration::from_millis(80));
-                }
-                spinner.finish_and_clear();
-                match res {
-                    Ok(resp) => return Ok(Some(resp.token)),
-                    Err(e) => {
-                        let err_text = e.to_string();
-                        let lower = err_text.to_lowercase();
-                        if lower.contains("bcrypt") || lower.contains("hashedpassword") || lower.contains("usuario no encontrado") || lower.contains("no rows") || lower.contains("invalid") || lower.contains("bad request") {
-                            println!("Credenciales inválidas: correo o contraseña incorrectos.");
-                        } else {
-                            println!("Fallo al iniciar sesión: {}", e);
-                        }
-                        return Ok(None);
-                    }
-                }
-            }
-            Err(TryRecvError::Empty) => {
-                spinner.tick();
-                thread::sleep(Duration::from_millis(80));
-            }
-            Err(_) => {
-                spinner.finish_and_clear();
-                println!("Fallo interno: no se pudo obtener el resultado del inicio de sesión.");
-                return Ok(None);
-            }
+/// Message for a failed login. Unknown user and wrong password both read
+/// "Credenciales inválidas" so the CLI does not reveal which accounts
+/// exist; with `auth_debug` (`--auth-debug`, for local/dev backends) the
+/// server's own reason is appended. Other failures read like any other
+/// error (see `error_message`).
+pub fn login_error_message(err: &anyhow::Error, auth_debug: bool, verbose: bool) -> String {
+    let api_error = err.downcast_ref::<ApiError>();
+    let rejected = api_error.is_some_and(|e| e.status == reqwest::StatusCode::UNAUTHORIZED || e.status == reqwest::StatusCode::NOT_FOUND);
+    let lower = err.to_string().to_lowercase();
+    if rejected || lower.contains("bcrypt") || lower.contains("hashedpassword") || lower.contains("usuario no encontrado") || lower.contains("no rows") || lower.contains("invalid") || lower.contains("bad request") {
+        let generic = "Credenciales inválidas: correo o contraseña incorrectos.".to_string();
+        let reason = api_error.and_then(ApiError::server_message);
+        match reason {
+            Some(reason) if auth_debug => format!("{} [auth-debug] Motivo del servidor: {}", generic, reason),
+            _ => generic,
         }
+    } else {
+        format!("Fallo al iniciar sesión: {}", error_message(err, verbose))
     }
 }
 
@@ -469,20 +1378,211 @@ fn handle_login(api: &ApiClient) -> Result<Option<String>> {
 // meta JSON file. See `ApiClient::persist_token_to_project` and
 // `ApiClient::load_token_from_project`.
 
-// Try to extract "nombre_completo" from a JWT token without verifying signature.
-// This is only for display purposes when restoring a session.
-fn extract_name_from_jwt(token: &str) -> Option<String> {
-    // JWT is three base64url parts separated by '.'; we want the payload (2nd part)
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() != 3 {
-        return None;
+/// Print a profile as a titled section. Missing fields show as "-".
+pub fn print_profile(out: &Output, profile: &Profile) {
+    let or_dash = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".into());
+    print_section(out, "NeumoDiagnostics - Mi perfil");
+    say!(out, "Nombre: {}", or_dash(&profile.nombre_completo));
+    say!(out, "Correo: {}", or_dash(&profile.correo));
+    say!(out, "Rol: {}", profile.rol.map_or("-", |r| r.label()));
+    say!(out, "Identificación: {}", or_dash(&profile.identificacion));
+    say!(out, "Edad: {}", profile.edad.map(|e| e.to_string()).unwrap_or_else(|| "-".into()));
+}
+
+/// Resolve the current user's profile: server data merged over the token
+/// claims. If the server can't be reached the token data is used alone
+/// and the returned error explains why.
+pub fn current_profile(api: &ApiClient) -> (Profile, Option<anyhow::Error>) {
+    let from_token = api.token().and_then(Profile::from_jwt).unwrap_or_default();
+    match api.get_profile() {
+        Ok(server) => (Profile::merge(server, from_token), None),
+        Err(e) => (from_token, Some(e)),
+    }
+}
+
+/// Describe when a token expires, e.g. "expira en 2 h 5 min".
+fn describe_expiry(token: &str) -> String {
+    let exp = match token_expiry(token) {
+        Some(exp) => exp,
+        None => return "sin fecha de expiración".into(),
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if exp <= now {
+        return "expirado".into();
+    }
+    let mins = (exp - now) / 60;
+    match (mins / 60, mins % 60) {
+        (0, m) => format!("expira en {} min", m),
+        (h, m) => format!("expira en {} h {} min", h, m),
+    }
+}
+
+/// Refresh the session token. On success the in-memory token is replaced
+/// and, if a token was saved on disk, the saved copy is updated keeping
+/// its meta flags. When the server rejects the token (401/403) the
+/// session is cleared so the user logs in again; other failures (network
+/// errors, timeouts, 5xx) keep it. Returns whether the refresh succeeded.
+pub fn refresh_session(out: &Output, api: &mut ApiClient) -> bool {
+    match api.refresh() {
+        Ok(token) => {
+            api.set_token(&token);
+            if let Ok(Some(_)) = api.load_token_from_project() {
+                let meta = api.load_token_meta().ok().flatten().unwrap_or_default();
+                let saved = api
+                    .persist_token_with_meta(&token, meta)
+                    .and_then(|_| api.set_clean_exit_meta(meta.clean_exit));
+                if let Err(e) = saved {
+                    say!(out, "Aviso: no se pudo guardar el nuevo token: {}", e);
+                }
+            }
+            say!(out, "Sesión renovada ({}).", describe_expiry(&token));
+            true
+        }
+        Err(e) => {
+            show_error(out, api, format!("No se pudo renovar la sesión: {}", error_message(&e, api.is_verbose())), &e);
+            let rejected = e
+                .downcast_ref::<ApiError>()
+                .is_some_and(|err| matches!(err.status.as_u16(), 401 | 403));
+            if rejected {
+                api.clear_token();
+                api.clear_persisted_token_in_project();
+                say!(out, "Inicie sesión nuevamente.");
+            } else {
+                say!(out, "La sesión actual se conserva; intente de nuevo más tarde.");
+            }
+            false
+        }
+    }
+}
+
+/// Ask the backend whether the current token is still valid and report
+/// valid / expired / revoked. Returns `Some(valid)` for an authoritative
+/// answer and `None` when the server could not be asked.
+pub fn verify_session(out: &Output, api: &ApiClient) -> Option<bool> {
+    let api_cloned = api.clone();
+    match run_with_progress("Verificando sesión...", move |progress| api_cloned.with_retry_progress(progress).verify_token()) {
+        Some(Ok(true)) => {
+            say!(out, "Sesión válida.");
+            Some(true)
+        }
+        Some(Ok(false)) => {
+            // The server's "no" is authoritative; the claims only tell
+            // us why, for a clearer message.
+            let expired = api.token().and_then(is_token_expired).unwrap_or(false);
+            if expired {
+                say!(out, "Sesión expirada. Inicie sesión nuevamente.");
+            } else {
+                say!(out, "Sesión revocada por el servidor. Inicie sesión nuevamente.");
+            }
+            Some(false)
+        }
+        Some(Err(e)) => {
+            show_error(out, api, format!("No se pudo verificar la sesión (sin respuesta del servidor): {}", error_message(&e, api.is_verbose())), &e);
+            None
+        }
+        None => {
+            say!(out, "Fallo interno: no se pudo obtener el resultado de la verificación.");
+            None
+        }
+    }
+}
+
+/// Explanation shown before revoking consent. Patients are told what
+/// they lose in the diagnostic service; other roles get the general
+/// consequences.
+pub fn consent_revocation_warning(rol: Option<Role>) -> String {
+    let general = "Al revocar el consentimiento, NeumoDiagnostics dejará de tratar sus datos personales \
+                   y su cuenta quedará limitada hasta que vuelva a aceptarlo.";
+    match rol {
+        Some(Role::Paciente) => format!(
+            "{} No podrá subir nuevas imágenes ni recibir diagnósticos, y su médico tratante ya no verá \
+             su información.",
+            general
+        ),
+        _ => general.to_string(),
+    }
+}
+
+/// Explain the consequences, ask for confirmation (never skipped by
+/// `--yes`) and revoke consent, recording it in the audit log.
+fn handle_revoke_consent(api: &ApiClient, opts: &UiOptions) -> Result<()> {
+    if api.is_read_only() {
+        say!(opts.output, "{}", READ_ONLY_MSG);
+        return Ok(());
+    }
+    let (profile, _) = current_profile(api);
+    say!(opts.output, "{}", consent_revocation_warning(profile.rol));
+    let confirmed =
+        opts.prompts.select(Some("¿Desea revocar su consentimiento de tratamiento de datos?"), &["No", "Sí, revocar"], 0)? == 1;
+    if !confirmed {
+        say!(opts.output, "Revocación cancelada.");
+        return Ok(());
+    }
+    let api_cloned = api.clone();
+    match run_with_progress("Revocando consentimiento...", move |progress| {
+        api_cloned.with_retry_progress(progress).revoke_consent()
+    }) {
+        Some(Ok(())) => {
+            say!(opts.output, "Consentimiento revocado.");
+            let details = serde_json::json!({"user_id": profile.user_id});
+            if let Err(e) = audit::record(api, "consent_revoked", details) {
+                say!(opts.output, "Aviso: no se pudo escribir el registro de auditoría: {}", e);
+            }
+        }
+        Some(Err(e)) => show_error(&opts.output, api, format!("No se pudo revocar el consentimiento: {}", error_message(&e, api.is_verbose())), &e),
+        None => say!(opts.output, "Fallo interno: no se pudo obtener el resultado de la revocación."),
+    }
+    Ok(())
+}
+
+/// Remove every file the CLI persisted (see `reset.rs`) after the user
+/// types `RESET_CONFIRMATION_WORD`. The configuration file is offered
+/// separately. Clears the in-memory token too and returns whether
+/// anything was deleted. With `opts.force` nothing is asked and the
+/// configuration is kept.
+pub fn reset_local_data(api: &mut ApiClient, opts: &UiOptions) -> Result<bool> {
+    let paths = StoragePaths::resolve(api, opts.config_path.as_deref())?;
+    let mut files: Vec<DataFile> = local_data_files(&paths).into_iter().filter(|f| f.path.exists()).collect();
+    let config = Some(config_file(&paths)).filter(|f| f.path.exists());
+    if files.is_empty() && config.is_none() {
+        say!(opts.output, "No hay datos locales que borrar.");
+        return Ok(false);
+    }
+
+    say!(opts.output, "Se eliminarán los siguientes archivos:");
+    for file in &files {
+        say!(opts.output, "  - {} ({})", file.description, file.path.display());
+    }
+    if let Some(config) = config.filter(|_| !opts.force) {
+        let prompt = format!("¿Eliminar también la configuración ({})?", config.path.display());
+        let remove_config = opts.prompts.select(Some(&prompt), &["No", "Sí"], 0)? == 1;
+        if remove_config {
+            files.push(config);
+        }
+    }
+    if files.is_empty() {
+        say!(opts.output, "No se borró nada.");
+        return Ok(false);
+    }
+
+    let confirmed = confirm_destructive(opts, || {
+        let prompt = format!("Escriba {} para confirmar", RESET_CONFIRMATION_WORD);
+        let typed = opts.prompts.input(TextPrompt::new(&prompt).with_allow_empty(true))?;
+        Ok(typed.trim() == RESET_CONFIRMATION_WORD)
+    })?;
+    if !confirmed {
+        say!(opts.output, "Borrado cancelado.");
+        return Ok(false);
+    }
+
+    api.clear_token();
+    let removed = remove_data_files(&files)?;
+    for file in &removed {
+        say!(opts.output, "Eliminado: {} ({})", file.description, file.path.display());
     }
-    let payload_b64 = parts[1];
-    // base64 in JWT is URL-safe without padding; standard engine accepts padded base64,
-    // try to add padding if necessary.
-    let mut s = payload_b64.replace('-', "+").replace('_', "/");
-    while s.len() % 4 != 0 { s.push('='); }
-    let decoded = base64_standard.decode(&s).ok()?;
-    let json: serde_json::Value = serde_json::from_slice(&decoded).ok()?;
-    json.get("nombre_completo").and_then(|v| v.as_str()).map(|s| s.to_string())
+    say!(opts.output, "Datos locales borrados.");
+    Ok(!removed.is_empty())
 }
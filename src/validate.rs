@@ -0,0 +1,139 @@
+// Dry registration check
+// ----------------------
+// `neumodiag-cli validate usuarios.csv` runs the client-side registration
+// checks (`RegisterRequest::problems`) over a batch of rows and reports
+// which rows and fields would be rejected, without any network call. The
+// first line names the columns, using the registration field names:
+//
+// ```text
+// nombre_completo,edad,rol,identificacion,correo,contrasena,acepta_tratamiento_datos
+// Ana Pérez,34,paciente,1020304050,ana@example.com,s3creta-larga,si
+// ```
+//
+// Values may be double-quoted (`"Pérez, Ana"`, with `""` for a quote).
+// Consent accepts the usual yes/no answers (see `yes_no.rs`) as well as
+// `true`/`false` and `1`/`0`.
+
+use crate::api::{FieldLimits, RegisterField, RegisterRequest, Role};
+use crate::yes_no::{parse_yes_no, Lang};
+use anyhow::Result;
+
+// Columns every file must have, in `RegisterField` terms.
+const REQUIRED_COLUMNS: [RegisterField; 7] = [
+    RegisterField::NombreCompleto,
+    RegisterField::Edad,
+    RegisterField::Rol,
+    RegisterField::Identificacion,
+    RegisterField::Correo,
+    RegisterField::Contrasena,
+    RegisterField::AceptaTratamientoDatos,
+];
+
+/// RowReport
+///
+/// The problems found in one data row; `line` is its line number in the
+/// file (the header is line 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowReport {
+    pub line: usize,
+    pub problems: Vec<(RegisterField, String)>,
+}
+
+impl RowReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check every data row of `csv` against `limits`. Fails only when the
+/// header is missing or lacks a column; bad values are reported per row.
+/// Blank lines are skipped.
+pub fn validate_csv(csv: &str, limits: &FieldLimits) -> Result<Vec<RowReport>> {
+    let mut lines = csv.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    let header = match lines.next() {
+        Some((_, header)) => split_csv_line(header),
+        None => anyhow::bail!("El archivo está vacío; la primera línea debe nombrar las columnas."),
+    };
+    let columns: Vec<Option<RegisterField>> = header.iter().map(|name| RegisterField::from_name(name)).collect();
+    let missing: Vec<&str> = REQUIRED_COLUMNS
+        .iter()
+        .filter(|field| !columns.contains(&Some(**field)))
+        .map(|field| field.label())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("Faltan columnas en el encabezado: {}", missing.join(", "));
+    }
+
+    Ok(lines.map(|(i, line)| validate_row(i + 1, &columns, &split_csv_line(line), limits)).collect())
+}
+
+fn validate_row(line: usize, columns: &[Option<RegisterField>], values: &[String], limits: &FieldLimits) -> RowReport {
+    let value = |field: RegisterField| {
+        columns
+            .iter()
+            .position(|c| *c == Some(field))
+            .and_then(|i| values.get(i))
+            .map(|v| v.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut problems = Vec::new();
+    let edad = match value(RegisterField::Edad).parse::<i32>() {
+        Ok(edad) => Some(edad),
+        Err(_) => {
+            problems.push((RegisterField::Edad, format!("Debe ser un número entero (se ingresó '{}').", value(RegisterField::Edad))));
+            None
+        }
+    };
+    let rol = value(RegisterField::Rol).parse::<Role>();
+    if let Err(e) = &rol {
+        problems.push((RegisterField::Rol, e.clone()));
+    }
+    let consent = value(RegisterField::AceptaTratamientoDatos);
+    let acepta = match consent.to_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        other => parse_yes_no(other, Lang::Es),
+    };
+    if acepta.is_none() {
+        problems.push((RegisterField::AceptaTratamientoDatos, format!("Se esperaba sí o no (se ingresó '{}').", consent)));
+    }
+    let req = RegisterRequest {
+        nombre_completo: value(RegisterField::NombreCompleto),
+        edad: edad.unwrap_or_default(),
+        rol: rol.unwrap_or(Role::Paciente),
+        identificacion: value(RegisterField::Identificacion),
+        correo: value(RegisterField::Correo),
+        contrasena: value(RegisterField::Contrasena),
+        acepta_tratamiento_datos: acepta.unwrap_or(false),
+    };
+    // Values that didn't parse were reported above; skip their checks.
+    problems.extend(req.problems(limits).into_iter().filter(|(field, _)| match field {
+        RegisterField::Edad => edad.is_some(),
+        RegisterField::AceptaTratamientoDatos => acepta.is_some(),
+        _ => true,
+    }));
+    problems.sort_by_key(|(field, _)| REQUIRED_COLUMNS.iter().position(|c| c == field));
+    RowReport { line, problems }
+}
+
+/// Split one CSV line on commas. Double-quoted values may contain commas
+/// and `""` for a literal quote.
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => values.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    values.push(current);
+    values
+}
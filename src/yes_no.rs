@@ -0,0 +1,59 @@
+// Yes/no answers
+// --------------
+// Typed confirmations (as opposed to the arrow-key `Select` prompts)
+// accept the usual short and long forms in the user's language. Input is
+// compared case- and accent-insensitively, so "Sí", "SI" and "si" are the
+// same answer. English "y"/"yes" are also accepted in Spanish, since many
+// users type them out of habit.
+
+/// Lang
+///
+/// Language of the answers accepted by `parse_yes_no`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    Es,
+    En,
+}
+
+impl Lang {
+    // Accepted "yes" words, lowercase and without accents.
+    fn yes_words(&self) -> &'static [&'static str] {
+        match self {
+            Lang::Es => &["s", "si", "y", "yes"],
+            Lang::En => &["y", "yes"],
+        }
+    }
+
+    // Accepted "no" words, lowercase and without accents.
+    fn no_words(&self) -> &'static [&'static str] {
+        &["n", "no"]
+    }
+}
+
+/// `Some(true)` for a "yes", `Some(false)` for a "no" and `None` for
+/// anything else (including empty input), in `lang`.
+pub fn parse_yes_no(input: &str, lang: Lang) -> Option<bool> {
+    let answer = fold_accents(&input.trim().to_lowercase());
+    if lang.yes_words().contains(&answer.as_str()) {
+        Some(true)
+    } else if lang.no_words().contains(&answer.as_str()) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// Replace accented vowels with plain ones ("sí" -> "si").
+fn fold_accents(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            other => other,
+        })
+        .collect()
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::audit::{mask_identifier, parse_events, read_events, AUDIT_FILE_NAME};
+use neumodiag_cli::api::ApiClient;
+use neumodiag_cli::backend::FakeBackend;
+use neumodiag_cli::prompt::{Output, Prompts};
+use neumodiag_cli::spinner;
+use neumodiag_cli::ui::{main_menu, UiOptions};
+use std::sync::Arc;
+
+#[test]
+fn history_lists_events_newest_first_and_skips_broken_lines() {
+    let log = r#"{"ts": 1760000000, "action": "login", "user_id": 1234567}
+not json
+{"ts": 1760000600, "action": "upload"}
+{"action": "logout"}
+{"ts": 1760000600, "action": "logout", "correo": "ana@example.com"}
+"#;
+
+    let events = parse_events(log);
+
+    let actions: Vec<&str> = events.iter().map(|e| e.action.as_str()).collect();
+    // Same timestamp: the later line comes first.
+    assert_eq!(actions, ["logout", "upload", "login"]);
+    assert_eq!(events[0].describe(), "2025-10-09 09:03 UTC  Cierre de sesión (correo a***@example.com)");
+    assert_eq!(events[1].describe(), "2025-10-09 09:03 UTC  Foto de perfil subida");
+    assert_eq!(events[2].describe(), "2025-10-09 08:53 UTC  Inicio de sesión (usuario ***67)");
+}
+
+#[test]
+fn identifiers_are_masked() {
+    assert_eq!(mask_identifier("ana.perez@example.com"), "a***@example.com");
+    assert_eq!(mask_identifier("1020304050"), "***50");
+    assert_eq!(mask_identifier("42"), "***");
+}
+
+#[test]
+fn menu_records_login_upload_and_logout() {
+    spinner::set_min_display_ms(0);
+    let dir = tempfile::tempdir().unwrap();
+    let picture = write_file(&dir, "perfil.jpg", b"\xFF\xD8\xFFfake-jpeg");
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(200).json_body(auth_body());
+    });
+    server.mock(|when, then| {
+        when.method(POST).path("/upload");
+        then.status(200);
+    });
+    let api = client_for(&server).with_state_dir(dir.path());
+    let path = picture.display().to_string();
+    let answers = [
+        "Iniciar sesión",
+        "ana@example.com",
+        "s3creta-larga",
+        "Subir foto de perfil",
+        "Ingresar ruta manualmente",
+        &path,
+        "Cerrar sesión",
+        "Sí",
+        "Historial de sesión",
+        "Salir",
+    ];
+    let opts = UiOptions { assume_yes: true, prompts: Prompts::scripted(answers), ..Default::default() };
+
+    main_menu(api.clone(), opts).unwrap();
+
+    assert!(dir.path().join(AUDIT_FILE_NAME).exists());
+    let actions: Vec<String> = read_events(&api).unwrap().into_iter().map(|e| e.action).collect();
+    assert_eq!(actions, ["logout", "upload", "login"]);
+}
+
+#[test]
+fn log_file_keeps_no_plain_identifiers() {
+    spinner::set_min_display_ms(0);
+    let dir = tempfile::tempdir().unwrap();
+    let backend = Arc::new(FakeBackend::new().with_user("Ana Pérez", "ana.perez@example.com", "s3creta-larga"));
+    let api = ApiClient::new("http://gateway.invalid").unwrap().with_backend(backend).with_state_dir(dir.path());
+    let answers = ["Iniciar sesión", "ana.perez@example.com", "s3creta-larga", "Cerrar sesión", "Sí", "Salir"];
+    let opts = UiOptions { assume_yes: true, prompts: Prompts::scripted(answers), output: Output::buffer(), ..Default::default() };
+
+    main_menu(api, opts).unwrap();
+
+    let raw = std::fs::read_to_string(dir.path().join(AUDIT_FILE_NAME)).unwrap();
+    assert!(!raw.contains("ana.perez@example.com"), "{}", raw);
+    assert!(raw.contains(r#""correo":"a***@example.com""#), "{}", raw);
+}
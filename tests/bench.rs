@@ -0,0 +1,22 @@
+use neumodiag_cli::bench::LatencyStats;
+use std::time::Duration;
+
+#[test]
+fn latency_stats_from_samples() {
+    // 1..=20 ms, shuffled: p95 by nearest rank is the 19th value.
+    let samples: Vec<Duration> = [7, 3, 20, 1, 15, 9, 12, 2, 18, 5, 11, 4, 19, 6, 14, 8, 17, 10, 16, 13]
+        .iter()
+        .map(|ms| Duration::from_millis(*ms))
+        .collect();
+
+    let stats = LatencyStats::from_samples(&samples).unwrap();
+    assert_eq!(stats.count, 20);
+    assert_eq!(stats.min_ms, 1.0);
+    assert_eq!(stats.max_ms, 20.0);
+    assert_eq!(stats.avg_ms, 10.5);
+    assert_eq!(stats.p95_ms, 19.0);
+
+    let single = LatencyStats::from_samples(&[Duration::from_millis(40)]).unwrap();
+    assert_eq!((single.min_ms, single.max_ms, single.p95_ms), (40.0, 40.0, 40.0));
+    assert!(LatencyStats::from_samples(&[]).is_none());
+}
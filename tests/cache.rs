@@ -0,0 +1,84 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use serde_json::json;
+use std::time::Duration;
+
+
+#[test]
+fn second_get_within_ttl_is_served_from_cache() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/capabilities");
+        then.status(200).json_body(json!({"features": ["upload"]}));
+    });
+    let api = client_for(&server).with_cache_ttl(Some(Duration::from_secs(60)));
+
+    assert!(api.get_capabilities().unwrap().supports("upload"));
+    assert!(api.clone().get_capabilities().unwrap().supports("upload"));
+
+    assert_eq!(mock.hits(), 1);
+}
+
+#[test]
+fn expired_or_disabled_cache_refetches() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/profile");
+        then.status(200).json_body(json!({"nombre": "Ana Pérez"}));
+    });
+    let mut api = client_for(&server).with_cache_ttl(Some(Duration::from_millis(100)));
+    api.set_token(TOKEN);
+
+    api.get_profile().unwrap();
+    std::thread::sleep(Duration::from_millis(150));
+    api.get_profile().unwrap();
+    assert_eq!(mock.hits(), 2);
+
+    let mut uncached = client_for(&server).with_cache_ttl(None);
+    uncached.set_token(TOKEN);
+    uncached.get_profile().unwrap();
+    uncached.get_profile().unwrap();
+    assert_eq!(mock.hits(), 4);
+}
+
+#[test]
+fn cache_is_per_token() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/profile");
+        then.status(200).json_body(json!({"nombre": "Ana Pérez"}));
+    });
+    let mut api = client_for(&server);
+    api.set_token(TOKEN);
+    api.get_profile().unwrap();
+    api.set_token("otro.token.jwt");
+    api.get_profile().unwrap();
+
+    assert_eq!(mock.hits(), 2);
+}
+
+#[test]
+fn profile_edit_drops_the_cached_profile() {
+    use neumodiag_cli::profile::ProfilePatch;
+
+    let server = MockServer::start();
+    let get = server.mock(|when, then| {
+        when.method(GET).path("/profile");
+        then.status(200).json_body(json!({"nombre": "Ana Pérez"}));
+    });
+    let patch = server.mock(|when, then| {
+        when.method(httpmock::Method::PATCH).path("/profile");
+        then.status(200);
+    });
+    let mut api = client_for(&server).with_cache_ttl(Some(Duration::from_secs(60)));
+    api.set_token(TOKEN);
+
+    api.get_profile().unwrap();
+    api.update_profile(&ProfilePatch { edad: Some(35), ..ProfilePatch::default() }).unwrap();
+    api.get_profile().unwrap();
+
+    patch.assert();
+    assert_eq!(get.hits(), 2);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::state::StateStore;
+use neumodiag_cli::cli::Command;
+use neumodiag_cli::commands;
+
+fn login_command(remember: bool) -> Command {
+    Command::Login { email: "ana@example.com".into(), remember, no_remember: !remember }
+}
+
+fn run_login(remember: bool) -> tempfile::TempDir {
+    // Same value in every test, so setting it concurrently is harmless.
+    std::env::set_var("NEUMODIAG_PASSWORD", "s3creta-larga");
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/auth").json_body(serde_json::json!({"correo": "ana@example.com", "contrasena": "s3creta-larga"}));
+        then.status(200).json_body(auth_body());
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let api = client_for(&server).with_state_dir(dir.path());
+
+    commands::run(api, login_command(remember), &Default::default()).expect("login");
+
+    mock.assert();
+    dir
+}
+
+#[test]
+fn login_with_remember_saves_the_session() {
+    let dir = run_login(true);
+
+    let state = StateStore::new(dir.path()).load().unwrap();
+    assert_eq!(state.token.as_deref(), Some(TOKEN));
+    assert!(state.meta.unwrap().persist);
+}
+
+#[test]
+fn login_with_no_remember_writes_nothing() {
+    let dir = run_login(false);
+
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
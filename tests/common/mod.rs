@@ -0,0 +1,114 @@
+// Shared helpers for the integration suite
+// ----------------------------------------
+// Every test spins up its own `httpmock` server acting as the API
+// gateway and talks to it through a real `ApiClient`, so request shapes,
+// headers and error mapping are exercised end to end.
+
+#![allow(dead_code)]
+
+use httpmock::MockServer;
+use neumodiag_cli::api::{ApiClient, AuthRequest, RegisterRequest, Role};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+
+/// Build an `ApiClient` pointed at the mock gateway.
+pub fn client_for(server: &MockServer) -> ApiClient {
+    ApiClient::new(server.base_url()).expect("building ApiClient")
+}
+
+/// An unsigned JWT-shaped token carrying `claims`.
+pub fn jwt(claims: serde_json::Value) -> String {
+    use base64::Engine as _;
+    format!("h.{}.s", base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(claims.to_string()))
+}
+
+/// A sample JWT-shaped token. Only its shape matters to the client.
+pub const TOKEN: &str = "header.eyJub21icmVfY29tcGxldG8iOiJBbmEgUGVyZXoifQ.signature";
+
+pub fn sample_register() -> RegisterRequest {
+    RegisterRequest {
+        nombre_completo: "Ana Pérez".into(),
+        edad: 34,
+        rol: Role::Paciente,
+        identificacion: "1020304050".into(),
+        correo: "ana@example.com".into(),
+        contrasena: "s3creta-larga".into(),
+        acepta_tratamiento_datos: true,
+    }
+}
+
+pub fn sample_auth() -> AuthRequest {
+    AuthRequest {
+        correo: "ana@example.com".into(),
+        contrasena: "s3creta-larga".into(),
+    }
+}
+
+/// JSON body the gateway returns on a successful `/auth`.
+pub fn auth_body() -> serde_json::Value {
+    serde_json::json!({
+        "nombre": "Ana Pérez",
+        "token": TOKEN,
+        "rol": "paciente",
+        "user_id": 7,
+        "correo": "ana@example.com",
+    })
+}
+
+/// Write `bytes` to `name` inside `dir` and return the path.
+pub fn write_file(dir: &tempfile::TempDir, name: &str, bytes: &[u8]) -> PathBuf {
+    let path = dir.path().join(name);
+    let mut f = std::fs::File::create(&path).expect("creating fixture");
+    f.write_all(bytes).expect("writing fixture");
+    path
+}
+
+/// A raw TCP gateway for replies `httpmock` can't produce (broken gzip,
+/// exact multipart bytes). It accepts `connections` connections one after
+/// the other, reads each whole request (headers plus a `Content-Length`
+/// body), writes `reply(index, request)` and closes. Joining the handle
+/// returns the requests in order.
+pub fn raw_gateway<F>(connections: usize, mut reply: F) -> (SocketAddr, JoinHandle<Vec<Vec<u8>>>)
+where
+    F: FnMut(usize, &[u8]) -> Vec<u8> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for i in 0..connections {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_request(&mut stream);
+            let _ = stream.write_all(&reply(i, &request));
+            requests.push(request);
+        }
+        requests
+    });
+    (addr, handle)
+}
+
+// Read one HTTP/1.1 request: up to the blank line, then the body its
+// `Content-Length` announces.
+fn read_request(stream: &mut impl Read) -> Vec<u8> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut buf).unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request).to_lowercase();
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length: usize = text
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length:"))
+                .map(|v| v.trim().parse().unwrap())
+                .unwrap_or(0);
+            if request.len() >= end + 4 + length || n == 0 {
+                return request;
+            }
+        } else if n == 0 {
+            return request;
+        }
+    }
+}
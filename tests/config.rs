@@ -0,0 +1,181 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::config::{env_headers, Config};
+use std::collections::BTreeMap;
+
+#[test]
+fn extra_headers_are_parsed_from_toml() {
+    let config = Config::from_toml_str(
+        r#"
+        [extra_headers]
+        "X-Tenant" = "clinicA"
+        "X-Api-Key" = "k-123"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.extra_headers["X-Tenant"], "clinicA");
+    assert_eq!(config.extra_headers["X-Api-Key"], "k-123");
+}
+
+#[test]
+fn env_header_names_use_dashes() {
+    let vars = vec![
+        ("NEUMODIAG_HEADER_X_TENANT".to_string(), "clinicB".to_string()),
+        ("PATH".to_string(), "/usr/bin".to_string()),
+    ];
+
+    let headers = env_headers(vars.into_iter());
+
+    assert_eq!(headers.len(), 1);
+    assert_eq!(headers["X-TENANT"], "clinicB");
+}
+
+#[test]
+fn configured_headers_are_sent_with_authorization() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/profile")
+            .header("x-tenant", "clinicA")
+            .header("authorization", format!("Bearer {}", TOKEN));
+        then.status(200).json_body(serde_json::json!({}));
+    });
+    let headers = BTreeMap::from([("X-Tenant".to_string(), "clinicA".to_string())]);
+    let mut api = client_for(&server).with_extra_headers(&headers).unwrap();
+    api.set_token(TOKEN);
+
+    api.get_profile().unwrap();
+
+    mock.assert();
+}
+
+#[test]
+fn invalid_header_names_are_rejected() {
+    let server = MockServer::start();
+    let headers = BTreeMap::from([("X Tenant".to_string(), "clinicA".to_string())]);
+
+    let err = client_for(&server).with_extra_headers(&headers).err().unwrap().to_string();
+
+    assert!(err.contains("Nombre de encabezado inválido"), "{}", err);
+}
+
+const WITH_ENVS: &str = r#"
+gateway_url = "http://localhost:8080"
+
+[extra_headers]
+"X-Tenant" = "clinicA"
+"X-Api-Key" = "k-base"
+
+[env.staging]
+gateway_url = "https://staging.example.com"
+
+[env.prod]
+gateway_url = "https://api.example.com"
+[env.prod.extra_headers]
+"X-Api-Key" = "k-prod"
+"#;
+
+#[test]
+fn selected_env_overrides_base_and_inherits_the_rest() {
+    let staging = Config::from_toml_str(WITH_ENVS).unwrap().for_env(Some("staging")).unwrap();
+    assert_eq!(staging.gateway_url.as_deref(), Some("https://staging.example.com"));
+    assert_eq!(staging.extra_headers["X-Tenant"], "clinicA");
+    assert_eq!(staging.extra_headers["X-Api-Key"], "k-base");
+
+    let prod = Config::from_toml_str(WITH_ENVS).unwrap().for_env(Some("prod")).unwrap();
+    assert_eq!(prod.gateway_url.as_deref(), Some("https://api.example.com"));
+    assert_eq!(prod.extra_headers["X-Tenant"], "clinicA");
+    assert_eq!(prod.extra_headers["X-Api-Key"], "k-prod");
+
+    let base = Config::from_toml_str(WITH_ENVS).unwrap().for_env(None).unwrap();
+    assert_eq!(base.gateway_url.as_deref(), Some("http://localhost:8080"));
+}
+
+#[test]
+fn unknown_env_lists_the_available_ones() {
+    let err = Config::from_toml_str(WITH_ENVS).unwrap().for_env(Some("qa")).unwrap_err().to_string();
+
+    assert!(err.contains("'qa'"), "{}", err);
+    assert!(err.contains("prod, staging"), "{}", err);
+}
+
+#[test]
+fn env_sections_name_keys_they_cannot_override() {
+    let toml = "bell = true\n\n[env.staging]\ngateway_url = \"https://staging.example.com\"\nretry_status = [429]\n";
+
+    let err = Config::from_toml_str(toml).unwrap_err().to_string();
+
+    assert!(err.contains("'retry_status'"), "{}", err);
+    assert!(err.contains("[env.staging]"), "{}", err);
+    assert!(err.contains("gateway_url, extra_headers, token_store, token_header, health"), "{}", err);
+}
+
+#[test]
+fn config_path_loads_exactly_that_file() {
+    use clap::Parser;
+    use neumodiag_cli::cli::Cli;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clinica-b.toml");
+    std::fs::write(&path, "gateway_url = \"http://clinica-b.example:9000\"\n").unwrap();
+
+    let cli = Cli::try_parse_from(["neumodiag-cli", "--config-path", path.to_str().unwrap()]).unwrap();
+    let config = cli.load_config().unwrap();
+    assert_eq!(config.gateway_url.as_deref(), Some("http://clinica-b.example:9000"));
+    let api = cli.build_api_client(&config).unwrap();
+    assert_eq!(api.base_url(), "http://clinica-b.example:9000");
+
+    let missing = dir.path().join("no-existe.toml");
+    let cli = Cli::try_parse_from(["neumodiag-cli", "--config-path", missing.to_str().unwrap()]).unwrap();
+    let err = cli.load_config().unwrap_err().to_string();
+    assert!(err.contains("No se encontró el archivo de configuración"), "{}", err);
+    assert!(err.contains("no-existe.toml"), "{}", err);
+}
+
+#[test]
+fn default_role_is_checked_against_the_allowed_roles() {
+    let config = Config::from_toml_str("default_role = \"Paciente\"\nhide_role_prompt = true\n").unwrap();
+    assert_eq!(config.default_role().unwrap(), Some(neumodiag_cli::api::Role::Paciente));
+
+    let err = Config::from_toml_str("default_role = \"admin\"\n").unwrap().default_role().unwrap_err().to_string();
+    assert!(err.contains("default_role"), "{}", err);
+    assert!(err.contains("'admin'"), "{}", err);
+
+    let err = Config::from_toml_str("hide_role_prompt = true\n").unwrap().default_role().unwrap_err().to_string();
+    assert!(err.contains("requiere default_role"), "{}", err);
+}
+
+#[test]
+fn image_settings_are_checked_at_load() {
+    let config = Config::from_toml_str("allowed_image_exts = [\"jpg\", \"png\"]\nmax_upload_mb = 5\n").unwrap();
+    let policy = config.image_policy().unwrap();
+    assert_eq!(policy.extensions(), ["jpg", "png"]);
+    assert_eq!(policy.max_bytes(), 5 * 1024 * 1024);
+
+    let err = Config::from_toml_str("allowed_image_exts = [\"jpg\", \"gif\"]\n").unwrap_err().to_string();
+    assert!(err.contains("'gif'"), "{}", err);
+    let err = Config::from_toml_str("allowed_image_exts = []\n").unwrap_err().to_string();
+    assert!(err.contains("allowed_image_exts"), "{}", err);
+    let err = Config::from_toml_str("max_upload_mb = 0\n").unwrap_err().to_string();
+    assert!(err.contains("max_upload_mb"), "{}", err);
+}
+
+#[test]
+fn greeting_style_is_read_from_the_file() {
+    use neumodiag_cli::profile::GreetingStyle;
+
+    assert_eq!(Config::default().greeting_style.unwrap_or_default(), GreetingStyle::Full);
+    let config = Config::from_toml_str("greeting_style = \"formal\"\n").unwrap();
+    assert_eq!(config.greeting_style, Some(GreetingStyle::Formal));
+    assert!(Config::from_toml_str("greeting_style = \"nickname\"\n").is_err());
+}
+
+#[test]
+fn bell_is_off_unless_configured() {
+    assert!(!Config::default().bell_enabled());
+    let config = Config::from_toml_str("bell = true\n").unwrap();
+    assert!(config.bell);
+}
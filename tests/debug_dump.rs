@@ -0,0 +1,40 @@
+use httpmock::prelude::*;
+use neumodiag_cli::api::{ApiClient, AuthRequest};
+use serde_json::json;
+
+#[test]
+fn login_is_dumped_with_secrets_redacted() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(200).json_body(json!({
+            "nombre": "Ana Pérez",
+            "token": "header.payload.signature",
+            "rol": "paciente",
+            "user_id": 7,
+            "correo": "ana@example.com",
+        }));
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let dump = dir.path().join("debug.jsonl");
+    // The only test in this binary, so setting the variable races nothing.
+    std::env::set_var("API_GATEWAY_URL", server.base_url());
+    let api = ApiClient::from_env().unwrap().with_debug_dump(dump.clone());
+
+    api.login(&AuthRequest { correo: "ana@example.com".into(), contrasena: "s3creta-larga".into() })
+        .expect("login");
+
+    let text = std::fs::read_to_string(&dump).unwrap();
+    assert!(!text.contains("s3creta-larga"), "{}", text);
+    assert!(!text.contains("header.payload.signature"), "{}", text);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let entry: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(entry["method"], "POST");
+    assert_eq!(entry["url"], format!("{}/auth", server.base_url()));
+    assert_eq!(entry["status"], 200);
+    assert_eq!(entry["request"], json!({"correo": "ana@example.com", "contrasena": "***"}));
+    assert_eq!(entry["response"]["token"], "***");
+    assert_eq!(entry["response"]["nombre"], "Ana Pérez");
+    assert!(lines[0].contains(r#""contrasena":"***""#), "{}", lines[0]);
+}
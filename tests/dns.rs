@@ -0,0 +1,32 @@
+use neumodiag_cli::api::ApiClient;
+use neumodiag_cli::dns::{resolve_with, ResolveError};
+use std::io;
+use std::net::SocketAddr;
+
+#[test]
+fn resolver_distinguishes_nxdomain_from_other_failures() {
+    let nxdomain = resolve_with("gatewy.example.com", 443, |_, _| {
+        Err(io::Error::other("failed to lookup address information: Name or service not known"))
+    });
+    assert_eq!(nxdomain, Err(ResolveError::NotFound { host: "gatewy.example.com".into() }));
+
+    let temporary = resolve_with("gateway.example.com", 443, |_, _| {
+        Err(io::Error::other("failed to lookup address information: Temporary failure in name resolution"))
+    });
+    assert!(matches!(temporary, Err(ResolveError::Other { ref host, .. }) if host == "gateway.example.com"));
+
+    let ok = resolve_with("gateway.example.com", 443, |_, _| Ok(vec![SocketAddr::from(([10, 0, 0, 1], 443))]));
+    assert_eq!(ok, Ok(()));
+}
+
+#[test]
+fn resolve_error_names_the_host() {
+    let err = ResolveError::NotFound { host: "gatewy.example.com".into() };
+    assert!(err.to_string().starts_with("No se pudo resolver el host gatewy.example.com"), "{}", err);
+}
+
+#[test]
+fn ip_gateways_skip_the_lookup() {
+    let api = ApiClient::new("http://127.0.0.1:9").unwrap();
+    api.preflight_dns().expect("nothing to resolve");
+}
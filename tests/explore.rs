@@ -0,0 +1,38 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::explore::{parse_body, send, ExploreRequest};
+use reqwest::Method;
+use serde_json::json;
+
+#[test]
+fn explorer_sends_the_chosen_method_path_and_body() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(PUT)
+            .path("/pacientes/7")
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .json_body(json!({"edad": 35}));
+        then.status(200).json_body(json!({"id": 7, "edad": 35}));
+    });
+    let mut api = client_for(&server);
+    api.set_token(TOKEN);
+
+    let req = ExploreRequest {
+        method: Method::PUT,
+        path: "/pacientes/7".into(),
+        body: parse_body(r#" {"edad": 35} "#).unwrap(),
+    };
+    let out = send(&api, &req).unwrap();
+
+    mock.assert();
+    assert!(out.starts_with("200 OK\n"), "{}", out);
+    assert!(out.contains("\"edad\": 35"), "{}", out);
+}
+
+#[test]
+fn explorer_body_must_be_json_or_empty() {
+    assert_eq!(parse_body("   ").unwrap(), None);
+    assert!(parse_body("{edad: 35}").is_err());
+}
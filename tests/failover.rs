@@ -0,0 +1,72 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::api::ApiClient;
+use neumodiag_cli::config::parse_gateway_urls;
+
+// A gateway URL nothing listens on.
+fn dead_gateway() -> String {
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    format!("http://{}", addr)
+}
+
+#[test]
+fn requests_fail_over_to_the_next_gateway_and_stay_there() {
+    let secondary = MockServer::start();
+    let auth = secondary.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(200).json_body(auth_body());
+    });
+    let health = secondary.mock(|when, then| {
+        when.method(GET).path("/health");
+        then.status(200);
+    });
+    let primary = dead_gateway();
+    let api = ApiClient::new(primary.clone()).unwrap().with_fallback_urls(vec![secondary.base_url()]).unwrap();
+    assert_eq!(api.active_gateway_url(), primary);
+
+    let resp = api.login(&sample_auth()).expect("login through the secondary");
+
+    assert_eq!(resp.token, TOKEN);
+    auth.assert();
+    assert_eq!(api.active_gateway_url(), secondary.base_url());
+    // Clones share the choice, and later requests go straight there.
+    api.clone().health_check().expect("health");
+    health.assert();
+    assert_eq!(api.base_url(), primary);
+}
+
+#[test]
+fn failover_keeps_the_fallback_path_prefix() {
+    let secondary = MockServer::start();
+    let profile = secondary.mock(|when, then| {
+        when.method(GET).path("/api/profile").query_param("v", "1");
+        then.status(200).json_body(serde_json::json!({"nombre_completo": "Ana"}));
+    });
+    let api = ApiClient::new(dead_gateway())
+        .unwrap()
+        .with_fallback_urls(vec![format!("{}/api/", secondary.base_url())])
+        .unwrap();
+
+    let raw = api.request_with_query(reqwest::Method::GET, "/profile", &[("v", "1")], None).expect("profile");
+
+    assert_eq!(raw.status, 200);
+    profile.assert();
+}
+
+#[test]
+fn every_gateway_down_reports_the_connection_error() {
+    let api = ApiClient::new(dead_gateway()).unwrap().with_fallback_urls(vec![dead_gateway()]).unwrap();
+
+    let err = api.health_check().unwrap_err();
+
+    assert!(format!("{:#}", err).contains("health"), "{:#}", err);
+}
+
+#[test]
+fn gateway_lists_are_comma_separated() {
+    assert_eq!(parse_gateway_urls(" http://a:8080 , ,http://b:8080/api"), ["http://a:8080", "http://b:8080/api"]);
+    assert!(parse_gateway_urls("").is_empty());
+    assert!(ApiClient::new("http://a").unwrap().with_fallback_urls(vec!["no es una url".into()]).is_err());
+}
@@ -0,0 +1,77 @@
+use neumodiag_cli::api::{check_length, FieldLimits, RegisterRequest, Role};
+
+// A request whose every text field is exactly at its limit.
+fn at_limit(limits: &FieldLimits) -> RegisterRequest {
+    RegisterRequest {
+        nombre_completo: "n".repeat(limits.nombre_completo),
+        edad: 30,
+        rol: Role::Paciente,
+        identificacion: "1".repeat(limits.identificacion),
+        correo: format!("{}@x.co", "c".repeat(limits.correo - 5)),
+        contrasena: "p".repeat(limits.contrasena),
+        acepta_tratamiento_datos: true,
+    }
+}
+
+#[test]
+fn check_length_counts_characters_not_bytes() {
+    assert!(check_length("ñññ", 3).is_ok());
+    let err = check_length("ññññ", 3).unwrap_err();
+    assert_eq!(err, "Máximo 3 caracteres (se ingresaron 4).");
+    assert!(check_length("", 0).is_ok());
+}
+
+#[test]
+fn every_field_is_accepted_at_its_limit() {
+    let limits = FieldLimits::default();
+    assert!(at_limit(&limits).validate_lengths(&limits).is_ok());
+}
+
+type Grow = fn(&mut RegisterRequest);
+
+#[test]
+fn each_field_is_rejected_one_past_its_limit() {
+    let limits = FieldLimits::default();
+    let cases: [(&str, Grow); 4] = [
+        ("Nombre completo", |r| r.nombre_completo.push('n')),
+        ("Identificación", |r| r.identificacion.push('1')),
+        ("Correo electrónico", |r| r.correo.insert(0, 'c')),
+        ("Contraseña", |r| r.contrasena.push('p')),
+    ];
+    for (name, grow) in cases {
+        let mut req = at_limit(&limits);
+        grow(&mut req);
+        let err = req.validate_lengths(&limits).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("{}: Máximo", name)), "{}", err);
+    }
+}
+
+#[test]
+fn limits_can_be_overridden_from_the_environment() {
+    // Only this test touches these variables, so it cannot race the others.
+    std::env::set_var("NEUMODIAG_MAX_NOMBRE_LEN", "10");
+    std::env::set_var("NEUMODIAG_MAX_IDENTIFICACION_LEN", " 8 ");
+    std::env::set_var("NEUMODIAG_MAX_CORREO_LEN", "0");
+    std::env::set_var("NEUMODIAG_MAX_CONTRASENA_LEN", "mucho");
+    let limits = FieldLimits::from_env();
+    for key in [
+        "NEUMODIAG_MAX_NOMBRE_LEN",
+        "NEUMODIAG_MAX_IDENTIFICACION_LEN",
+        "NEUMODIAG_MAX_CORREO_LEN",
+        "NEUMODIAG_MAX_CONTRASENA_LEN",
+    ] {
+        std::env::remove_var(key);
+    }
+
+    assert_eq!(limits.nombre_completo, 10);
+    assert_eq!(limits.identificacion, 8);
+    // Zero and unparsable values keep the defaults.
+    let defaults = FieldLimits::default();
+    assert_eq!(limits.correo, defaults.correo);
+    assert_eq!(limits.contrasena, defaults.contrasena);
+
+    let mut req = at_limit(&limits);
+    assert!(req.validate_lengths(&limits).is_ok());
+    req.nombre_completo.push('n');
+    assert!(req.validate_lengths(&limits).is_err());
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::config::Config;
+use neumodiag_cli::health::{HealthCheck, HealthMethod, HealthMonitor, HealthStatus};
+use std::time::{Duration, Instant};
+
+#[test]
+fn status_follows_probe_results() {
+    use HealthStatus::*;
+
+    assert_eq!(HealthStatus::default(), Unknown);
+    assert_eq!(Unknown.next(true), Connected);
+    assert_eq!(Unknown.next(false), Disconnected);
+    assert_eq!(Connected.next(true), Connected);
+    // One failure after a good probe is not yet "sin conexión".
+    assert_eq!(Connected.next(false), Unknown);
+    assert_eq!(Connected.next(false).next(false), Disconnected);
+    assert_eq!(Disconnected.next(false), Disconnected);
+    assert_eq!(Disconnected.next(true), Connected);
+
+    assert_eq!(Connected.label(), "[conectado]");
+    assert_eq!(Disconnected.label(), "[sin conexión]");
+}
+
+fn wait_for(monitor: &HealthMonitor, wanted: HealthStatus) -> HealthStatus {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while monitor.status() != wanted && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    monitor.status()
+}
+
+#[test]
+fn monitor_reports_a_reachable_gateway_in_the_background() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/health");
+        then.status(200);
+    });
+
+    let monitor = HealthMonitor::start(&client_for(&server), Duration::from_secs(60));
+
+    assert_eq!(wait_for(&monitor, HealthStatus::Connected), HealthStatus::Connected);
+}
+
+#[test]
+fn probes_leave_the_last_correlation_id_alone() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(500).header("X-Request-Id", "fallo-del-usuario");
+    });
+    let probed = server.mock(|when, then| {
+        when.method(GET).path("/health");
+        then.status(200).header("X-Request-Id", "sonda");
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let dump = dir.path().join("dump.jsonl");
+    let api = client_for(&server).with_debug_dump(dump.clone());
+    api.login(&sample_auth()).unwrap_err();
+
+    let monitor = HealthMonitor::start(&api, Duration::from_secs(60));
+    assert_eq!(wait_for(&monitor, HealthStatus::Connected), HealthStatus::Connected);
+
+    probed.assert();
+    assert_eq!(api.last_correlation_id().as_deref(), Some("fallo-del-usuario"));
+    let dumped = std::fs::read_to_string(&dump).unwrap();
+    assert!(!dumped.contains("/health"), "{}", dumped);
+}
+
+#[test]
+fn monitor_reports_an_unreachable_gateway() {
+    // Nothing listens on a port taken from a listener that was closed.
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let api = neumodiag_cli::api::ApiClient::new(format!("http://{}", addr)).unwrap();
+
+    let monitor = HealthMonitor::start(&api, Duration::from_secs(60));
+
+    assert_eq!(wait_for(&monitor, HealthStatus::Disconnected), HealthStatus::Disconnected);
+}
+
+#[test]
+fn health_check_can_post_a_body_to_a_custom_path() {
+    let server = MockServer::start();
+    let ping = server.mock(|when, then| {
+        when.method(POST).path("/api/ping").json_body(serde_json::json!({"probe": true}));
+        then.status(204);
+    });
+    let config = Config::from_toml_str(
+        r#"
+        [health]
+        method = "POST"
+        path = "/api/ping"
+        body = { probe = true }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.health.method, HealthMethod::Post);
+
+    let api = client_for(&server).with_health_check(config.health).unwrap();
+
+    api.health_check().unwrap();
+    ping.assert();
+}
+
+#[test]
+fn health_check_accepts_only_the_configured_statuses() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/status");
+        then.status(503).body("draining");
+    });
+    let check = |expected: Vec<u16>| {
+        let health = HealthCheck { path: "/status".into(), expected_status: expected, ..HealthCheck::default() };
+        client_for(&server).with_health_check(health).unwrap().health_check()
+    };
+
+    // Default: any 2xx.
+    let err = check(Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("503"), "{err}");
+    check(vec![200, 503]).unwrap();
+    assert!(check(vec![200]).is_err());
+}
+
+#[test]
+fn invalid_health_settings_are_rejected() {
+    let api = neumodiag_cli::api::ApiClient::new("http://localhost:8080").unwrap();
+    let bad_path = HealthCheck { path: "health".into(), ..HealthCheck::default() };
+    assert!(api.clone().with_health_check(bad_path).is_err());
+    let bad_status = HealthCheck { expected_status: vec![42], ..HealthCheck::default() };
+    assert!(api.with_health_check(bad_status).is_err());
+    assert!(Config::from_toml_str("[health]\nmethod = \"DELETE\"\n").is_err());
+}
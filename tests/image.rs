@@ -0,0 +1,122 @@
+use neumodiag_cli::image::{
+    detect_file_mime, dialog_extensions, extension_for_mime, image_extensions, is_heic, mime_for_path, read_image_capped, sniff_mime,
+    ImagePolicy,
+};
+use std::path::Path;
+
+#[test]
+fn new_formats_map_to_their_mime_types() {
+    assert_eq!(mime_for_path(Path::new("rx.webp")), Some("image/webp"));
+    assert_eq!(mime_for_path(Path::new("IMG_0001.HEIC")), Some("image/heic"));
+    assert_eq!(mime_for_path(Path::new("scan.heif")), Some("image/heic"));
+    assert!(is_heic(Path::new("IMG_0001.heic")));
+    assert!(!is_heic(Path::new("rx.webp")));
+}
+
+#[test]
+fn existing_formats_keep_their_mime_types_and_others_are_rejected() {
+    assert_eq!(mime_for_path(Path::new("foto.jpg")), Some("image/jpeg"));
+    assert_eq!(mime_for_path(Path::new("foto.jpeg")), Some("image/jpeg"));
+    assert_eq!(mime_for_path(Path::new("foto.png")), Some("image/png"));
+    assert_eq!(mime_for_path(Path::new("informe.pdf")), None);
+    assert_eq!(mime_for_path(Path::new("sin_extension")), None);
+    for ext in ["webp", "heic", "heif"] {
+        assert!(image_extensions().contains(&ext), "{}", ext);
+    }
+}
+
+#[test]
+fn formats_are_sniffed_from_their_first_bytes() {
+    assert_eq!(sniff_mime(&[0xFF, 0xD8, 0xFF, 0xE0, 0, 0]), Some("image/jpeg"));
+    assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+    assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+    assert_eq!(sniff_mime(b"\0\0\0\x18ftypheic\0\0"), Some("image/heic"));
+    assert_eq!(sniff_mime(b"%PDF-1.7"), None);
+    assert_eq!(sniff_mime(&[]), None);
+    assert_eq!(extension_for_mime("image/png"), Some("png"));
+}
+
+#[test]
+fn images_over_the_cap_are_rejected_while_reading() {
+    let data = vec![0u8; 2 * 1024 * 1024 + 1];
+    let err = read_image_capped(&data[..], 2 * 1024 * 1024).unwrap_err().to_string();
+    assert!(err.contains("supera el tamaño máximo de 2 MB"), "{}", err);
+
+    assert_eq!(read_image_capped(&data[..10], 2 * 1024 * 1024).unwrap().len(), 10);
+    assert!(read_image_capped(&[][..], 10).is_err());
+}
+
+#[test]
+fn uppercase_extensions_are_accepted_by_the_dialog_and_the_checks() {
+    let exts = dialog_extensions();
+    for ext in ["jpg", "JPG", "jpeg", "JPEG", "png", "PNG"] {
+        assert!(exts.iter().any(|e| e == ext), "{}", ext);
+    }
+    assert_eq!(mime_for_path(Path::new("FOTO.JPG")), Some("image/jpeg"));
+    assert_eq!(mime_for_path(Path::new("Scan.Png")), Some("image/png"));
+}
+
+#[test]
+fn picked_files_are_checked_by_content() {
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("FOTO.JPG");
+    std::fs::write(&png, b"\x89PNG\r\n\x1a\nrest of the image").unwrap();
+    let no_ext = dir.path().join("captura");
+    std::fs::write(&no_ext, [0xFF, 0xD8, 0xFF, 0xE0, 1, 2, 3]).unwrap();
+    let text = dir.path().join("notas.txt");
+    std::fs::write(&text, "hola").unwrap();
+    let fake_jpg = dir.path().join("foto.JPG");
+    std::fs::write(&fake_jpg, "esto no es una imagen").unwrap();
+
+    // The content wins over a misleading extension.
+    assert_eq!(detect_file_mime(&png), Some("image/png"));
+    assert_eq!(detect_file_mime(&no_ext), Some("image/jpeg"));
+    assert_eq!(detect_file_mime(&text), None);
+    // An image extension alone is not enough, nor is a file that can't be read.
+    assert_eq!(detect_file_mime(&fake_jpg), None);
+    assert_eq!(detect_file_mime(&dir.path().join("falta.JPEG")), None);
+
+    let policy = ImagePolicy::default();
+    assert_eq!(policy.check_file(&png).unwrap(), "image/png");
+    let err = policy.check_file(&fake_jpg).unwrap_err().to_string();
+    assert!(err.contains("Formato no soportado"), "{}", err);
+    let err = policy.check_file(&dir.path().join("falta.jpg")).unwrap_err().to_string();
+    assert!(err.contains("No se pudo leer el archivo"), "{}", err);
+    assert!(policy.check_bytes("foto.jpg", b"hola").is_err());
+}
+
+#[test]
+fn configured_extensions_narrow_the_dialog_and_the_checks() {
+    let policy = ImagePolicy::new(&["JPG".to_string(), ".png".to_string()], 1).unwrap();
+    assert_eq!(policy.extensions(), ["jpg", "png"]);
+    assert_eq!(policy.dialog_extensions(), ["jpg", "JPG", "png", "PNG"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let png = dir.path().join("foto.png");
+    std::fs::write(&png, b"\x89PNG\r\n\x1a\nrest of the image").unwrap();
+    let webp = dir.path().join("foto.webp");
+    std::fs::write(&webp, b"RIFF\0\0\0\0WEBPVP8 ").unwrap();
+    // A listed extension doesn't let an unlisted format through.
+    let disguised = dir.path().join("foto.jpg");
+    std::fs::write(&disguised, b"RIFF\0\0\0\0WEBPVP8 ").unwrap();
+    let big = dir.path().join("grande.png");
+    std::fs::write(&big, [b"\x89PNG\r\n\x1a\n".as_slice(), &vec![0u8; 1024 * 1024]].concat()).unwrap();
+
+    assert_eq!(policy.check_file(&png).unwrap(), "image/png");
+    let err = policy.check_file(&webp).unwrap_err().to_string();
+    assert!(err.contains("Formato no soportado. Use una imagen jpg, png."), "{}", err);
+    assert!(policy.check_file(&disguised).is_err());
+    let err = policy.check_file(&big).unwrap_err().to_string();
+    assert!(err.contains("supera el tamaño máximo de 1 MB"), "{}", err);
+
+    assert_eq!(policy.check_bytes("imagen.png", b"\x89PNG\r\n\x1a\n..").unwrap(), "image/png");
+    assert!(policy.check_bytes("imagen.webp", b"RIFF\0\0\0\0WEBPVP8 ").is_err());
+}
+
+#[test]
+fn the_default_policy_accepts_every_format() {
+    let policy = ImagePolicy::default();
+    assert_eq!(policy.extensions(), image_extensions());
+    assert_eq!(policy.dialog_extensions(), dialog_extensions());
+    assert_eq!(policy.max_bytes(), 10 * 1024 * 1024);
+}
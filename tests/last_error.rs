@@ -0,0 +1,69 @@
+mod common;
+
+use common::{client_for, sample_auth};
+use httpmock::prelude::*;
+use neumodiag_cli::api::{ApiError, Method};
+use neumodiag_cli::last_error::ErrorStore;
+
+#[test]
+fn store_keeps_only_the_most_recent_error() {
+    let store = ErrorStore::new();
+    assert!(store.last().is_none());
+
+    store.record("Fallo la subida: 500", None, None);
+    store.record("Fallo al iniciar sesión: 503", None, Some("req-42".into()));
+
+    let last = store.last().expect("an error was recorded");
+    assert_eq!(last.message, "Fallo al iniciar sesión: 503");
+    assert_eq!(last.correlation_id.as_deref(), Some("req-42"));
+    let report = last.to_report();
+    assert!(report.contains("Fallo al iniciar sesión: 503"), "{}", report);
+    assert!(report.contains("ID de correlación: req-42"), "{}", report);
+}
+
+#[test]
+fn store_keeps_the_status_and_body_of_gateway_errors() {
+    let store = ErrorStore::new();
+    let err = anyhow::Error::new(ApiError {
+        label: "Upload".into(),
+        status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+        body: r#"{"error": "mantenimiento"}"#.into(),
+        summary: None,
+    });
+
+    store.record("Fallo la subida: mantenimiento", Some(&err), Some("req-7".into()));
+
+    let last = store.last().unwrap();
+    assert_eq!(last.status, Some(503));
+    assert_eq!(last.body.as_deref(), Some(r#"{"error": "mantenimiento"}"#));
+    assert_eq!(last.correlation_id.as_deref(), Some("req-7"));
+    let report = last.to_report();
+    assert!(report.contains("Estado HTTP: 503"), "{}", report);
+    assert!(report.contains("mantenimiento"), "{}", report);
+
+    // Other failures have no status or body.
+    store.record("No se pudo leer el historial", Some(&anyhow::anyhow!("disco lleno")), None);
+    let last = store.last().unwrap();
+    assert_eq!((last.status, last.body), (None, None));
+}
+
+#[test]
+fn client_remembers_the_correlation_id_of_the_last_response() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(500).header("X-Request-Id", "abc-123").body("boom");
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/verify");
+        then.status(500);
+    });
+    let api = client_for(&server);
+
+    api.login(&sample_auth()).unwrap_err();
+    assert_eq!(api.last_correlation_id().as_deref(), Some("abc-123"));
+
+    // A response without the header clears it.
+    api.request(Method::GET, "/verify", None).unwrap();
+    assert_eq!(api.last_correlation_id(), None);
+}
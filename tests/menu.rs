@@ -0,0 +1,230 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::api::ApiClient;
+use neumodiag_cli::backend::FakeBackend;
+use neumodiag_cli::prompt::{Output, Prompts};
+use neumodiag_cli::spinner;
+use neumodiag_cli::ui::{main_menu, UiOptions};
+use std::sync::Arc;
+
+fn scripted(answers: &[&str]) -> UiOptions {
+    UiOptions { prompts: Prompts::scripted(answers.iter().copied()), output: Output::buffer(), ..Default::default() }
+}
+
+#[test]
+fn scripted_menu_logs_in_uploads_and_logs_out() {
+    spinner::set_min_display_ms(0);
+    let dir = tempfile::tempdir().unwrap();
+    let picture = write_file(&dir, "perfil.jpg", b"\xFF\xD8\xFFfake-jpeg");
+    let backend = Arc::new(FakeBackend::new().with_user("Ana Pérez", "ana@example.com", "s3creta-larga"));
+    let api = ApiClient::new("http://gateway.invalid").unwrap().with_backend(backend.clone()).with_ephemeral(true);
+    let path = picture.display().to_string();
+
+    let opts = scripted(&[
+        "Iniciar sesión",
+        "Continuar",
+        "ana@example.com",
+        "s3creta-larga",
+        "Subir foto de perfil",
+        "Ingresar ruta manualmente",
+        &path,
+        "Cerrar sesión",
+        "Salir",
+    ]);
+    let output = opts.output.clone();
+    main_menu(api, opts).unwrap();
+
+    assert_eq!(backend.calls(), ["capabilities", "login ana@example.com", "upload perfil.jpg"]);
+    assert_eq!(backend.picture("ana@example.com").unwrap(), b"\xFF\xD8\xFFfake-jpeg");
+    let shown = output.contents();
+    for line in ["Sesión iniciada.", "Imagen de perfil cargada exitosamente.", "Sesión cerrada.", "Saliendo..."] {
+        assert!(shown.contains(line), "{}", shown);
+    }
+}
+
+#[test]
+fn scripted_menu_fails_when_the_script_runs_out() {
+    let server = MockServer::start();
+    let api = client_for(&server).with_ephemeral(true);
+
+    let err = main_menu(api, scripted(&["Iniciar sesión", "Continuar"])).unwrap_err().to_string();
+
+    assert!(err.contains("El guion no tiene más respuestas"), "{}", err);
+    assert!(err.contains("Correo electrónico"), "{}", err);
+}
+
+#[test]
+fn scripted_menu_rejects_entries_that_are_not_shown() {
+    let server = MockServer::start();
+    let api = client_for(&server).with_ephemeral(true);
+
+    // Logged out, the menu has no upload entry.
+    let err = main_menu(api, scripted(&["Subir foto de perfil"])).unwrap_err().to_string();
+
+    assert!(err.contains("'Subir foto de perfil', que no está entre las opciones"), "{}", err);
+}
+
+#[test]
+fn session_stays_usable_when_it_cannot_be_saved() {
+    spinner::set_min_display_ms(0);
+    let dir = tempfile::tempdir().unwrap();
+    let picture = write_file(&dir, "perfil.jpg", b"\xFF\xD8\xFFfake-jpeg");
+    // A file where the state folder should be: every write to it fails.
+    let not_a_dir = write_file(&dir, "no-es-carpeta", b"");
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(200).json_body(auth_body());
+    });
+    let upload = server.mock(|when, then| {
+        when.method(POST).path("/upload").header("authorization", format!("Bearer {}", TOKEN));
+        then.status(200);
+    });
+    let api = client_for(&server).with_state_dir(&not_a_dir);
+    let path = picture.display().to_string();
+    let answers = [
+        "Iniciar sesión",
+        "ana@example.com",
+        "s3creta-larga",
+        "Subir foto de perfil",
+        "Ingresar ruta manualmente",
+        &path,
+        "Salir",
+    ];
+    let opts = UiOptions { assume_yes: true, ..scripted(&answers) };
+
+    main_menu(api, opts).unwrap();
+
+    // Still logged in after the failed save: the upload entry was shown
+    // and the request carried the token.
+    upload.assert();
+}
+
+#[test]
+fn configured_default_role_skips_the_role_prompt() {
+    spinner::set_min_display_ms(0);
+    let server = MockServer::start();
+    let register = server.mock(|when, then| {
+        when.method(POST).path("/register").json_body_partial(r#"{"rol": "paciente"}"#);
+        then.status(201);
+    });
+    let api = client_for(&server).with_ephemeral(true);
+
+    // No answer for "Rol": the next answer goes to "Identificación".
+    let answers = [
+        "Registrarse",
+        "Ana Pérez",
+        "34",
+        "1020304050",
+        "ana@example.com",
+        "s3creta-larga",
+        "s3creta-larga",
+        "Sí",
+        "Salir",
+    ];
+    let opts = UiOptions {
+        assume_yes: true,
+        default_role: Some(neumodiag_cli::api::Role::Paciente),
+        hide_role_prompt: true,
+        ..scripted(&answers)
+    };
+    main_menu(api, opts).unwrap();
+
+    register.assert();
+}
+
+#[test]
+fn yes_lists_rejected_fields_without_asking_to_correct_them() {
+    spinner::set_min_display_ms(0);
+    let server = MockServer::start();
+    let register = server.mock(|when, then| {
+        when.method(POST).path("/register");
+        then.status(422).json_body(serde_json::json!({"errors": [{"field": "correo", "message": "ya registrado"}]}));
+    });
+    let api = client_for(&server).with_ephemeral(true);
+
+    // A re-prompt would take "Salir" as its answer and fail the script.
+    let answers = [
+        "Registrarse",
+        "Ana Pérez",
+        "34",
+        "1020304050",
+        "ana@example.com",
+        "s3creta-larga",
+        "s3creta-larga",
+        "Sí",
+        "Salir",
+    ];
+    let opts = UiOptions {
+        assume_yes: true,
+        default_role: Some(neumodiag_cli::api::Role::Paciente),
+        hide_role_prompt: true,
+        ..scripted(&answers)
+    };
+    main_menu(api, opts).unwrap();
+
+    assert_eq!(register.hits(), 1);
+}
+
+#[test]
+fn session_is_restored_after_a_recent_unclean_exit() {
+    use neumodiag_cli::api::TokenMeta;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let token = jwt(serde_json::json!({"nombre_completo": "Ana Pérez", "exp": now + 3_600}));
+    let server = MockServer::start();
+    let run = |last_seen: u64, answers: &[&str]| {
+        let dir = tempfile::tempdir().unwrap();
+        let api = client_for(&server).with_state_dir(dir.path());
+        // Saved without a clean exit, as after a crash.
+        api.persist_token_with_meta(&token, TokenMeta { last_seen: Some(last_seen), ..TokenMeta::new(true) }).unwrap();
+        let opts = UiOptions { unclean_grace: Some(Duration::from_secs(300)), ..scripted(answers) };
+        main_menu(api, opts)
+    };
+
+    // Seen two minutes ago: logged in, so "Cerrar sesión" is offered.
+    run(now - 120, &["Cerrar sesión", "No", "Salir"]).unwrap();
+
+    // Seen an hour ago: past the grace, the user must log in again.
+    let err = run(now - 3_600, &["Cerrar sesión"]).unwrap_err().to_string();
+    assert!(err.contains("'Cerrar sesión', que no está entre las opciones"), "{}", err);
+}
+
+#[test]
+fn download_asks_before_replacing_an_existing_picture() {
+    spinner::set_min_display_ms(0);
+    let dir = tempfile::tempdir().unwrap();
+    let existing = write_file(&dir, "foto.png", b"mi foto");
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(200).json_body(auth_body());
+    });
+    let download = server.mock(|when, then| {
+        when.method(GET).path("/profile/picture");
+        then.status(200).header("content-type", "image/png").body(b"\x89PNG\r\n\x1a\n-nueva-");
+    });
+    let api = client_for(&server).with_ephemeral(true);
+    let dest = dir.path().join("foto").display().to_string();
+
+    let opts = scripted(&[
+        "Iniciar sesión",
+        "Continuar",
+        "ana@example.com",
+        "s3creta-larga",
+        "Descargar mi foto",
+        &dest,
+        "No",
+        "Descargar mi foto",
+        &dest,
+        "Sí",
+        "Salir",
+    ]);
+    main_menu(api, opts).unwrap();
+
+    download.assert_hits(1);
+    assert_eq!(std::fs::read(existing).unwrap(), b"\x89PNG\r\n\x1a\n-nueva-");
+}
@@ -0,0 +1,58 @@
+use std::process::Command;
+
+// Runs the real binary so the printed output and the environment lookup
+// are what a user would see.
+fn paths(dir: &std::path::Path, args: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_neumodiag-cli"))
+        .arg("paths")
+        .args(args)
+        .env("NEUMODIAG_CONFIG_DIR", dir)
+        .env("API_GATEWAY_URL", "http://127.0.0.1:9")
+        .output()
+        .expect("running neumodiag-cli");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn json_paths_follow_the_config_dir_override() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&paths(dir.path(), &["--json"])).unwrap();
+
+    let path = |key: &str| std::path::PathBuf::from(json[key].as_str().unwrap());
+    assert_eq!(path("project_dir"), dir.path());
+    for key in ["session_file", "legacy_token_file", "legacy_meta_file", "config_file", "audit_log", "usage_file"] {
+        assert_eq!(path(key).parent().unwrap(), dir.path(), "{}", key);
+    }
+    assert_eq!(path("config_file").file_name().unwrap(), "neumodiag.toml");
+    assert_eq!(json["token_store"], "file");
+    assert!(json["cache_dir"].is_null());
+    // Listing paths creates nothing.
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn text_paths_name_each_file() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let text = paths(dir.path(), &[]);
+
+    let first = text.lines().next().unwrap();
+    assert!(first.starts_with("Carpeta de datos:") && first.ends_with(&dir.path().display().to_string()), "{}", text);
+    assert!(text.contains(&dir.path().join("neumodiag.toml").display().to_string()), "{}", text);
+    assert!(text.contains("Caché:"), "{}", text);
+}
+
+#[test]
+fn explicit_config_path_and_memory_only_sessions_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("otra.toml");
+    std::fs::write(&config, "").unwrap();
+
+    let out = paths(dir.path(), &["--json", "--config-path", config.to_str().unwrap(), "--ephemeral"]);
+    let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+
+    assert_eq!(json["config_file"], config.to_str().unwrap());
+    assert_eq!(json["token_store"], "none");
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::api::Role;
+use neumodiag_cli::profile::{greeting_name_from_jwt, Profile};
+use serde_json::json;
+
+#[test]
+fn from_jwt_reads_claims() {
+    let token = jwt(json!({"nombre_completo": "Ana Pérez", "rol": "paciente", "correo": "ana@example.com"}));
+
+    let p = Profile::from_jwt(&token).unwrap();
+
+    assert_eq!(p.nombre_completo.as_deref(), Some("Ana Pérez"));
+    assert_eq!(p.rol, Some(Role::Paciente));
+    assert_eq!(p.identificacion, None);
+    assert!(Profile::from_jwt("not-a-jwt").is_none());
+}
+
+#[test]
+fn merge_prefers_server_and_keeps_single_source_fields() {
+    let server = Profile {
+        nombre_completo: Some("Ana María Pérez".into()),
+        identificacion: Some("1020304050".into()),
+        ..Profile::default()
+    };
+    let token = Profile {
+        nombre_completo: Some("Ana Pérez".into()),
+        rol: Some(Role::Paciente),
+        ..Profile::default()
+    };
+
+    let merged = Profile::merge(server, token);
+
+    assert_eq!(merged.nombre_completo.as_deref(), Some("Ana María Pérez"));
+    assert_eq!(merged.identificacion.as_deref(), Some("1020304050"));
+    assert_eq!(merged.rol, Some(Role::Paciente));
+    assert_eq!(merged.correo, None);
+}
+
+#[test]
+fn get_profile_sends_token_and_accepts_short_name() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/profile").header("authorization", format!("Bearer {}", TOKEN));
+        then.status(200).json_body(json!({"nombre": "Ana Pérez", "edad": 34}));
+    });
+    let mut api = client_for(&server);
+    api.set_token(TOKEN);
+
+    let p = api.get_profile().unwrap();
+
+    mock.assert();
+    assert_eq!(p.nombre_completo.as_deref(), Some("Ana Pérez"));
+    assert_eq!(p.edad, Some(34));
+}
+
+#[test]
+fn rol_claim_may_be_a_string_or_a_list() {
+    use neumodiag_cli::profile::token_roles;
+
+    let single = jwt(json!({"rol": "paciente"}));
+    assert_eq!(token_roles(&single), [Role::Paciente]);
+    assert_eq!(Profile::from_jwt(&single).unwrap().rol, Some(Role::Paciente));
+
+    let both = jwt(json!({"nombre_completo": "Ana Pérez", "rol": ["doctor", "paciente"]}));
+    assert_eq!(token_roles(&both), [Role::Doctor, Role::Paciente]);
+    let p = Profile::from_jwt(&both).expect("a role list must not break decoding");
+    assert_eq!(p.rol, Some(Role::Doctor));
+    assert_eq!(p.nombre_completo.as_deref(), Some("Ana Pérez"));
+
+    assert!(token_roles(&jwt(json!({"correo": "ana@example.com"}))).is_empty());
+    assert!(token_roles(&jwt(json!({"rol": ["", 3]}))).is_empty());
+}
+
+#[test]
+fn unknown_roles_are_read_as_unknown() {
+    use neumodiag_cli::profile::token_roles;
+
+    let claims = jwt(json!({"nombre_completo": "Ana Pérez", "rol": ["enfermera", "Paciente"]}));
+    assert_eq!(token_roles(&claims), [Role::Unknown, Role::Paciente]);
+    assert_eq!(Profile::from_jwt(&claims).unwrap().rol, Some(Role::Unknown));
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/profile");
+        then.status(200).json_body(json!({"nombre": "Ana Pérez", "rol": "administrador"}));
+    });
+    let mut api = client_for(&server);
+    api.set_token(TOKEN);
+    let p = api.get_profile().expect("an unknown role must not break the profile");
+    assert_eq!(p.rol, Some(Role::Unknown));
+    assert_eq!(p.nombre_completo.as_deref(), Some("Ana Pérez"));
+
+    assert_eq!(serde_json::from_str::<Role>(r#""administrador""#).unwrap(), Role::Unknown);
+    assert!("administrador".parse::<Role>().is_err());
+}
+
+#[test]
+fn greeting_name_prefers_the_full_name() {
+    let token = jwt(json!({"nombre_completo": "Ana Pérez", "nombre": "Ana", "sub": "u-1"}));
+    assert_eq!(greeting_name_from_jwt(&token), "Ana Pérez");
+}
+
+#[test]
+fn greeting_name_falls_back_to_nombre() {
+    let token = jwt(json!({"nombre": "Ana", "sub": "u-1"}));
+    assert_eq!(greeting_name_from_jwt(&token), "Ana");
+}
+
+#[test]
+fn greeting_name_falls_back_to_sub_then_email() {
+    assert_eq!(greeting_name_from_jwt(&jwt(json!({"sub": "ana.perez", "email": "ana@example.com"}))), "ana.perez");
+    assert_eq!(greeting_name_from_jwt(&jwt(json!({"nombre": "  ", "email": "ana@example.com"}))), "ana@example.com");
+    assert_eq!(greeting_name_from_jwt(&jwt(json!({"correo": "ana@example.com"}))), "ana@example.com");
+}
+
+#[test]
+fn greeting_name_defaults_to_usuario() {
+    assert_eq!(greeting_name_from_jwt(&jwt(json!({"rol": "paciente", "sub": 42}))), "usuario");
+    assert_eq!(greeting_name_from_jwt("not-a-jwt"), "usuario");
+}
+
+#[test]
+fn an_unchanged_profile_edit_is_not_sent() {
+    use neumodiag_cli::profile::{ProfilePatch, NO_PROFILE_CHANGES_MSG};
+    use neumodiag_cli::terminal::PlainTerminal;
+    use neumodiag_cli::ui::save_profile_changes;
+
+    let server = MockServer::start();
+    let patch_mock = server.mock(|when, then| {
+        when.method(httpmock::Method::PATCH).path("/profile").json_body(json!({"correo": "ana.perez@example.com"}));
+        then.status(200);
+    });
+    let mut api = client_for(&server);
+    api.set_token(TOKEN);
+
+    let current = Profile { nombre_completo: Some("Ana Pérez".into()), correo: Some("ana@example.com".into()), ..Profile::default() };
+    assert!(ProfilePatch::default().is_empty());
+    assert!(ProfilePatch::diff(&current, &current).is_empty());
+    assert_eq!(save_profile_changes(&api, &ProfilePatch::default(), &mut PlainTerminal::new(Vec::new())).unwrap(), NO_PROFILE_CHANGES_MSG);
+    patch_mock.assert_hits(0);
+
+    let edited = Profile { correo: Some("ana.perez@example.com".into()), ..current.clone() };
+    let patch = ProfilePatch::diff(&current, &edited);
+    assert_eq!(patch, ProfilePatch { correo: Some("ana.perez@example.com".into()), ..ProfilePatch::default() });
+    assert_eq!(save_profile_changes(&api, &patch, &mut PlainTerminal::new(Vec::new())).unwrap(), "Perfil actualizado.");
+    patch_mock.assert_hits(1);
+}
+
+#[test]
+fn greeting_styles_shape_the_name() {
+    use neumodiag_cli::profile::{format_greeting, GreetingStyle};
+
+    let name = "Ana María Pérez";
+    assert_eq!(format_greeting(name, Some("doctor"), GreetingStyle::Full), "Ana María Pérez");
+    assert_eq!(format_greeting(name, Some("paciente"), GreetingStyle::First), "Ana");
+    assert_eq!(format_greeting(name, Some("doctor"), GreetingStyle::Formal), "Dr./Dra. Pérez");
+    assert_eq!(format_greeting(name, Some("paciente, doctor"), GreetingStyle::Formal), "Dr./Dra. Pérez");
+    // Only doctors get the title.
+    assert_eq!(format_greeting(name, Some("paciente"), GreetingStyle::Formal), "Ana María Pérez");
+    assert_eq!(format_greeting(name, None, GreetingStyle::Formal), "Ana María Pérez");
+    assert_eq!(format_greeting("usuario", None, GreetingStyle::First), "usuario");
+}
+
+#[test]
+fn greeting_from_a_token_uses_its_role() {
+    use neumodiag_cli::profile::{greeting_from_jwt, GreetingStyle};
+
+    let doctor = jwt(json!({"nombre_completo": "Luis Gómez", "rol": ["doctor", "paciente"]}));
+    assert_eq!(greeting_from_jwt(&doctor, GreetingStyle::Formal), "Dr./Dra. Gómez");
+    assert_eq!(greeting_from_jwt(&doctor, GreetingStyle::First), "Luis");
+    assert_eq!(greeting_from_jwt(&doctor, GreetingStyle::Full), "Luis Gómez");
+    assert_eq!(greeting_from_jwt("not-a-jwt", GreetingStyle::Formal), "usuario");
+}
@@ -0,0 +1,78 @@
+use neumodiag_cli::api::locate_project_dir;
+use std::io;
+use std::path::PathBuf;
+
+fn exe_unavailable() -> io::Result<PathBuf> {
+    Err(io::Error::new(io::ErrorKind::NotFound, "no /proc"))
+}
+
+#[test]
+fn override_wins_even_when_exe_lookup_fails() {
+    let dir = locate_project_dir(
+        Some(PathBuf::from("/srv/neumodiag")),
+        Some(PathBuf::from("/src/cli-front-end")),
+        exe_unavailable(),
+        Err(io::Error::other("cwd gone")),
+    )
+    .unwrap();
+
+    assert_eq!(dir, PathBuf::from("/srv/neumodiag"));
+}
+
+#[test]
+fn failed_exe_lookup_falls_back_to_cwd_or_a_typed_error() {
+    let cwd = locate_project_dir(None, None, exe_unavailable(), Ok(PathBuf::from("/home/ana"))).unwrap();
+    assert_eq!(cwd, PathBuf::from("/home/ana"));
+
+    let err = locate_project_dir(None, None, exe_unavailable(), Err(io::Error::other("cwd gone"))).unwrap_err();
+    assert!(err.to_string().contains("NEUMODIAG_CONFIG_DIR"), "{}", err);
+}
+
+#[test]
+fn exe_lookup_finds_the_nearest_cargo_toml() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("Cargo.toml"), "").unwrap();
+    let exe = root.path().join("target").join("debug").join("neumodiag-cli");
+
+    let dir = locate_project_dir(None, None, Ok(exe), Ok(PathBuf::from("/elsewhere"))).unwrap();
+
+    assert_eq!(dir, root.path());
+}
+
+#[test]
+fn exe_lookup_skips_a_workspace_root_for_the_crate_folder() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("Cargo.toml"), "[workspace]\nmembers = [\"otro\", \"cli-front-end\"]\n").unwrap();
+    let krate = root.path().join("cli-front-end");
+    std::fs::create_dir_all(&krate).unwrap();
+    std::fs::write(krate.join("Cargo.toml"), "[package]\nname = \"neumodiag-cli\"\n").unwrap();
+    let other = root.path().join("otro");
+    std::fs::create_dir_all(&other).unwrap();
+    std::fs::write(other.join("Cargo.toml"), "[package]\nname = \"otro\"\n").unwrap();
+    // Workspace builds put the executable under the root's `target`.
+    let exe = root.path().join("target").join("debug").join("neumodiag-cli");
+
+    let dir = locate_project_dir(None, None, Ok(exe), Ok(PathBuf::from("/elsewhere"))).unwrap();
+
+    assert_eq!(dir, krate);
+}
+
+#[test]
+fn exe_lookup_walks_past_nested_manifests_to_the_crate() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("Cargo.toml"), "[package]\nname = \"neumodiag-cli\"\n").unwrap();
+    // A nested workspace (e.g. vendored tools) between the exe and the crate.
+    let nested = root.path().join("herramientas");
+    std::fs::create_dir_all(nested.join("crates").join("gen")).unwrap();
+    std::fs::write(nested.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n").unwrap();
+    std::fs::write(nested.join("crates").join("gen").join("Cargo.toml"), "[package]\nname = \"gen\"\n").unwrap();
+    let exe = nested.join("target").join("release").join("neumodiag-cli");
+
+    let dir = locate_project_dir(None, None, Ok(exe.clone()), Ok(PathBuf::from("/elsewhere"))).unwrap();
+    assert_eq!(dir, root.path());
+
+    // Without this crate's manifest anywhere, the nearest one is used.
+    std::fs::write(root.path().join("Cargo.toml"), "[package]\nname = \"otro\"\n").unwrap();
+    let dir = locate_project_dir(None, None, Ok(exe), Ok(PathBuf::from("/elsewhere"))).unwrap();
+    assert_eq!(dir, nested);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::query::QueryParams;
+use serde_json::json;
+
+#[test]
+fn unset_params_are_left_out() {
+    assert_eq!(QueryParams::new().to_query_string(), "");
+    assert_eq!(QueryParams::new().per_page(25).to_query_string(), "per_page=25");
+    assert_eq!(QueryParams::new().search("   ").to_query_string(), "");
+}
+
+#[test]
+fn params_serialize_in_a_fixed_order() {
+    let params = QueryParams::new().filter("rol", "medico").search("ana").per_page(10).page(2);
+
+    assert_eq!(params.to_query_string(), "page=2&per_page=10&search=ana&rol=medico");
+}
+
+#[test]
+fn values_and_keys_are_percent_encoded() {
+    let params = QueryParams::new().search("Ana Pérez & co").filter("correo", "a+b@x.com").filter("año=", "1/2");
+
+    assert_eq!(params.to_query_string(), "search=Ana+P%C3%A9rez+%26+co&correo=a%2Bb%40x.com&a%C3%B1o%3D=1%2F2");
+}
+
+#[test]
+fn setting_a_filter_again_replaces_it() {
+    let params = QueryParams::new().filter("rol", "paciente").filter("activo", "true").filter("rol", "medico");
+
+    assert_eq!(params.pairs(), [("rol".to_string(), "medico".to_string()), ("activo".to_string(), "true".to_string())]);
+}
+
+#[test]
+fn list_users_sends_the_params_as_query_string() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/users").query_param("page", "3").query_param("search", "Ana Pérez").query_param("rol", "medico");
+        then.status(200).json_body(json!([{"correo": "ana@example.com"}]));
+    });
+    let params = QueryParams::new().page(3).search("Ana Pérez").filter("rol", "medico");
+
+    let users = client_for(&server).list_users(&params).unwrap();
+
+    mock.assert();
+    assert_eq!(users, json!([{"correo": "ana@example.com"}]));
+}
+
+#[test]
+fn list_users_failure_includes_status() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/users");
+        then.status(403).body("prohibido");
+    });
+
+    let err = client_for(&server).list_users(&QueryParams::new()).unwrap_err().to_string();
+
+    assert!(err.contains("403"), "{}", err);
+}
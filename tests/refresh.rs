@@ -0,0 +1,59 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::prompt::Output;
+use neumodiag_cli::ui::refresh_session;
+use serde_json::json;
+
+const NEW_TOKEN: &str = "nuevo.token.jwt";
+
+// One test walks through every outcome: the saved token lives in the
+// project folder, which is resolved through a process-wide variable.
+#[test]
+fn refresh_session_updates_or_clears_the_saved_session() {
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_var("CARGO_MANIFEST_DIR", dir.path());
+    let server = MockServer::start();
+
+    // Success: the saved copy is replaced and keeps its meta flags.
+    let mut api = client_for(&server);
+    api.set_token(TOKEN);
+    api.persist_token_to_project(TOKEN, true).unwrap();
+    let mut ok = server.mock(|when, then| {
+        when.method(POST).path("/refresh").header("authorization", format!("Bearer {}", TOKEN));
+        then.status(200).json_body(json!({"token": NEW_TOKEN}));
+    });
+    assert!(refresh_session(&Output::default(), &mut api));
+    ok.assert();
+    ok.delete();
+    assert_eq!(api.load_token_from_project().unwrap().as_deref(), Some(NEW_TOKEN));
+    let meta = api.load_token_meta().unwrap().unwrap();
+    assert!(meta.persist);
+
+    // A gateway outage is not a verdict on the token: keep the session.
+    let mut down = server.mock(|when, then| {
+        when.method(POST).path("/refresh");
+        then.status(503).body("mantenimiento");
+    });
+    assert!(!refresh_session(&Output::default(), &mut api));
+    down.delete();
+    assert!(api.has_token());
+    assert_eq!(api.load_token_from_project().unwrap().as_deref(), Some(NEW_TOKEN));
+
+    // An unreachable gateway keeps it too.
+    let mut offline = neumodiag_cli::api::ApiClient::new("http://127.0.0.1:9").unwrap();
+    offline.set_token(NEW_TOKEN);
+    assert!(!refresh_session(&Output::default(), &mut offline));
+    assert!(offline.has_token());
+    assert!(api.load_token_from_project().unwrap().is_some());
+
+    // The server rejecting the token clears the session everywhere.
+    server.mock(|when, then| {
+        when.method(POST).path("/refresh");
+        then.status(401).body("token expirado");
+    });
+    assert!(!refresh_session(&Output::default(), &mut api));
+    assert!(!api.has_token());
+    assert!(api.load_token_from_project().unwrap().is_none());
+}
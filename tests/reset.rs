@@ -0,0 +1,120 @@
+use neumodiag_cli::api::{ApiClient, TOKEN_FILE_NAME};
+use neumodiag_cli::state::STATE_FILE_NAME;
+use neumodiag_cli::cli::Command;
+use neumodiag_cli::commands;
+use neumodiag_cli::config::Config;
+use neumodiag_cli::paths::StoragePaths;
+use neumodiag_cli::prompt::Prompts;
+use neumodiag_cli::reset::{local_data_files, remove_data_files};
+use neumodiag_cli::ui::{logout, reset_local_data, UiOptions};
+
+#[test]
+fn reset_removes_every_app_file_and_nothing_else() {
+    let dir = tempfile::tempdir().unwrap();
+    let api = ApiClient::new("http://localhost:1").unwrap().with_state_dir(dir.path());
+    api.persist_token_to_project("header.payload.signature", true).unwrap();
+    api.set_clean_exit_meta(true).unwrap();
+    std::fs::write(dir.path().join("notas.txt"), "no es del CLI").unwrap();
+    // A leftover file from the old two-file layout is removed too.
+    std::fs::write(dir.path().join(TOKEN_FILE_NAME), "viejo").unwrap();
+
+    let files = local_data_files(&StoragePaths::resolve(&api, None).unwrap());
+    let removed = remove_data_files(&files).unwrap();
+
+    assert_eq!(removed.len(), 2);
+    let left: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(left, ["notas.txt"]);
+    assert!(!left.iter().any(|f| f == STATE_FILE_NAME || f == TOKEN_FILE_NAME));
+
+    // A second reset finds nothing to do.
+    assert!(remove_data_files(&files).unwrap().is_empty());
+}
+
+fn saved_session(dir: &tempfile::TempDir) -> ApiClient {
+    let mut api = ApiClient::new("http://localhost:1").unwrap().with_state_dir(dir.path());
+    api.persist_token_to_project("header.payload.signature", true).unwrap();
+    api.set_token("header.payload.signature");
+    api
+}
+
+fn opts(force: bool) -> UiOptions {
+    UiOptions { force, ..Default::default() }
+}
+
+#[test]
+fn logout_keeps_the_saved_session_until_confirmed() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = saved_session(&dir);
+
+    let kept = logout(&mut api, &opts(false), || Ok(false)).unwrap();
+
+    assert!(kept);
+    assert!(!api.has_token());
+    assert!(dir.path().join(STATE_FILE_NAME).exists());
+
+    let kept = logout(&mut api, &opts(false), || Ok(true)).unwrap();
+
+    assert!(!kept);
+    assert!(api.load_token_from_project().unwrap().is_none());
+}
+
+#[test]
+fn force_deletes_without_asking() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = saved_session(&dir);
+
+    let kept = logout(&mut api, &opts(true), || panic!("--force must not ask")).unwrap();
+
+    assert!(!kept);
+    assert!(api.load_token_from_project().unwrap().is_none());
+}
+
+#[test]
+fn logout_without_a_saved_session_does_not_ask() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = ApiClient::new("http://localhost:1").unwrap().with_state_dir(dir.path());
+    api.set_token("header.payload.signature");
+
+    assert!(!logout(&mut api, &opts(false), || panic!("nothing to confirm")).unwrap());
+}
+
+#[test]
+fn reset_command_needs_confirmation_unless_forced() {
+    let dir = tempfile::tempdir().unwrap();
+    let api = saved_session(&dir);
+
+    // Nobody types the confirmation word: nothing is deleted.
+    let _ = commands::run(api.clone(), Command::Reset, &opts(false));
+    assert!(dir.path().join(STATE_FILE_NAME).exists());
+
+    commands::run(api, Command::Reset, &opts(true)).unwrap();
+    assert!(!dir.path().join(STATE_FILE_NAME).exists());
+}
+
+#[test]
+fn reset_offers_the_config_file_given_with_config_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = saved_session(&dir);
+    let config = dir.path().join("otra-config.toml");
+    std::fs::write(&config, "telemetry = false").unwrap();
+    let opts = UiOptions {
+        config_path: Some(config.clone()),
+        prompts: Prompts::scripted(["Sí", "BORRAR"]),
+        ..Default::default()
+    };
+
+    assert!(reset_local_data(&mut api, &opts).unwrap());
+
+    assert!(!config.exists());
+    assert!(!dir.path().join(STATE_FILE_NAME).exists());
+}
+
+#[test]
+fn confirm_destructive_is_on_unless_configured_off() {
+    assert!(Config::default().confirm_destructive());
+    assert!(Config::from_toml_str("confirm_destructive = true").unwrap().confirm_destructive());
+    assert!(!Config::from_toml_str("confirm_destructive = false").unwrap().confirm_destructive());
+}
@@ -0,0 +1,35 @@
+use neumodiag_cli::session::{export_session, import_session, SessionBundle};
+use serde_json::json;
+
+fn bundle() -> SessionBundle {
+    SessionBundle {
+        token: "header.payload.signature".into(),
+        meta: json!({"persist": true, "clean_exit": false}),
+    }
+}
+
+#[test]
+fn export_then_import_round_trips() {
+    let blob = export_session(&bundle(), "correcta caballo batería").unwrap();
+
+    assert!(!blob.contains("header.payload.signature"));
+    let back = import_session(&blob, "correcta caballo batería").unwrap();
+    assert_eq!(back, bundle());
+}
+
+#[test]
+fn import_with_wrong_passphrase_fails() {
+    let blob = export_session(&bundle(), "correcta caballo batería").unwrap();
+
+    let err = import_session(&blob, "otra frase").unwrap_err().to_string();
+    assert!(err.contains("Frase de paso incorrecta"), "{}", err);
+}
+
+#[test]
+fn bundles_without_persist_are_imported_as_remembered_sessions() {
+    let with = |meta| SessionBundle { token: "header.payload.signature".into(), meta };
+
+    assert!(with(json!({"clean_exit": true})).token_meta().unwrap().persist);
+    assert!(with(serde_json::Value::Null).token_meta().unwrap().persist);
+    assert!(!with(json!({"persist": false})).token_meta().unwrap().persist);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::*;
+use neumodiag_cli::api::{ApiClient, RememberFor, SessionState};
+use serde_json::json;
+
+const NOW: u64 = 1_700_000_000;
+
+fn client(dir: &tempfile::TempDir) -> ApiClient {
+    ApiClient::new("http://localhost:8080").unwrap().with_state_dir(dir.path())
+}
+
+#[test]
+fn no_token_is_logged_out() {
+    let dir = tempfile::tempdir().unwrap();
+
+    assert_eq!(client(&dir).session_state_at(NOW), SessionState::LoggedOut);
+    assert_eq!(SessionState::from_token(Some("  "), NOW), SessionState::LoggedOut);
+}
+
+#[test]
+fn valid_token_reports_name_role_and_expiry() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = client(&dir);
+    api.set_token(&jwt(json!({"nombre_completo": "Ana Pérez", "rol": "paciente", "exp": NOW + 60})));
+
+    let expected = SessionState::LoggedIn {
+        name: Some("Ana Pérez".into()),
+        role: Some("paciente".into()),
+        expires_at: Some(NOW + 60),
+    };
+    assert_eq!(api.session_state_at(NOW), expected);
+}
+
+#[test]
+fn chosen_role_replaces_the_claimed_roles() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = client(&dir);
+    api.set_token(&jwt(json!({"rol": ["doctor", "paciente"]})));
+    api.set_active_role(Some("doctor"));
+
+    match api.session_state_at(NOW) {
+        SessionState::LoggedIn { role, expires_at, .. } => {
+            assert_eq!(role.as_deref(), Some("doctor"));
+            assert_eq!(expires_at, None);
+        }
+        other => panic!("unexpected state {:?}", other),
+    }
+}
+
+#[test]
+fn past_exp_is_expired() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = client(&dir);
+    api.set_token(&jwt(json!({"nombre_completo": "Ana Pérez", "exp": NOW})));
+
+    assert_eq!(api.session_state_at(NOW), SessionState::Expired);
+}
+
+#[test]
+fn opaque_token_is_logged_in_without_details() {
+    let expected = SessionState::LoggedIn { name: None, role: None, expires_at: None };
+
+    assert_eq!(SessionState::from_token(Some("opaque-token"), NOW), expected);
+}
+
+#[test]
+fn saved_token_is_used_when_none_is_in_memory() {
+    let dir = tempfile::tempdir().unwrap();
+    let api = client(&dir);
+    api.persist_token_with_meta(&jwt(json!({"nombre": "Ana"})), RememberFor::Days(1).meta(NOW)).unwrap();
+
+    assert!(matches!(api.session_state_at(NOW + 60), SessionState::LoggedIn { name: Some(n), .. } if n == "Ana"));
+    // The one-day remember window has passed.
+    assert_eq!(api.session_state_at(NOW + 2 * 86_400), SessionState::Expired);
+    // Ephemeral clients never look at the saved session.
+    assert_eq!(client(&dir).with_ephemeral(true).session_state_at(NOW + 60), SessionState::LoggedOut);
+}
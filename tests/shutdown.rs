@@ -0,0 +1,45 @@
+use neumodiag_cli::api::ApiClient;
+use neumodiag_cli::shutdown;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const TOKEN: &str = "header.eyJub21icmVfY29tcGxldG8iOiJBbmEgUGVyZXoifQ.signature";
+
+#[test]
+fn sigterm_leaves_consistent_token_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let api = ApiClient::new("http://localhost:8081".to_string()).unwrap().with_state_dir(dir.path());
+    api.persist_token_to_project(TOKEN, true).unwrap();
+
+    // Keep rewriting the session files, as a login racing the signal would.
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let (api, stop) = (api.clone(), Arc::clone(&stop));
+        thread::spawn(move || {
+            while !stop.load(Ordering::SeqCst) {
+                api.persist_token_to_project(TOKEN, true).unwrap();
+            }
+        })
+    };
+    thread::sleep(Duration::from_millis(50));
+
+    shutdown::install_handler().unwrap();
+    #[cfg(unix)]
+    signal_hook::low_level::raise(signal_hook::consts::SIGTERM).unwrap();
+    #[cfg(not(unix))]
+    shutdown::request();
+    assert!(shutdown::requested());
+
+    let guard = shutdown::prepare_exit(&api, true);
+    let meta = api.load_token_meta().unwrap().expect("meta file");
+    assert!(meta.clean_exit);
+    assert!(meta.persist);
+    assert_eq!(api.load_token_from_project().unwrap().as_deref(), Some(TOKEN));
+    assert!(!dir.path().join(".neumodiag_state.json.tmp").exists());
+
+    drop(guard);
+    stop.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::*;
+use httpmock::prelude::*;
+use neumodiag_cli::signing::{hmac_signature, SIGNATURE_HEADER, SIGNATURE_TIMESTAMP_HEADER};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const SECRET: &str = "s3cret";
+
+#[test]
+fn signature_matches_a_known_value() {
+    let body = br#"{"correo":"ana@example.com"}"#;
+
+    assert_eq!(
+        hmac_signature(SECRET.as_bytes(), 1_700_000_000, "POST", "/auth", body),
+        "1a452511348cff07fbf1af88fd6fee845d116fd6987ed8f1be9686e8053ce8cf"
+    );
+    // Any change to the secret or the request changes the signature.
+    let base = hmac_signature(SECRET.as_bytes(), 1_700_000_000, "POST", "/auth", body);
+    assert_ne!(base, hmac_signature(b"other", 1_700_000_000, "POST", "/auth", body));
+    assert_ne!(base, hmac_signature(SECRET.as_bytes(), 1_700_000_001, "POST", "/auth", body));
+    assert_ne!(base, hmac_signature(SECRET.as_bytes(), 1_700_000_000, "POST", "/auth", b"{}"));
+}
+
+// Whether the request carries a valid signature for `SECRET`.
+fn signed_correctly(req: &HttpMockRequest) -> bool {
+    let header = |name: &str| {
+        req.headers.as_ref()?.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.clone())
+    };
+    let (Some(signature), Some(timestamp)) = (header(SIGNATURE_HEADER), header(SIGNATURE_TIMESTAMP_HEADER)) else {
+        return false;
+    };
+    let Ok(timestamp) = timestamp.parse() else {
+        return false;
+    };
+    let body = req.body.clone().unwrap_or_default();
+    signature == hmac_signature(SECRET.as_bytes(), timestamp, req.method.as_str(), &req.path, &body)
+}
+
+#[test]
+fn requests_are_signed_when_a_secret_is_set() {
+    let server = MockServer::start();
+    let auth = server.mock(|when, then| {
+        when.method(POST).path("/auth").matches(signed_correctly);
+        then.status(200).json_body(auth_body());
+    });
+
+    let api = client_for(&server).with_hmac_secret(Some(SECRET.into()));
+    api.login(&sample_auth()).expect("signed login");
+
+    auth.assert();
+}
+
+#[test]
+fn requests_are_unsigned_by_default() {
+    let server = MockServer::start();
+    let unsigned = server.mock(|when, then| {
+        when.method(GET).path("/health").matches(|req| {
+            req.headers.iter().flatten().all(|(k, _)| !k.eq_ignore_ascii_case(SIGNATURE_HEADER))
+        });
+        then.status(200);
+    });
+
+    client_for(&server).health_check().expect("health");
+
+    unsigned.assert();
+}
+
+// A clock that moves one second every time it is read.
+static TICKS: AtomicU64 = AtomicU64::new(1_700_000_000);
+
+fn ticking_clock() -> u64 {
+    TICKS.fetch_add(1, Ordering::SeqCst)
+}
+
+#[test]
+fn a_retried_request_is_signed_again_with_a_new_timestamp() {
+    let server = MockServer::start();
+    let first = server.mock(|when, then| {
+        when.method(POST)
+            .path("/auth")
+            .header(SIGNATURE_TIMESTAMP_HEADER, "1700000000")
+            .matches(signed_correctly);
+        then.status(503);
+    });
+    let retry = server.mock(|when, then| {
+        when.method(POST)
+            .path("/auth")
+            .header(SIGNATURE_TIMESTAMP_HEADER, "1700000001")
+            .matches(signed_correctly);
+        then.status(200).json_body(auth_body());
+    });
+
+    let api = client_for(&server)
+        .with_hmac_secret(Some(SECRET.into()))
+        .with_signing_clock(ticking_clock)
+        .with_retries(1, 4);
+    api.login(&sample_auth()).expect("login after one retry");
+
+    first.assert();
+    retry.assert();
+}
+
+#[test]
+fn uploads_are_signed_over_the_multipart_body() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(&dir, "perfil.jpg", b"\xFF\xD8\xFFfake-jpeg");
+    let server = MockServer::start();
+    let upload = server.mock(|when, then| {
+        when.method(POST).path("/upload").body_contains("fake-jpeg").matches(signed_correctly);
+        then.status(200);
+    });
+
+    let mut api = client_for(&server).with_hmac_secret(Some(SECRET.into()));
+    api.set_token(TOKEN);
+    api.upload_profile_picture(&path).expect("signed upload");
+
+    upload.assert();
+}
+
+#[test]
+fn clients_report_whether_they_sign() {
+    let server = MockServer::start();
+
+    assert!(!client_for(&server).signs_requests());
+    assert!(client_for(&server).with_hmac_secret(Some(SECRET.into())).signs_requests());
+}
@@ -0,0 +1,163 @@
+use neumodiag_cli::spinner::{
+    is_fail_fast_error, run_on, run_result_on, set_min_display_ms, spinner_style, Spinner, MIN_SPINNER_MS,
+};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Writer that keeps everything written to it for inspection.
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn plain_spinner_emits_no_control_sequences() {
+    let capture = Capture::default();
+    let mut spinner = Spinner::plain("Subiendo la imagen...", Box::new(capture.clone()));
+    for _ in 0..5 {
+        spinner.tick();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    spinner.finish();
+
+    let out = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(out, "Subiendo la imagen...\n");
+    assert!(!out.contains('\x1b') && !out.contains('\r'));
+}
+
+#[test]
+fn progress_updates_replace_the_message() {
+    let capture = Capture::default();
+    let spinner = Spinner::plain("Iniciando sesión...", Box::new(capture.clone()));
+
+    let result = run_on(spinner, |progress| {
+        progress.send("Reintentando (1/2)...".to_string()).unwrap();
+        // Give the spinner loop a chance to pick the update up.
+        std::thread::sleep(Duration::from_millis(200));
+        42
+    });
+
+    assert_eq!(result, Some(42));
+    let out = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(out, "Iniciando sesión...\nReintentando (1/2)...\n");
+}
+
+#[test]
+fn stuck_upload_is_abandoned_after_the_deadline() {
+    use httpmock::prelude::*;
+    use neumodiag_cli::api::ApiClient;
+    use neumodiag_cli::spinner::{run_on_with_deadline, Outcome};
+    use std::sync::atomic::Ordering;
+    use std::time::Instant;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/upload");
+        then.status(200).delay(Duration::from_secs(5));
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let image = dir.path().join("foto.jpg");
+    std::fs::write(&image, b"\xff\xd8\xff").unwrap();
+    let api = ApiClient::new(server.base_url()).unwrap();
+
+    let capture = Capture::default();
+    let spinner = Spinner::plain("Subiendo la imagen...", Box::new(capture.clone()));
+    let flag = Arc::new(Mutex::new(None));
+    let seen = Arc::clone(&flag);
+    let started = Instant::now();
+    let outcome = run_on_with_deadline(spinner, Duration::from_millis(300), move |abandoned| {
+        *seen.lock().unwrap() = Some(Arc::clone(&abandoned));
+        api.with_abandon_flag(abandoned).upload_profile_picture(&image)
+    });
+
+    assert!(matches!(outcome, Outcome::TimedOut));
+    assert!(started.elapsed() < Duration::from_secs(3));
+    let abandoned = flag.lock().unwrap().clone().expect("worker started");
+    assert!(abandoned.load(Ordering::SeqCst));
+}
+
+#[test]
+fn invalid_spinner_template_falls_back_to_the_default() {
+    let (style, warning) = spinner_style("{spinner} {msg:abc}");
+    let warning = warning.expect("a warning for the bad template");
+    assert!(warning.contains("plantilla de spinner inválida"), "{}", warning);
+
+    // The fallback style is usable.
+    let pb = indicatif::ProgressBar::hidden();
+    pb.set_style(style);
+    pb.set_message("Subiendo la imagen...");
+    pb.tick();
+    pb.finish_and_clear();
+
+    assert!(spinner_style("{spinner:.green} {msg}").1.is_none());
+}
+
+// Tests that depend on the minimum display time hold this lock, so one
+// changing it can't race with another.
+static MIN_DISPLAY: Mutex<()> = Mutex::new(());
+
+#[test]
+fn fast_results_skip_the_minimum_display_time() {
+    use std::time::Instant;
+
+    let _guard = MIN_DISPLAY.lock().unwrap_or_else(|e| e.into_inner());
+    set_min_display_ms(0);
+    let started = Instant::now();
+    assert_eq!(run_on(Spinner::animated("Verificando sesión..."), |_| 7), Some(7));
+    let fast = started.elapsed();
+
+    set_min_display_ms(MIN_SPINNER_MS);
+    let started = Instant::now();
+    assert_eq!(run_on(Spinner::animated("Verificando sesión..."), |_| 7), Some(7));
+    let normal = started.elapsed();
+
+    assert!(fast < Duration::from_millis(500), "{:?}", fast);
+    assert!(normal >= Duration::from_millis(MIN_SPINNER_MS), "{:?}", normal);
+}
+
+#[test]
+fn fail_fast_errors_skip_the_minimum_display_time() {
+    use std::time::Instant;
+
+    let _guard = MIN_DISPLAY.lock().unwrap_or_else(|e| e.into_inner());
+    // A port nobody listens on: the connection is refused right away.
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let api = neumodiag_cli::api::ApiClient::new(format!("http://127.0.0.1:{}", port)).unwrap();
+
+    let started = Instant::now();
+    let res = run_result_on(Spinner::animated("Verificando sesión..."), move |_| api.get_capabilities()).unwrap();
+    let failed = started.elapsed();
+    assert!(is_fail_fast_error(&res.err().unwrap()));
+
+    let started = Instant::now();
+    let res = run_result_on(Spinner::animated("Verificando sesión..."), |_| Ok(7)).unwrap();
+    let succeeded = started.elapsed();
+
+    assert_eq!(res.unwrap(), 7);
+    assert!(failed < Duration::from_millis(MIN_SPINNER_MS), "{:?}", failed);
+    assert!(succeeded >= Duration::from_millis(MIN_SPINNER_MS), "{:?}", succeeded);
+}
+
+#[test]
+fn only_setup_errors_fail_fast() {
+    let bad_url = neumodiag_cli::api::ApiClient::new("http://gw example").unwrap().get_capabilities().unwrap_err();
+    assert!(is_fail_fast_error(&bad_url), "{:#}", bad_url);
+
+    let server = httpmock::MockServer::start();
+    server.mock(|when, then| {
+        when.any_request();
+        then.status(500);
+    });
+    let api = neumodiag_cli::api::ApiClient::new(server.base_url()).unwrap();
+    let server_error = api.get_capabilities().unwrap_err();
+    assert!(!is_fail_fast_error(&server_error), "{:#}", server_error);
+}
@@ -0,0 +1,23 @@
+use neumodiag_cli::stats::{SessionStats, StatsSnapshot, SummaryFormat};
+
+#[test]
+fn stats_accumulate_across_operations() {
+    let stats = SessionStats::new();
+    assert_eq!(stats.snapshot(), StatsSnapshot::default());
+
+    stats.record_login();
+    stats.record_upload(false);
+    stats.record_error();
+    stats.record_upload(true);
+    stats.record_login();
+
+    let snap = stats.snapshot();
+    assert_eq!(snap, StatsSnapshot { logins: 2, uploads_attempted: 2, uploads_succeeded: 1, errors: 1 });
+
+    let text = snap.render(SummaryFormat::Text).unwrap();
+    assert!(text.contains("2 inicio(s) de sesión"), "{}", text);
+    assert!(text.contains("1/2 subida(s)"), "{}", text);
+    let json: serde_json::Value = serde_json::from_str(&snap.render(SummaryFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["uploads_succeeded"], 1);
+    assert_eq!(snap.render(SummaryFormat::None), None);
+}
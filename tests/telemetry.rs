@@ -0,0 +1,29 @@
+use neumodiag_cli::telemetry::{load_usage, Telemetry};
+
+#[test]
+fn disabled_telemetry_is_a_no_op() {
+    let dir = tempfile::tempdir().unwrap();
+    let telemetry = Telemetry::new(false, dir.path());
+
+    assert!(!telemetry.first_run_notice().unwrap());
+    telemetry.record("Iniciar sesión");
+
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn enabled_telemetry_counts_actions_and_shows_the_notice_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let telemetry = Telemetry::new(true, dir.path());
+
+    assert!(telemetry.first_run_notice().unwrap());
+    assert!(!telemetry.first_run_notice().unwrap());
+    telemetry.record("Iniciar sesión");
+    telemetry.record("Ver perfil");
+    telemetry.record("Iniciar sesión");
+
+    let usage = load_usage(dir.path()).unwrap();
+    assert_eq!(usage.counts["Iniciar sesión"], 2);
+    assert_eq!(usage.counts["Ver perfil"], 1);
+    assert_eq!(usage.counts.len(), 2);
+}
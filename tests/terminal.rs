@@ -0,0 +1,95 @@
+use neumodiag_cli::terminal::{wrap_title, AnsiTerminal, PlainTerminal, Terminal};
+
+#[test]
+fn plain_terminal_emits_no_control_sequences() {
+    let mut out = Vec::new();
+    {
+        let mut term = PlainTerminal::new(&mut out);
+        term.clear_previous_lines(3);
+        term.clear_line();
+        term.show_cursor();
+        term.rule(80);
+    }
+    assert!(out.is_empty(), "{:?}", String::from_utf8_lossy(&out));
+
+    let mut out = Vec::new();
+    PlainTerminal::new(&mut out).centered("Registro", 80);
+    assert_eq!(String::from_utf8(out).unwrap(), "Registro\n");
+}
+
+#[test]
+fn ansi_terminal_writes_escape_sequences() {
+    let mut out = Vec::new();
+    AnsiTerminal::new(&mut out).clear_previous_lines(1);
+    assert!(out.starts_with(b"\x1b["), "{:?}", String::from_utf8_lossy(&out));
+}
+
+fn banner(title: &str, width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    {
+        let mut term = AnsiTerminal::new(&mut out);
+        term.centered(title, width);
+        term.rule(width);
+    }
+    String::from_utf8(out).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn short_titles_are_centered_on_one_line() {
+    let lines = banner("Menú", 20);
+
+    assert_eq!(lines, ["        Menú        ", "===================="]);
+}
+
+#[test]
+fn overlong_titles_wrap_within_the_rule() {
+    let lines = banner("NeumoDiagnostics - Resumen de registro del paciente", 20);
+
+    let rule = lines.last().unwrap().chars().count();
+    assert_eq!(rule, 20);
+    assert!(lines.len() > 2, "{:?}", lines);
+    for line in &lines {
+        assert!(line.chars().count() <= rule, "{:?} is wider than the rule", line);
+    }
+    let words: Vec<&str> = lines[..lines.len() - 1].iter().flat_map(|l| l.split_whitespace()).collect();
+    assert_eq!(words.join(" "), "NeumoDiagnostics - Resumen de registro del paciente");
+}
+
+#[test]
+fn wrap_title_breaks_at_spaces_and_cuts_long_words() {
+    assert_eq!(wrap_title("uno dos tres", 7), ["uno dos", "tres"]);
+    assert_eq!(wrap_title("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    assert_eq!(wrap_title("corto", 80), ["corto"]);
+    assert_eq!(wrap_title("", 80), [""]);
+}
+
+#[test]
+fn warnings_are_yellow_only_on_a_terminal() {
+    let mut out = Vec::new();
+    PlainTerminal::new(&mut out).warning("la imagen fue recomprimida");
+    assert_eq!(String::from_utf8(out).unwrap(), "Aviso: la imagen fue recomprimida\n");
+
+    let mut out = Vec::new();
+    AnsiTerminal::new(&mut out).warning("la imagen fue recomprimida");
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("\x1b["), "{:?}", text);
+    assert!(text.contains("Aviso: la imagen fue recomprimida"), "{:?}", text);
+}
+
+#[test]
+fn bell_rings_only_when_enabled_on_a_terminal() {
+    use neumodiag_cli::terminal::ring_bell;
+
+    let mut out = Vec::new();
+    ring_bell(&mut AnsiTerminal::new(&mut out), true);
+    assert_eq!(out, b"\x07");
+
+    let mut out = Vec::new();
+    ring_bell(&mut AnsiTerminal::new(&mut out), false);
+    assert!(out.is_empty(), "{:?}", out);
+
+    // Not a TTY: never, even when enabled.
+    let mut out = Vec::new();
+    ring_bell(&mut PlainTerminal::new(&mut out), true);
+    assert!(out.is_empty(), "{:?}", out);
+}
@@ -0,0 +1,127 @@
+use neumodiag_cli::api::{ApiClient, RememberFor, TokenMeta, TOKEN_FILE_NAME, TOKEN_META_FILE_NAME, TOKEN_META_VERSION};
+use neumodiag_cli::state::{AppState, StateStore, STATE_FILE_NAME, STATE_VERSION};
+
+const TOKEN: &str = "header.payload.signature";
+
+fn client_in(dir: &std::path::Path) -> ApiClient {
+    ApiClient::new("http://localhost:8081".to_string()).unwrap().with_state_dir(dir)
+}
+
+#[test]
+fn missing_meta_keys_take_defaults() {
+    let meta = TokenMeta::from_json(r#"{"clean_exit": true}"#).unwrap();
+    assert_eq!(meta, TokenMeta { version: TOKEN_META_VERSION, persist: false, clean_exit: true, expires_at: None, last_seen: None });
+
+    let empty = TokenMeta::from_json("{}").unwrap();
+    assert!(!empty.persist && !empty.clean_exit);
+
+    // Keys added by a newer CLI are ignored, not an error.
+    let newer = TokenMeta::from_json(r#"{"version": 1, "persist": true, "device": "x"}"#).unwrap();
+    assert!(newer.persist);
+}
+
+#[test]
+fn state_round_trips_through_the_store() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = StateStore::new(dir.path());
+    let state = AppState {
+        version: STATE_VERSION,
+        token: Some(TOKEN.into()),
+        meta: Some(TokenMeta { version: TOKEN_META_VERSION, persist: true, clean_exit: true, expires_at: None, last_seen: None }),
+    };
+
+    store.save(&state).unwrap();
+    assert_eq!(store.load().unwrap(), state);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    store.clear();
+    assert_eq!(store.load().unwrap(), AppState { version: STATE_VERSION, ..AppState::default() });
+}
+
+#[test]
+fn legacy_two_file_layout_is_migrated() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(TOKEN_FILE_NAME), TOKEN).unwrap();
+    // Unversioned meta, as written before `version` existed.
+    std::fs::write(dir.path().join(TOKEN_META_FILE_NAME), r#"{"persist":true,"clean_exit":true}"#).unwrap();
+    let api = client_in(dir.path());
+
+    assert_eq!(api.load_token_from_project().unwrap().as_deref(), Some(TOKEN));
+    let meta = api.load_token_meta().unwrap().expect("meta");
+    assert_eq!(meta, TokenMeta { version: TOKEN_META_VERSION, persist: true, clean_exit: true, expires_at: None, last_seen: None });
+
+    // The next save moves everything into the state file.
+    api.set_clean_exit_meta(false).unwrap();
+    let names: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, [STATE_FILE_NAME]);
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.path().join(STATE_FILE_NAME)).unwrap()).unwrap();
+    assert_eq!(saved["version"], STATE_VERSION);
+    assert_eq!(saved["token"], TOKEN);
+    assert_eq!(saved["meta"]["persist"], true);
+    assert_eq!(saved["meta"]["clean_exit"], false);
+}
+
+#[test]
+fn remember_window_follows_the_chosen_option() {
+    const NOW: u64 = 1_700_000_000;
+    const DAY: u64 = 86_400;
+
+    let always = RememberFor::Always.meta(NOW);
+    assert!(always.persist && always.within_remember_window(NOW + 365 * DAY));
+
+    let week = RememberFor::Days(7).meta(NOW);
+    assert!(week.persist);
+    assert!(week.within_remember_window(NOW + 7 * DAY - 1));
+    assert!(!week.within_remember_window(NOW + 7 * DAY));
+
+    let day = RememberFor::Days(1).meta(NOW);
+    assert!(day.within_remember_window(NOW + DAY - 1));
+    assert!(!day.within_remember_window(NOW + DAY + 1));
+
+    assert!(!RememberFor::No.meta(NOW).persist);
+    let labels: Vec<String> = RememberFor::CHOICES.iter().map(|c| c.label()).collect();
+    assert_eq!(labels, ["Siempre", "7 días", "1 día", "No"]);
+}
+
+#[test]
+fn refreshed_token_keeps_the_remember_window() {
+    let dir = tempfile::tempdir().unwrap();
+    let api = client_in(dir.path());
+    api.persist_token_with_meta(TOKEN, RememberFor::Days(1).meta(1_000)).unwrap();
+
+    let meta = api.load_token_meta().unwrap().expect("meta");
+    api.persist_token_with_meta("new.token.value", meta).unwrap();
+    assert_eq!(api.load_token_meta().unwrap().expect("meta").expires_at, Some(1_000 + 86_400));
+}
+
+#[test]
+fn unclean_exit_is_forgiven_only_within_the_grace() {
+    use std::time::Duration;
+    const NOW: u64 = 1_700_000_000;
+    let grace = Some(Duration::from_secs(300));
+
+    let crashed = TokenMeta { last_seen: Some(NOW - 120), ..TokenMeta::new(true) };
+    assert!(crashed.may_auto_login(NOW, grace));
+    assert!(!crashed.may_auto_login(NOW + 600, grace));
+    // Without a grace only a clean exit counts.
+    assert!(!crashed.may_auto_login(NOW, None));
+    assert!(!TokenMeta::new(true).may_auto_login(NOW, grace));
+    assert!(TokenMeta { clean_exit: true, ..TokenMeta::new(true) }.may_auto_login(NOW, None));
+}
+
+#[test]
+fn last_seen_is_recorded_only_for_a_saved_session() {
+    let dir = tempfile::tempdir().unwrap();
+    let api = client_in(dir.path());
+    api.touch_last_seen(1_000).unwrap();
+    assert_eq!(api.load_token_meta().unwrap(), None);
+
+    api.persist_token_to_project(TOKEN, true).unwrap();
+    api.touch_last_seen(2_000).unwrap();
+    api.set_clean_exit_meta(false).unwrap();
+    assert_eq!(api.load_token_meta().unwrap().expect("meta").last_seen, Some(2_000));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{auth_body, client_for, sample_auth, TOKEN};
+use httpmock::prelude::*;
+
+fn logged_in_server() -> MockServer {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(200).json_body(auth_body());
+    });
+    server
+}
+
+#[test]
+fn persisted_token_is_written_to_state_dir() {
+    let server = logged_in_server();
+    let dir = tempfile::tempdir().unwrap();
+    let api = client_for(&server).with_state_dir(dir.path());
+
+    let resp = api.login(&sample_auth()).expect("login");
+    api.persist_token_to_project(&resp.token, true).unwrap();
+
+    assert_eq!(api.load_token_from_project().unwrap().as_deref(), Some(TOKEN));
+    assert!(dir.path().join(".neumodiag_state.json").exists());
+}
+
+#[test]
+fn a_saved_token_that_cannot_be_sent_is_reported_on_load() {
+    let server = logged_in_server();
+    let dir = tempfile::tempdir().unwrap();
+    let api = client_for(&server).with_state_dir(dir.path());
+    api.persist_token_to_project("abc\u{7f}def", true).unwrap();
+
+    let err = api.load_token_from_project().unwrap_err().to_string();
+
+    assert!(err.contains("token inválido"), "{}", err);
+}
+
+#[test]
+fn ephemeral_mode_writes_nothing_to_disk() {
+    let server = logged_in_server();
+    let dir = tempfile::tempdir().unwrap();
+    let mut api = client_for(&server).with_state_dir(dir.path()).with_ephemeral(true);
+
+    let resp = api.login(&sample_auth()).expect("login");
+    api.set_token(&resp.token);
+    api.persist_token_to_project(&resp.token, true).unwrap();
+    api.set_clean_exit_meta(true).unwrap();
+
+    assert!(api.has_token());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    assert!(api.load_token_from_project().unwrap().is_none());
+
+    api.clear_token();
+    api.clear_persisted_token_in_project();
+    assert!(!api.has_token());
+}
+
+fn cli(args: &[&str]) -> neumodiag_cli::cli::Cli {
+    use clap::Parser;
+    neumodiag_cli::cli::Cli::try_parse_from(std::iter::once("neumodiag-cli").chain(args.iter().copied())).unwrap()
+}
+
+#[test]
+fn token_store_none_writes_nothing_after_login() {
+    let server = logged_in_server();
+    let dir = tempfile::tempdir().unwrap();
+    // Only this test resolves the project folder through the environment.
+    std::env::set_var("NEUMODIAG_CONFIG_DIR", dir.path());
+    std::env::set_var("NEUMODIAG_PASSWORD", "s3creta-larga");
+    let config = neumodiag_cli::config::Config { gateway_url: Some(server.base_url()), ..Default::default() };
+    let cli = cli(&["--token-store", "none", "login", "--email", "ana@example.com", "--remember"]);
+
+    let api = cli.build_api_client(&config).unwrap();
+    assert!(api.is_ephemeral());
+    neumodiag_cli::commands::run(api, cli.command.unwrap(), &Default::default()).expect("login");
+
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn token_store_values_are_validated() {
+    use clap::Parser;
+    assert!(neumodiag_cli::cli::Cli::try_parse_from(["neumodiag-cli", "--token-store", "disk"]).is_err());
+
+    let config = neumodiag_cli::config::Config::default();
+    let err = cli(&["--token-store", "keyring"]).build_api_client(&config).err().expect("keyring is rejected");
+    assert!(err.to_string().contains("keyring"), "{}", err);
+    assert!(!cli(&["--token-store", "file"]).build_api_client(&config).unwrap().is_ephemeral());
+}
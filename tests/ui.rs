@@ -0,0 +1,437 @@
+mod common;
+
+use anyhow::Result;
+use common::*;
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use httpmock::prelude::*;
+use neumodiag_cli::api::{Capabilities, Role};
+use neumodiag_cli::ui::{
+    confirm_destructive, confirm_routine, consent_revocation_warning, drain_input, login_error_message, menu_items,
+    next_action, prompt_password, select_until_idle, wait_for_key, Clock, InputEvents, MenuAction, UiOptions,
+    MAX_DRAINED_EVENTS, NO_TTY_PASSWORD_MSG,
+};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// Time that only passes while the fake terminal waits for input.
+#[derive(Clone)]
+struct FakeClock(Rc<Cell<Instant>>);
+
+impl FakeClock {
+    fn new() -> Self {
+        FakeClock(Rc::new(Cell::new(Instant::now())))
+    }
+
+    fn advance(&self, by: Duration) {
+        self.0.set(self.0.get() + by);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        self.0.get()
+    }
+}
+
+// Input arriving at fixed delays: each event comes `delay` after the
+// previous one.
+struct ScriptedEvents {
+    clock: FakeClock,
+    queue: VecDeque<(Duration, Event)>,
+}
+
+impl ScriptedEvents {
+    fn new(clock: &FakeClock, events: &[(u64, Event)]) -> Self {
+        let queue = events.iter().map(|(secs, e)| (Duration::from_secs(*secs), e.clone())).collect();
+        ScriptedEvents { clock: clock.clone(), queue }
+    }
+}
+
+impl InputEvents for ScriptedEvents {
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<Event>> {
+        match self.queue.front_mut() {
+            Some((delay, _)) if *delay <= timeout => {
+                self.clock.advance(*delay);
+                Ok(self.queue.pop_front().map(|(_, e)| e))
+            }
+            Some((delay, _)) => {
+                *delay -= timeout;
+                self.clock.advance(timeout);
+                Ok(None)
+            }
+            None => {
+                self.clock.advance(timeout);
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn key(code: KeyCode) -> Event {
+    Event::Key(KeyEvent::from(code))
+}
+
+const WINDOW: Duration = Duration::from_secs(5);
+
+#[test]
+fn idle_countdown_expires_without_a_key_press() {
+    let clock = FakeClock::new();
+    let start = clock.now();
+    // A resize is not activity.
+    let mut events = ScriptedEvents::new(&clock, &[(2, Event::Resize(80, 24)), (9, key(KeyCode::Enter))]);
+
+    assert!(wait_for_key(&clock, &mut events, WINDOW).unwrap().is_none());
+    assert_eq!(clock.now() - start, WINDOW);
+}
+
+#[test]
+fn key_press_before_the_deadline_is_activity() {
+    let clock = FakeClock::new();
+    let start = clock.now();
+    let mut events = ScriptedEvents::new(&clock, &[(4, key(KeyCode::Char('x')))]);
+
+    let pressed = wait_for_key(&clock, &mut events, WINDOW).unwrap().expect("key press");
+
+    assert_eq!(pressed.code, KeyCode::Char('x'));
+    assert_eq!(clock.now() - start, Duration::from_secs(4));
+}
+
+#[test]
+fn idle_menu_restarts_the_window_on_every_key() {
+    let items = ["Registrarse", "Iniciar sesión", "Salir"];
+    let clock = FakeClock::new();
+    // 12 s in total, but never 5 s without a key.
+    let mut events =
+        ScriptedEvents::new(&clock, &[(4, key(KeyCode::Down)), (4, key(KeyCode::Down)), (4, key(KeyCode::Enter))]);
+    let mut drawn = Vec::new();
+
+    let picked = select_until_idle(&clock, &mut events, &items, WINDOW, |current| {
+        drawn.push(current);
+        Ok(())
+    });
+
+    assert_eq!(picked.unwrap(), Some(2));
+    assert_eq!(drawn, vec![0, 1, 2]);
+
+    // The countdown still runs inside the menu.
+    let mut events = ScriptedEvents::new(&clock, &[(4, key(KeyCode::Down)), (6, key(KeyCode::Enter))]);
+    assert_eq!(select_until_idle(&clock, &mut events, &items, WINDOW, |_| Ok(())).unwrap(), None);
+}
+
+#[test]
+fn capabilities_select_the_menu_entries() {
+    let caps: Capabilities =
+        serde_json::from_str(r#"{"features": ["Upload", "refresh"]}"#).unwrap();
+
+    assert_eq!(
+        menu_items(false, Some(&caps), false, false),
+        vec!["Iniciar sesión", "Historial de sesión", "Borrar datos locales", "Salir"]
+    );
+    assert_eq!(
+        menu_items(true, Some(&caps), false, false),
+        vec![
+            "Ver perfil",
+            "Subir foto de perfil",
+            "Renovar sesión",
+            "Cerrar sesión",
+            "Historial de sesión",
+            "Borrar datos locales",
+            "Salir",
+        ]
+    );
+
+    let none: Capabilities = serde_json::from_str("{}").unwrap();
+    assert_eq!(
+        menu_items(true, Some(&none), false, false),
+        vec!["Ver perfil", "Cerrar sesión", "Historial de sesión", "Borrar datos locales", "Salir"]
+    );
+
+    // Discovery failed: every entry is offered.
+    assert_eq!(
+        menu_items(false, None, false, false),
+        vec!["Registrarse", "Iniciar sesión", "Historial de sesión", "Borrar datos locales", "Salir"]
+    );
+    assert_eq!(
+        menu_items(true, None, false, false),
+        vec![
+            "Ver perfil",
+            "Subir foto de perfil",
+            "Descargar mi foto",
+            "Verificar sesión",
+            "Renovar sesión",
+            "Revocar consentimiento",
+            "Cerrar sesión",
+            "Historial de sesión",
+            "Borrar datos locales",
+            "Salir",
+        ]
+    );
+}
+
+#[test]
+fn yes_answers_routine_prompts_with_their_default_only() {
+    let yes = UiOptions { assume_yes: true, ..Default::default() };
+    let never = || -> Result<bool> { panic!("--yes must not ask") };
+
+    // "Continuar/Cancelar" preselects continuing, "¿Recordar?" preselects No.
+    assert!(confirm_routine(&yes, true, never).unwrap());
+    assert!(!confirm_routine(&yes, false, never).unwrap());
+
+    // Destructive confirmations are still asked, and the answer stands.
+    let mut asked = false;
+    assert!(!confirm_destructive(&yes, || {
+        asked = true;
+        Ok(false)
+    })
+    .unwrap());
+    assert!(asked);
+
+    // Without --yes the routine prompt is asked too.
+    let interactive = UiOptions::default();
+    assert!(!confirm_routine(&interactive, true, || Ok(false)).unwrap());
+}
+
+#[test]
+fn drain_drops_queued_input_but_not_later_keys() {
+    let clock = FakeClock::new();
+    let mut events = ScriptedEvents::new(
+        &clock,
+        &[(0, key(KeyCode::Enter)), (0, key(KeyCode::Enter)), (0, key(KeyCode::Down)), (1, key(KeyCode::Up))],
+    );
+
+    assert_eq!(drain_input(&mut events).unwrap(), 3);
+    assert_eq!(events.queue.len(), 1);
+    assert_eq!(drain_input(&mut events).unwrap(), 0);
+}
+
+#[test]
+fn drain_stops_at_the_cap() {
+    let clock = FakeClock::new();
+    let held_down: Vec<(u64, Event)> = (0..1000).map(|_| (0, key(KeyCode::Char('x')))).collect();
+    let mut events = ScriptedEvents::new(&clock, &held_down);
+
+    assert_eq!(drain_input(&mut events).unwrap(), MAX_DRAINED_EVENTS);
+    assert_eq!(events.queue.len(), 1000 - MAX_DRAINED_EVENTS);
+}
+
+#[test]
+fn consent_warning_spells_out_patient_consequences() {
+    let patient = consent_revocation_warning(Some(Role::Paciente));
+    let other = consent_revocation_warning(Some(Role::Unknown));
+    let unknown = consent_revocation_warning(None);
+
+    assert!(patient.contains("cuenta quedará limitada"), "{}", patient);
+    assert!(patient.contains("No podrá subir nuevas imágenes"), "{}", patient);
+    assert!(other.contains("cuenta quedará limitada"), "{}", other);
+    assert!(!other.contains("No podrá subir"), "{}", other);
+    assert_eq!(other, unknown);
+}
+
+#[test]
+fn login_failure_reason_is_shown_only_with_auth_debug() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(401).json_body(serde_json::json!({"error": "usuario no encontrado"}));
+    });
+    let err = client_for(&server).login(&sample_auth()).expect_err("login should fail");
+
+    let generic = login_error_message(&err, false, false);
+    assert_eq!(generic, "Credenciales inválidas: correo o contraseña incorrectos.");
+
+    let debug = login_error_message(&err, true, false);
+    assert!(debug.starts_with("Credenciales inválidas"), "{}", debug);
+    assert!(debug.contains("Motivo del servidor: usuario no encontrado"), "{}", debug);
+}
+
+fn actions(items: &[&str], is_logged: bool) -> Vec<MenuAction> {
+    (0..items.len()).map(|i| next_action(items, i, is_logged)).collect()
+}
+
+#[test]
+fn logged_out_menu_maps_to_account_actions() {
+    let items = menu_items(false, None, false, false);
+    assert_eq!(
+        actions(&items, false),
+        [MenuAction::Register, MenuAction::Login, MenuAction::SessionHistory, MenuAction::ResetLocalData, MenuAction::Exit]
+    );
+}
+
+#[test]
+fn logged_in_menu_maps_to_session_actions() {
+    let items = menu_items(true, None, true, false);
+    assert_eq!(
+        actions(&items, true),
+        [
+            MenuAction::ViewProfile,
+            MenuAction::UploadPicture,
+            MenuAction::DownloadPicture,
+            MenuAction::VerifySession,
+            MenuAction::RefreshSession,
+            MenuAction::RevokeConsent,
+            MenuAction::Logout,
+            MenuAction::CopyLastError,
+            MenuAction::SessionHistory,
+            MenuAction::ResetLocalData,
+            MenuAction::Exit,
+        ]
+    );
+
+    // Capabilities hide entries; the remaining ones still map correctly.
+    let caps = Capabilities { features: vec!["verify".into()], ..Default::default() };
+    let items = menu_items(true, Some(&caps), false, false);
+    assert_eq!(
+        actions(&items, true),
+        [
+            MenuAction::ViewProfile,
+            MenuAction::VerifySession,
+            MenuAction::Logout,
+            MenuAction::SessionHistory,
+            MenuAction::ResetLocalData,
+            MenuAction::Exit,
+        ]
+    );
+}
+
+#[test]
+fn read_only_menu_hides_entries_that_change_server_data() {
+    let items = menu_items(true, None, false, true);
+    assert_eq!(
+        actions(&items, true),
+        [
+            MenuAction::ViewProfile,
+            MenuAction::DownloadPicture,
+            MenuAction::VerifySession,
+            MenuAction::RefreshSession,
+            MenuAction::Logout,
+            MenuAction::SessionHistory,
+            MenuAction::ResetLocalData,
+            MenuAction::Exit,
+        ]
+    );
+    let items = menu_items(false, None, false, true);
+    assert_eq!(actions(&items, false), [MenuAction::Login, MenuAction::SessionHistory, MenuAction::ResetLocalData, MenuAction::Exit]);
+}
+
+#[test]
+fn session_actions_need_a_login_and_bad_selections_are_ignored() {
+    let items = menu_items(true, None, false, false);
+    // Session-only entries are refused without a session.
+    assert_eq!(next_action(&items, 1, false), MenuAction::LoginRequired);
+    assert_eq!(next_action(&items, items.len() - 1, false), MenuAction::Exit);
+    assert_eq!(next_action(&items, items.len(), true), MenuAction::Ignore);
+    assert_eq!(next_action(&["Otra opción"], 0, true), MenuAction::Ignore);
+}
+
+#[test]
+fn multi_error_bodies_are_routed_to_registration_fields() {
+    use neumodiag_cli::api::{ApiError, RegisterField};
+    use neumodiag_cli::ui::route_register_errors;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/register");
+        then.status(422).json_body(serde_json::json!({"errors": [
+            {"field": "correo", "message": "ya está registrado"},
+            {"field": "Password", "message": "demasiado corta"},
+            {"field": "telefono", "message": "no se admite"},
+            "revise los datos"
+        ]}));
+    });
+    let err = client_for(&server).register(&sample_register()).expect_err("register should fail");
+    let api_err = err.downcast_ref::<ApiError>().expect("ApiError");
+
+    let errors = api_err.field_errors();
+    assert_eq!(errors.len(), 4);
+    let (routed, other) = route_register_errors(&errors);
+    assert_eq!(
+        routed,
+        [
+            (RegisterField::Correo, "ya está registrado".to_string()),
+            (RegisterField::Contrasena, "demasiado corta".to_string()),
+        ]
+    );
+    let other: Vec<String> = other.iter().map(|e| e.to_string()).collect();
+    assert_eq!(other, ["telefono: no se admite", "revise los datos"]);
+
+    // Listed together when shown as a single message.
+    let all = api_err.server_message().unwrap();
+    assert!(all.contains("correo: ya está registrado; Password: demasiado corta"), "{}", all);
+}
+
+#[test]
+fn other_error_shapes_have_no_field_errors() {
+    use neumodiag_cli::api::ApiError;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/register");
+        then.status(400).json_body(serde_json::json!({"error": "datos inválidos"}));
+    });
+    let err = client_for(&server).register(&sample_register()).expect_err("register should fail");
+    let api_err = err.downcast_ref::<ApiError>().expect("ApiError");
+
+    assert!(api_err.field_errors().is_empty());
+    assert_eq!(api_err.server_message().as_deref(), Some("datos inválidos"));
+}
+
+#[test]
+fn password_prompt_without_terminal_points_to_the_subcommand() {
+    let err = prompt_password("Contraseña", false).unwrap_err();
+    assert_eq!(err.to_string(), NO_TTY_PASSWORD_MSG);
+    assert!(NO_TTY_PASSWORD_MSG.contains("NEUMODIAG_PASSWORD"));
+    assert!(NO_TTY_PASSWORD_MSG.contains("login --email"));
+}
+
+#[test]
+fn cancelled_registration_fields_are_offered_again_without_the_password() {
+    use neumodiag_cli::api::{RegisterDraft, RegisterField};
+    let mut draft = RegisterDraft::default();
+    assert!(draft.is_empty());
+    assert_eq!(draft.initial_text(RegisterField::Correo), None);
+
+    // A registration cancelled at the confirmation step.
+    draft.remember(&sample_register());
+
+    assert_eq!(draft.initial_text(RegisterField::NombreCompleto).as_deref(), Some("Ana Pérez"));
+    assert_eq!(draft.initial_text(RegisterField::Edad).as_deref(), Some("34"));
+    assert_eq!(draft.initial_text(RegisterField::Rol).as_deref(), Some("paciente"));
+    assert_eq!(draft.initial_text(RegisterField::Identificacion).as_deref(), Some("1020304050"));
+    assert_eq!(draft.initial_text(RegisterField::Correo).as_deref(), Some("ana@example.com"));
+    assert_eq!(draft.initial_text(RegisterField::AceptaTratamientoDatos).as_deref(), Some("Sí"));
+    assert_eq!(draft.initial_text(RegisterField::Contrasena), None);
+    assert!(!format!("{:?}", draft).contains("s3creta-larga"));
+}
+
+#[test]
+fn verbose_error_messages_include_the_underlying_cause() {
+    use neumodiag_cli::ui::error_message;
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/profile");
+        then.status(200).header("content-type", "application/json").body("{\"nombre\": ");
+    });
+    let err = client_for(&server).get_profile().expect_err("truncated json");
+
+    assert_eq!(error_message(&err, false), "Parsing profile json");
+    let verbose = error_message(&err, true);
+    assert!(verbose.starts_with("Parsing profile json\n  causa: "), "{}", verbose);
+    assert!(verbose.contains("EOF"), "{}", verbose);
+}
+
+#[test]
+fn verbose_login_failures_other_than_credentials_include_the_cause() {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(POST).path("/auth");
+        then.status(200).header("content-type", "application/json").body("{\"nombre\": ");
+    });
+    let err = client_for(&server).login(&sample_auth()).expect_err("truncated json");
+
+    assert_eq!(login_error_message(&err, false, false), "Fallo al iniciar sesión: Parsing auth response json");
+    let verbose = login_error_message(&err, false, true);
+    assert!(verbose.starts_with("Fallo al iniciar sesión: Parsing auth response json\n  causa: "), "{}", verbose);
+}
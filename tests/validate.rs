@@ -0,0 +1,74 @@
+use neumodiag_cli::api::{FieldLimits, RegisterField};
+use neumodiag_cli::validate::{split_csv_line, validate_csv};
+
+const HEADER: &str = "nombre_completo,edad,rol,identificacion,correo,contrasena,acepta_tratamiento_datos";
+
+fn fields(csv: &str) -> Vec<(usize, Vec<RegisterField>)> {
+    validate_csv(csv, &FieldLimits::default())
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.line, r.problems.into_iter().map(|(f, _)| f).collect()))
+        .collect()
+}
+
+#[test]
+fn valid_rows_have_no_problems() {
+    let csv = format!(
+        "{}\nAna Pérez,34,paciente,1020304050,ana@example.com,s3creta-larga,sí\n\"Gómez, Luis\",51,Doctor,CC-99.1,luis@clinica.co,otra-clave,true\n",
+        HEADER
+    );
+
+    assert_eq!(fields(&csv), [(2, vec![]), (3, vec![])]);
+}
+
+#[test]
+fn invalid_rows_report_each_failing_field() {
+    let csv = format!(
+        "{}\n,34,paciente,1020304050,ana-at-example.com,s3creta-larga,no\nLuis,abc,enfermero,12 34,luis@clinica,,quizás\n\nEva,200,paciente,77,eva@example.com,clave,1\n",
+        HEADER
+    );
+
+    use RegisterField::*;
+    assert_eq!(
+        fields(&csv),
+        [
+            (2, vec![NombreCompleto, Correo, AceptaTratamientoDatos]),
+            (3, vec![Edad, Rol, Identificacion, Correo, Contrasena, AceptaTratamientoDatos]),
+            (5, vec![Edad]),
+        ]
+    );
+}
+
+#[test]
+fn messages_explain_the_problem() {
+    let csv = format!("{}\nLuis,abc,paciente,1,luis@clinica.co,clave,si\n", HEADER);
+
+    let report = &validate_csv(&csv, &FieldLimits::default()).unwrap()[0];
+
+    assert_eq!(report.problems, [(RegisterField::Edad, "Debe ser un número entero (se ingresó 'abc').".to_string())]);
+}
+
+#[test]
+fn length_limits_apply() {
+    let limits = FieldLimits { correo: 10, ..FieldLimits::default() };
+    let csv = format!("{}\nAna,30,paciente,1,ana@example.com,clave,si\n", HEADER);
+
+    let report = &validate_csv(&csv, &limits).unwrap()[0];
+
+    assert_eq!(report.problems.len(), 1);
+    assert!(report.problems[0].1.contains("Máximo 10 caracteres"), "{:?}", report.problems);
+}
+
+#[test]
+fn missing_columns_are_an_error() {
+    let err = validate_csv("nombre_completo,edad\nAna,30\n", &FieldLimits::default()).unwrap_err().to_string();
+
+    assert!(err.contains("Faltan columnas"), "{}", err);
+    assert!(err.contains("Correo electrónico"), "{}", err);
+    assert!(validate_csv("", &FieldLimits::default()).is_err());
+}
+
+#[test]
+fn quoted_values_keep_commas_and_quotes() {
+    assert_eq!(split_csv_line(r#"a,"b, c","say ""hi""",,d"#), ["a", "b, c", "say \"hi\"", "", "d"]);
+}
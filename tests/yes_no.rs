@@ -0,0 +1,26 @@
+use neumodiag_cli::yes_no::{parse_yes_no, Lang};
+
+#[test]
+fn spanish_accepts_accented_unaccented_and_english_forms() {
+    for yes in ["s", "S", "si", "SI", "sí", "Sí", "SÍ", " si ", "y", "Yes"] {
+        assert_eq!(parse_yes_no(yes, Lang::Es), Some(true), "{:?}", yes);
+    }
+    for no in ["n", "N", "no", "NO", " No\n"] {
+        assert_eq!(parse_yes_no(no, Lang::Es), Some(false), "{:?}", no);
+    }
+}
+
+#[test]
+fn english_accepts_only_english_forms() {
+    assert_eq!(parse_yes_no("yes", Lang::En), Some(true));
+    assert_eq!(parse_yes_no("Y", Lang::En), Some(true));
+    assert_eq!(parse_yes_no("no", Lang::En), Some(false));
+    assert_eq!(parse_yes_no("sí", Lang::En), None);
+}
+
+#[test]
+fn anything_else_is_not_an_answer() {
+    for other in ["", "   ", "quizás", "sii", "nope", "1"] {
+        assert_eq!(parse_yes_no(other, Lang::Es), None, "{:?}", other);
+    }
+}